@@ -0,0 +1,22 @@
+-- Refresh tokens rotativos: solo se guarda el hash SHA-256 del token
+CREATE TABLE refresh_tokens (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    producer_id UUID NOT NULL REFERENCES producers(id) ON DELETE CASCADE,
+    session_id UUID NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE CHECK (length(token_hash) = 64),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Lista de revocación consultada por los middlewares de autenticación
+CREATE TABLE revoked_sessions (
+    session_id UUID PRIMARY KEY,
+    producer_id UUID NOT NULL REFERENCES producers(id) ON DELETE CASCADE,
+    revoked_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_refresh_tokens_producer_id ON refresh_tokens(producer_id);
+CREATE INDEX idx_refresh_tokens_session_id ON refresh_tokens(session_id);
+CREATE INDEX idx_revoked_sessions_expires_at ON revoked_sessions(expires_at);
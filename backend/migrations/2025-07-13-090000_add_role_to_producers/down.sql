@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_producers_role;
+ALTER TABLE producers DROP COLUMN role;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE producers ADD COLUMN role role_enum NOT NULL DEFAULT 'Producer';
+CREATE INDEX idx_producers_role ON producers(role) WHERE role <> 'Producer';
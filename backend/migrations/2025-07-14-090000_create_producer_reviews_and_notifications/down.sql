@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_producers_status;
+DROP TRIGGER IF EXISTS update_notifications_timestamp ON notifications;
+DROP TABLE IF EXISTS notifications;
+DROP TABLE IF EXISTS producer_reviews;
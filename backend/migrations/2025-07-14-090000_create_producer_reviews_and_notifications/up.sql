@@ -0,0 +1,35 @@
+-- Historial de decisiones de aprobación de productores
+CREATE TABLE producer_reviews (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    producer_id UUID NOT NULL REFERENCES producers(id) ON DELETE CASCADE,
+    reviewed_by UUID NOT NULL REFERENCES producers(id),
+    approved BOOLEAN NOT NULL,
+    reason TEXT CHECK (reason IS NULL OR length(reason) >= 3),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT check_rejection_reason CHECK (approved OR reason IS NOT NULL)
+);
+
+-- Notificaciones dirigidas a un productor
+CREATE TABLE notifications (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES producers(id) ON DELETE CASCADE,
+    title TEXT NOT NULL CHECK (length(title) >= 3),
+    message TEXT NOT NULL,
+    notification_type TEXT NOT NULL,
+    is_read BOOLEAN NOT NULL DEFAULT false,
+    metadata JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER update_notifications_timestamp
+    BEFORE UPDATE ON notifications
+    FOR EACH ROW EXECUTE FUNCTION update_timestamp();
+
+CREATE INDEX idx_producer_reviews_producer_id ON producer_reviews(producer_id);
+CREATE INDEX idx_notifications_user_id ON notifications(user_id);
+CREATE INDEX idx_notifications_unread ON notifications(user_id) WHERE is_read = false;
+CREATE INDEX idx_producers_status ON producers(status) WHERE status = 'pending';
+
+-- Las cuentas existentes ya operaban sin aprobación; no las bloqueamos
+UPDATE producers SET status = 'approved' WHERE status = 'pending';
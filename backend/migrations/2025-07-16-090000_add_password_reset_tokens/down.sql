@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM account_tokens WHERE purpose = 'password_reset';
+ALTER TABLE account_tokens DROP CONSTRAINT account_tokens_purpose_check;
+ALTER TABLE account_tokens ADD CONSTRAINT account_tokens_purpose_check
+    CHECK (purpose IN ('email_verification'));
@@ -0,0 +1,4 @@
+-- Los tokens de reseteo de contraseña reutilizan account_tokens
+ALTER TABLE account_tokens DROP CONSTRAINT account_tokens_purpose_check;
+ALTER TABLE account_tokens ADD CONSTRAINT account_tokens_purpose_check
+    CHECK (purpose IN ('email_verification', 'password_reset'));
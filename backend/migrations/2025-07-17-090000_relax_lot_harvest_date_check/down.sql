@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS check_lots_estimated_harvest_date ON lots;
+DROP FUNCTION IF EXISTS check_estimated_harvest_date();
+ALTER TABLE lots ADD CONSTRAINT lots_estimated_harvest_date_check
+    CHECK (estimated_harvest_date >= CURRENT_DATE) NOT VALID;
@@ -0,0 +1,18 @@
+-- El CHECK original se reevalúa en cada UPDATE, lo que impedía cambiar de estado
+-- un lote cuya fecha estimada ya pasó. La regla solo debe aplicar al fijar la fecha.
+ALTER TABLE lots DROP CONSTRAINT lots_estimated_harvest_date_check;
+
+CREATE OR REPLACE FUNCTION check_estimated_harvest_date()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.estimated_harvest_date < CURRENT_DATE THEN
+        RAISE EXCEPTION 'estimated_harvest_date must not be in the past'
+            USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER check_lots_estimated_harvest_date
+    BEFORE INSERT OR UPDATE OF estimated_harvest_date ON lots
+    FOR EACH ROW EXECUTE FUNCTION check_estimated_harvest_date();
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS protect_sealed_events ON events;
+DROP FUNCTION IF EXISTS protect_sealed_events();
+DROP INDEX IF EXISTS idx_events_corrects_event_id;
+DROP INDEX IF EXISTS idx_events_lot_sequence;
+ALTER TABLE events
+    DROP CONSTRAINT IF EXISTS events_sealed_check,
+    DROP COLUMN IF EXISTS corrects_event_id,
+    DROP COLUMN IF EXISTS hash,
+    DROP COLUMN IF EXISTS prev_hash,
+    DROP COLUMN IF EXISTS sequence;
@@ -0,0 +1,38 @@
+-- Cadena de hashes por lote: cada evento sellado guarda su posición, el hash del
+-- anterior y el SHA-256 de su JSON canónico (calculado en el backend).
+ALTER TABLE events
+    ADD COLUMN sequence BIGINT,
+    ADD COLUMN prev_hash TEXT,
+    ADD COLUMN hash TEXT,
+    ADD COLUMN corrects_event_id UUID REFERENCES events(id),
+    ADD CONSTRAINT events_sealed_check CHECK ((sequence IS NULL) = (hash IS NULL));
+
+CREATE UNIQUE INDEX idx_events_lot_sequence ON events(lot_id, sequence);
+-- Cada evento se corrige como mucho una vez; las correcciones sucesivas encadenan
+CREATE UNIQUE INDEX idx_events_corrects_event_id ON events(corrects_event_id)
+    WHERE corrects_event_id IS NOT NULL;
+
+-- Los eventos sellados no se modifican, y solo se borran junto con su lote.
+-- Los eventos existentes quedan sin sellar y se sellan en el siguiente alta del lote.
+CREATE OR REPLACE FUNCTION protect_sealed_events()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        IF OLD.hash IS NOT NULL THEN
+            RAISE EXCEPTION 'event % is sealed, append a correction instead', OLD.id
+                USING ERRCODE = 'integrity_constraint_violation';
+        END IF;
+        RETURN NEW;
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM lots WHERE id = OLD.lot_id) THEN
+        RAISE EXCEPTION 'event % cannot be deleted, append a correction instead', OLD.id
+            USING ERRCODE = 'integrity_constraint_violation';
+    END IF;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER protect_sealed_events
+    BEFORE UPDATE OR DELETE ON events
+    FOR EACH ROW EXECUTE FUNCTION protect_sealed_events();
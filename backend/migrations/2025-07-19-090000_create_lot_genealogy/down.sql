@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+CREATE OR REPLACE FUNCTION check_estimated_harvest_date()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.estimated_harvest_date < CURRENT_DATE THEN
+        RAISE EXCEPTION 'estimated_harvest_date must not be in the past'
+            USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TABLE IF EXISTS lot_genealogy;
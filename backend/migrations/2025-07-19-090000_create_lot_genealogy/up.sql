@@ -0,0 +1,28 @@
+-- Relaciones padre → hijo entre lotes al dividir o fusionar. La cantidad es la que
+-- sale del lote padre hacia el hijo, en la unidad de ambos lotes.
+CREATE TABLE lot_genealogy (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    parent_lot_id UUID NOT NULL REFERENCES lots(id) ON DELETE CASCADE,
+    child_lot_id UUID NOT NULL REFERENCES lots(id) ON DELETE CASCADE,
+    operation TEXT NOT NULL CHECK (operation IN ('split', 'merge')),
+    quantity NUMERIC(12,3) NOT NULL CHECK (quantity > 0),
+    created_by UUID NOT NULL REFERENCES producers(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT lot_genealogy_distinct_lots CHECK (parent_lot_id <> child_lot_id),
+    CONSTRAINT lot_genealogy_unique_link UNIQUE (parent_lot_id, child_lot_id)
+);
+
+CREATE INDEX idx_lot_genealogy_child ON lot_genealogy(child_lot_id);
+
+-- Los lotes derivados nacen ya cosechados con la fecha estimada del original, que
+-- puede estar en el pasado: la regla solo aplica a lotes aún sin cosechar.
+CREATE OR REPLACE FUNCTION check_estimated_harvest_date()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.actual_harvest_date IS NULL AND NEW.estimated_harvest_date < CURRENT_DATE THEN
+        RAISE EXCEPTION 'estimated_harvest_date must not be in the past'
+            USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS recall_notifications;
+DROP TABLE IF EXISTS recalls;
+DROP TABLE IF EXISTS shipments;
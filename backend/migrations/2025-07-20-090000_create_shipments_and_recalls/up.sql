@@ -0,0 +1,49 @@
+-- Envíos de un lote a un comprador. La cantidad sale de la disponible del lote.
+CREATE TABLE shipments (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    lot_id UUID NOT NULL REFERENCES lots(id) ON DELETE CASCADE,
+    buyer_name TEXT NOT NULL CHECK (length(buyer_name) >= 2),
+    buyer_email TEXT,
+    quantity NUMERIC(12,3) NOT NULL CHECK (quantity > 0),
+    shipped_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    reference TEXT,
+    created_by UUID NOT NULL REFERENCES producers(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_shipments_lot_id ON shipments(lot_id);
+
+-- Retiradas de producto. El informe es la foto de lotes y partes afectadas en el
+-- momento de iniciarla; no cambia aunque después se registren más envíos.
+CREATE TABLE recalls (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    initiated_by UUID NOT NULL REFERENCES producers(id),
+    reason TEXT NOT NULL CHECK (length(reason) >= 3),
+    criteria JSONB NOT NULL,
+    report JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_recalls_initiated_by ON recalls(initiated_by);
+
+-- Cola de avisos por correo a cada parte afectada
+CREATE TABLE recall_notifications (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    recall_id UUID NOT NULL REFERENCES recalls(id) ON DELETE CASCADE,
+    party_type TEXT NOT NULL CHECK (party_type IN ('producer', 'buyer')),
+    recipient_name TEXT NOT NULL,
+    recipient_email TEXT,
+    producer_id UUID REFERENCES producers(id) ON DELETE SET NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at TIMESTAMPTZ,
+    CONSTRAINT check_pending_has_email CHECK (status <> 'pending' OR recipient_email IS NOT NULL)
+);
+
+CREATE INDEX idx_recall_notifications_recall_id ON recall_notifications(recall_id);
+CREATE INDEX idx_recall_notifications_pending ON recall_notifications(created_at)
+    WHERE status = 'pending';
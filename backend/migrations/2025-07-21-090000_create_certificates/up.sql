@@ -0,0 +1,13 @@
+-- Certificados de trazabilidad emitidos. `document` es el certificado firmado tal
+-- cual se entregó; `head_hash` permite saber después si el lote tiene eventos nuevos.
+CREATE TABLE certificates (
+    id UUID PRIMARY KEY,
+    lot_id UUID NOT NULL REFERENCES lots(id) ON DELETE CASCADE,
+    key_id TEXT NOT NULL,
+    head_hash TEXT,
+    document JSONB NOT NULL,
+    issued_by UUID NOT NULL REFERENCES producers(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_certificates_lot_id ON certificates(lot_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE lots DROP COLUMN IF EXISTS plot_id;
+DROP TABLE IF EXISTS plots;
@@ -0,0 +1,24 @@
+-- Parcelas de cultivo. `boundary` es un Polygon GeoJSON en WGS84 ([lon, lat]) ya
+-- validado y normalizado; la superficie y el centroide los calcula el servidor.
+CREATE TABLE plots (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    producer_id UUID NOT NULL REFERENCES producers(id) ON DELETE CASCADE,
+    name TEXT NOT NULL CHECK (length(name) >= 2),
+    boundary JSONB NOT NULL,
+    area_hectares NUMERIC(12,4) NOT NULL CHECK (area_hectares > 0),
+    centroid POINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER update_plots_timestamp
+    BEFORE UPDATE ON plots
+    FOR EACH ROW EXECUTE FUNCTION update_timestamp();
+
+CREATE INDEX idx_plots_producer_id ON plots(producer_id);
+
+-- El lote apunta a su parcela; `location_coordinates` se mantiene como su centroide.
+-- Al borrar la parcela el lote conserva el último centroide.
+ALTER TABLE lots ADD COLUMN plot_id UUID REFERENCES plots(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_lots_plot_id ON lots(plot_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION IF EXISTS point_in_bbox(POINT, DOUBLE PRECISION, DOUBLE PRECISION, DOUBLE PRECISION, DOUBLE PRECISION);
+DROP FUNCTION IF EXISTS haversine_km(POINT, POINT);
+DROP INDEX IF EXISTS idx_lots_location_coordinates;
@@ -0,0 +1,24 @@
+-- Búsqueda de lotes por ubicación. POINT guarda (longitud, latitud) en grados.
+CREATE INDEX idx_lots_location_coordinates ON lots USING gist (location_coordinates);
+
+-- Distancia por la superficie de la esfera en km (radio medio IUGG).
+CREATE OR REPLACE FUNCTION haversine_km(a POINT, b POINT)
+RETURNS DOUBLE PRECISION AS $$
+    SELECT 2 * 6371.0088 * asin(least(1, sqrt(
+        power(sin(radians(b[1] - a[1]) / 2), 2)
+        + cos(radians(a[1])) * cos(radians(b[1])) * power(sin(radians(b[0] - a[0]) / 2), 2)
+    )))
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE;
+
+-- Función SQL simple para que el planificador la expanda y use el índice GiST.
+-- El recuadro no debe cruzar el antimeridiano; quien llama lo divide antes.
+CREATE OR REPLACE FUNCTION point_in_bbox(
+    p POINT,
+    min_lon DOUBLE PRECISION,
+    min_lat DOUBLE PRECISION,
+    max_lon DOUBLE PRECISION,
+    max_lat DOUBLE PRECISION
+)
+RETURNS BOOLEAN AS $$
+    SELECT p <@ box(point(min_lon, min_lat), point(max_lon, max_lat))
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE;
@@ -0,0 +1,19 @@
+-- Kilos por caja o saco de cada producto, configurados por el productor. Con ellos
+-- se normalizan a masa los totales de lotes en `box` y `sack`.
+CREATE TABLE packaging_weights (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    producer_id UUID NOT NULL REFERENCES producers(id) ON DELETE CASCADE,
+    product_name TEXT NOT NULL CHECK (length(trim(product_name)) >= 2),
+    unit TEXT NOT NULL CHECK (unit IN ('box', 'sack')),
+    kg_per_unit NUMERIC(12,3) NOT NULL CHECK (kg_per_unit > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER update_packaging_weights_timestamp
+    BEFORE UPDATE ON packaging_weights
+    FOR EACH ROW EXECUTE FUNCTION update_timestamp();
+
+-- Un peso por producto y envase; el producto se compara igual que en la aplicación
+CREATE UNIQUE INDEX idx_packaging_weights_product
+    ON packaging_weights(producer_id, lower(trim(product_name)), unit);
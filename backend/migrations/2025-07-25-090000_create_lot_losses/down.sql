@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS lot_losses;
+ALTER TABLE lots DROP COLUMN IF EXISTS actual_quantity;
@@ -0,0 +1,18 @@
+-- Cantidad realmente cosechada, en la unidad del lote. Se registra al pasar a
+-- HARVESTED o después; cero es una cosecha perdida por completo.
+ALTER TABLE lots ADD COLUMN actual_quantity NUMERIC(12,3) CHECK (actual_quantity >= 0);
+
+-- Pérdidas de un lote, antes o después de la cosecha. No se editan: forman parte
+-- del historial del lote junto con su evento.
+CREATE TABLE lot_losses (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    lot_id UUID NOT NULL REFERENCES lots(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL CHECK (reason IN ('WEATHER', 'PESTS', 'REJECTION', 'SPOILAGE')),
+    quantity NUMERIC(12,3) NOT NULL CHECK (quantity > 0),
+    notes TEXT,
+    occurred_on DATE NOT NULL DEFAULT CURRENT_DATE,
+    created_by UUID NOT NULL REFERENCES producers(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_lot_losses_lot_id ON lot_losses(lot_id);
@@ -0,0 +1,29 @@
+-- This file should undo anything in `up.sql`
+CREATE OR REPLACE FUNCTION protect_sealed_events()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        IF OLD.hash IS NOT NULL THEN
+            RAISE EXCEPTION 'event % is sealed, append a correction instead', OLD.id
+                USING ERRCODE = 'integrity_constraint_violation';
+        END IF;
+        RETURN NEW;
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM lots WHERE id = OLD.lot_id) THEN
+        RAISE EXCEPTION 'event % cannot be deleted, append a correction instead', OLD.id
+            USING ERRCODE = 'integrity_constraint_violation';
+    END IF;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE events
+    DROP CONSTRAINT events_lot_id_fkey,
+    ADD CONSTRAINT events_lot_id_fkey FOREIGN KEY (lot_id) REFERENCES lots(id) ON DELETE CASCADE;
+
+DROP INDEX IF EXISTS idx_lots_live;
+ALTER TABLE events DROP COLUMN IF EXISTS deleted_at;
+ALTER TABLE lots
+    DROP COLUMN IF EXISTS deleted_at,
+    DROP COLUMN IF EXISTS archived_at;
@@ -0,0 +1,35 @@
+-- Los lotes ya no se borran: se marcan con deleted_at y un admin los puede
+-- restaurar. Sus eventos se marcan con el mismo instante para restaurarlos juntos.
+ALTER TABLE lots
+    ADD COLUMN archived_at TIMESTAMPTZ,
+    ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE events ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX idx_lots_live ON lots(producer_id, created_at DESC)
+    WHERE deleted_at IS NULL AND archived_at IS NULL;
+
+-- Un borrado físico del lote ya no se lleva por delante su historial
+ALTER TABLE events
+    DROP CONSTRAINT events_lot_id_fkey,
+    ADD CONSTRAINT events_lot_id_fkey FOREIGN KEY (lot_id) REFERENCES lots(id) ON DELETE RESTRICT;
+
+-- deleted_at no forma parte del hash, así que es lo único que cambia en un evento sellado
+CREATE OR REPLACE FUNCTION protect_sealed_events()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        IF OLD.hash IS NOT NULL
+            AND to_jsonb(NEW) - 'deleted_at' IS DISTINCT FROM to_jsonb(OLD) - 'deleted_at' THEN
+            RAISE EXCEPTION 'event % is sealed, append a correction instead', OLD.id
+                USING ERRCODE = 'integrity_constraint_violation';
+        END IF;
+        RETURN NEW;
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM lots WHERE id = OLD.lot_id) THEN
+        RAISE EXCEPTION 'event % cannot be deleted, append a correction instead', OLD.id
+            USING ERRCODE = 'integrity_constraint_violation';
+    END IF;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,14 @@
+-- Una foto del lote por cada edición. La versión 1 guarda el estado anterior a la
+-- primera edición, así que no tiene autor; recorded_at es desde cuándo rige.
+CREATE TABLE lot_versions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    lot_id UUID NOT NULL REFERENCES lots(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL CHECK (version > 0),
+    snapshot JSONB NOT NULL,
+    changed_fields TEXT[] NOT NULL DEFAULT '{}',
+    actor_id UUID REFERENCES producers(id),
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT lot_versions_actor_check CHECK (version = 1 OR actor_id IS NOT NULL)
+);
+
+CREATE UNIQUE INDEX idx_lot_versions_lot_version ON lot_versions(lot_id, version);
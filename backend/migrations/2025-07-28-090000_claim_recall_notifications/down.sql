@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_recall_notifications_sending;
+
+UPDATE recall_notifications SET status = 'pending' WHERE status = 'sending';
+
+ALTER TABLE recall_notifications DROP CONSTRAINT check_pending_has_email;
+ALTER TABLE recall_notifications ADD CONSTRAINT check_pending_has_email
+    CHECK (status <> 'pending' OR recipient_email IS NOT NULL);
+
+ALTER TABLE recall_notifications DROP CONSTRAINT recall_notifications_status_check;
+ALTER TABLE recall_notifications ADD CONSTRAINT recall_notifications_status_check
+    CHECK (status IN ('pending', 'sent', 'failed'));
+
+ALTER TABLE recall_notifications DROP COLUMN claimed_at;
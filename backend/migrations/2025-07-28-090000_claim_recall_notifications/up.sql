@@ -0,0 +1,15 @@
+-- Un aviso reclamado por el despachador pasa a 'sending' mientras se envía, fuera
+-- de cualquier transacción. Si el proceso muere a mitad, claimed_at permite
+-- volver a reclamarlo pasado un tiempo.
+ALTER TABLE recall_notifications ADD COLUMN claimed_at TIMESTAMPTZ;
+
+ALTER TABLE recall_notifications DROP CONSTRAINT recall_notifications_status_check;
+ALTER TABLE recall_notifications ADD CONSTRAINT recall_notifications_status_check
+    CHECK (status IN ('pending', 'sending', 'sent', 'failed'));
+
+ALTER TABLE recall_notifications DROP CONSTRAINT check_pending_has_email;
+ALTER TABLE recall_notifications ADD CONSTRAINT check_pending_has_email
+    CHECK (status NOT IN ('pending', 'sending') OR recipient_email IS NOT NULL);
+
+CREATE INDEX idx_recall_notifications_sending ON recall_notifications(claimed_at)
+    WHERE status = 'sending';
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS check_lots_actual_harvest_date ON lots;
+DROP FUNCTION IF EXISTS check_actual_harvest_date();
+ALTER TABLE lots ADD CONSTRAINT lots_actual_harvest_date_check
+    CHECK (actual_harvest_date IS NULL OR actual_harvest_date >= CURRENT_DATE - INTERVAL '1 year') NOT VALID;
@@ -0,0 +1,22 @@
+-- Como pasó con la fecha estimada, el CHECK de la fecha real se reevalúa en cada
+-- UPDATE y, pasado un año de la cosecha, impedía archivar o borrar el lote. La
+-- regla solo debe aplicar al fijar o cambiar la fecha.
+ALTER TABLE lots DROP CONSTRAINT lots_actual_harvest_date_check;
+
+CREATE OR REPLACE FUNCTION check_actual_harvest_date()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW.actual_harvest_date IS NOT DISTINCT FROM OLD.actual_harvest_date THEN
+        RETURN NEW;
+    END IF;
+    IF NEW.actual_harvest_date < CURRENT_DATE - INTERVAL '1 year' THEN
+        RAISE EXCEPTION 'actual_harvest_date must not be more than a year in the past'
+            USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER check_lots_actual_harvest_date
+    BEFORE INSERT OR UPDATE OF actual_harvest_date ON lots
+    FOR EACH ROW EXECUTE FUNCTION check_actual_harvest_date();
@@ -1,55 +1,174 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpResponse};
 use crate::{
+    auth::{middleware::ProducerAuthMiddleware, password_reset, session, verification, Claims},
+    config::AppConfig,
     database::DbPool,
     errors::AppError,
-    models::producer::Producer,
-    auth::jwt::create_token,
+    etag,
+    mail::Mailer,
+    models::{notification::Notification, producer::Producer},
 };
-use kairos_common::{LoginRequest, TokenResponse, RegisterProducerRequest};
+use kairos_common::{
+    LoginRequest, PasswordResetConfirmRequest, PasswordResetRequest, RefreshTokenRequest,
+    RegisterProducerRequest, VerifyEmailRequest,
+};
+
+pub fn configure() -> actix_web::Scope {
+    web::scope("/auth")
+        .route("/register", web::post().to(register))
+        .route("/login", web::post().to(login))
+        .route("/refresh", web::post().to(refresh))
+        .route("/verify-email", web::post().to(verify_email))
+        .route("/password-reset/request", web::post().to(request_password_reset))
+        .route("/password-reset/confirm", web::post().to(confirm_password_reset))
+        .service(
+            web::resource("/resend-verification")
+                .wrap(ProducerAuthMiddleware::allow_unapproved())
+                .route(web::post().to(resend_verification)),
+        )
+        .service(
+            web::resource("/logout")
+                .wrap(ProducerAuthMiddleware::allow_unapproved())
+                .route(web::post().to(logout)),
+        )
+        .service(
+            web::resource("/me")
+                .wrap(ProducerAuthMiddleware::allow_unapproved())
+                .route(web::get().to(me)),
+        )
+        .service(
+            web::resource("/me/notifications")
+                .wrap(ProducerAuthMiddleware::allow_unapproved())
+                .route(web::get().to(my_notifications)),
+        )
+}
 
 pub async fn login(
     pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
     request: web::Json<LoginRequest>,
 ) -> Result<HttpResponse, AppError> {
     let mut conn = pool.get().map_err(AppError::from)?;
-    
+
     let producer = Producer::authenticate(&mut conn, &request.email, &request.password)?;
-    
-    let token = create_token(&producer.id.to_string(), &producer.email, "producer", "your-secret-key")?;
-    
-    Ok(HttpResponse::Ok().json(TokenResponse {
-        access_token: token.access_token,
-        token_type: token.token_type,
-        expires_in: token.expires_in,
-    }))
+
+    let token = session::issue_tokens(&mut conn, &config, &producer)?;
+
+    Ok(HttpResponse::Ok().json(token))
 }
 
 pub async fn register(
     pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    mailer: web::Data<dyn Mailer>,
     request: web::Json<RegisterProducerRequest>,
 ) -> Result<HttpResponse, AppError> {
     let mut conn = pool.get().map_err(AppError::from)?;
-    
+
     // Verificar si el email ya existe
     if Producer::find_by_email(&mut conn, &request.email).is_ok() {
         return Err(AppError::BadRequest("Email already exists".to_string()));
     }
-    
+
     let producer = Producer::create(&mut conn, request.into_inner())?;
-    
-    let token = create_token(&producer.id.to_string(), &producer.email, "producer", "your-secret-key")?;
-    
-    Ok(HttpResponse::Created().json(TokenResponse {
-        access_token: token.access_token,
-        token_type: token.token_type,
-        expires_in: token.expires_in,
-    }))
+
+    // Un fallo de correo no debe impedir el registro; el productor puede pedir el reenvío
+    if let Err(e) = verification::send_verification_email(&mut conn, &config, mailer.get_ref(), &producer) {
+        tracing::error!(producer_id = %producer.id, "failed to send verification email: {}", e);
+    }
+
+    let token = session::issue_tokens(&mut conn, &config, &producer)?;
+
+    Ok(HttpResponse::Created().json(token))
+}
+
+pub async fn refresh(
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    request: web::Json<RefreshTokenRequest>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get().map_err(AppError::from)?;
+
+    let token = session::rotate_refresh_token(&mut conn, &config, &request.refresh_token)?;
+
+    Ok(HttpResponse::Ok().json(token))
+}
+
+pub async fn verify_email(
+    pool: web::Data<DbPool>,
+    request: web::Json<VerifyEmailRequest>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get().map_err(AppError::from)?;
+
+    verification::verify_email(&mut conn, &request.token)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Email verified successfully"
+    })))
+}
+
+pub async fn resend_verification(
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    mailer: web::Data<dyn Mailer>,
+    producer: web::ReqData<Producer>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get().map_err(AppError::from)?;
+
+    verification::resend_verification_email(&mut conn, &config, mailer.get_ref(), &producer)?;
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "message": "Verification email sent"
+    })))
+}
+
+pub async fn request_password_reset(
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    mailer: web::Data<dyn Mailer>,
+    request: web::Json<PasswordResetRequest>,
+) -> Result<HttpResponse, AppError> {
+    // La respuesta no revela si el email está registrado, ni por su contenido ni por
+    // lo que tarda: la búsqueda, el token y el correo se hacen después de responder
+    let email = request.into_inner().email;
+    actix_web::rt::task::spawn_blocking(move || {
+        let result = pool.get().map_err(AppError::from).and_then(|mut conn| {
+            password_reset::request_password_reset(&mut conn, &config, mailer.get_ref(), &email)
+        });
+        if let Err(e) = result {
+            tracing::error!("password reset request failed: {}", e);
+        }
+    });
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "message": "If the email is registered, a reset link has been sent"
+    })))
+}
+
+pub async fn confirm_password_reset(
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    request: web::Json<PasswordResetConfirmRequest>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get().map_err(AppError::from)?;
+
+    password_reset::confirm_password_reset(&mut conn, &config, &request.token, &request.new_password)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Password updated, please log in again"
+    })))
 }
 
 pub async fn logout(
-    _pool: web::Data<DbPool>,
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    claims: web::ReqData<Claims>,
 ) -> Result<HttpResponse, AppError> {
-    // En una implementación real, aquí invalidarías el token
+    let mut conn = pool.get().map_err(AppError::from)?;
+    let claims = claims.into_inner();
+
+    session::revoke_session(&mut conn, &config, claims.sub, claims.sid)?;
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Logged out successfully"
     })))
@@ -60,5 +179,18 @@ pub async fn me(
     producer: web::ReqData<Producer>,
 ) -> Result<HttpResponse, AppError> {
     let producer = producer.into_inner();
-    Ok(HttpResponse::Ok().json(producer))
-} 
\ No newline at end of file
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag::entity_tag(producer.updated_at)))
+        .json(producer))
+}
+
+pub async fn my_notifications(
+    pool: web::Data<DbPool>,
+    producer: web::ReqData<Producer>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get().map_err(AppError::from)?;
+
+    let notifications = Notification::find_by_user(&mut conn, producer.id)?;
+
+    Ok(HttpResponse::Ok().json(notifications))
+}
@@ -1,25 +1,31 @@
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use chrono::{Duration, Utc};
-use crate::errors::AppError;
+use crate::{errors::AppError, models::role::Role};
 use uuid::Uuid;
-use std::time::{SystemTime, UNIX_EPOCH};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
 use kairos_common::TokenResponse;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
     pub exp: i64,
     pub iat: i64,
+    /// Sesión a la que pertenece el token; es lo que se revoca en logout.
+    pub sid: Uuid,
+    pub role: Role,
 }
 
 impl Claims {
-    pub fn new(user_id: Uuid, expiration: i64) -> Self {
+    pub fn new(user_id: Uuid, session_id: Uuid, role: Role, expiration: i64) -> Self {
         let now = Utc::now();
         Self {
             sub: user_id,
             exp: (now + Duration::seconds(expiration)).timestamp(),
             iat: now.timestamp(),
+            sid: session_id,
+            role,
         }
     }
 
@@ -35,49 +41,46 @@ impl Claims {
         decode::<Claims>(
             token,
             &DecodingKey::from_secret(secret.as_bytes()),
-            &Validation::default(),
+            &Validation::new(Algorithm::HS256),
         )
         .map(|data| data.claims)
     }
 }
 
+/// Emite un access token de corta duración. El refresh token lo añade `auth::session`.
 pub fn create_token(
-    user_id: &str, 
-    _email: &str, 
-    _role: &str, 
-    jwt_secret: &str
+    user_id: &str,
+    session_id: Uuid,
+    _email: &str,
+    role: Role,
+    jwt_secret: &str,
+    expires_in: i64,
 ) -> Result<TokenResponse, AppError> {
-    let expiration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as usize + (24 * 60 * 60); // 24 horas
+    let claims = Claims::new(Uuid::parse_str(user_id)?, session_id, role, expires_in);
 
-    let claims = Claims {
-        sub: Uuid::parse_str(user_id)?,
-        exp: expiration as i64,
-        iat: Utc::now().timestamp(),
-    };
-
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(jwt_secret.as_ref()),
-    )
-    .map_err(|e| AppError::JwtError(e))?;
+    let token = claims.encode(jwt_secret).map_err(AppError::JwtError)?;
 
     Ok(TokenResponse {
         access_token: token,
         token_type: "Bearer".to_string(),
-        expires_in: 24 * 60 * 60, // 24 horas en segundos
+        expires_in,
+        refresh_token: String::new(),
     })
 }
 
 pub fn validate_token(token: &str, jwt_secret: &str) -> Result<Claims, AppError> {
-    let validation = Validation::default();
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(jwt_secret.as_bytes()),
-        &validation,
-    )?;
-    Ok(token_data.claims)
-} 
\ No newline at end of file
+    Ok(Claims::decode(token, jwt_secret)?)
+}
+
+/// Genera un token opaco de 256 bits codificado en hex (refresh tokens, enlaces por email).
+pub fn generate_secure_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Los tokens opacos solo se guardan hasheados; SHA-256 basta porque el token
+/// ya tiene entropía completa y necesitamos poder buscarlo por igualdad.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
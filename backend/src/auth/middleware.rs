@@ -1,23 +1,40 @@
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    error::ErrorUnauthorized,
+    error::{ErrorForbidden, ErrorUnauthorized},
     http::header,
     Error, HttpMessage, HttpRequest,
 };
 use futures_util::future::{ready, LocalBoxFuture, Ready};
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use kairos_common::ProducerStatus;
+use std::rc::Rc;
 use crate::{
     auth::Claims,
     config::AppConfig,
-    models::producer::Producer,
+    database::DbPool,
+    models::{producer::Producer, revoked_session::RevokedSession, role::Role},
     errors::AppError,
 };
 
+// Rechaza tokens cuya sesión fue cerrada (logout, rotación comprometida, reset de contraseña)
+fn ensure_session_active(req: &ServiceRequest, claims: &Claims) -> Result<(), Error> {
+    let pool = req.app_data::<actix_web::web::Data<DbPool>>()
+        .ok_or_else(|| ErrorUnauthorized("Database pool not available"))?;
+
+    let mut conn = pool.get()
+        .map_err(|_| ErrorUnauthorized("Database connection failed"))?;
+
+    match RevokedSession::is_revoked(&mut conn, claims.sid) {
+        Ok(false) => Ok(()),
+        Ok(true) => Err(ErrorUnauthorized("Session has been revoked")),
+        Err(_) => Err(ErrorUnauthorized("Unable to verify session")),
+    }
+}
+
 pub struct AuthMiddleware;
 
 impl<S, B> Transform<S, ServiceRequest> for AuthMiddleware
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + Clone + 'static,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -28,17 +45,17 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(AuthMiddlewareService { service }))
+        ready(Ok(AuthMiddlewareService { service: Rc::new(service) }))
     }
 }
 
 pub struct AuthMiddlewareService<S> {
-    service: S,
+    service: Rc<S>,
 }
 
 impl<S, B> Service<ServiceRequest> for AuthMiddlewareService<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + Clone + 'static,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -66,14 +83,13 @@ where
         Box::pin(async move {
             if let Some(token) = auth_header {
                 if let Some(config) = config {
-                    let validation = Validation::default();
-                    let key = DecodingKey::from_secret(config.jwt_secret.as_ref());
-                    
-                    match decode::<Claims>(&token, &key, &validation) {
-                        Ok(token_data) => {
+                    match Claims::decode(&token, &config.jwt_secret) {
+                        Ok(claims) => {
+                            ensure_session_active(&req, &claims)?;
+
                             // Crear un nuevo request con los claims
                             let req = req;
-                            req.extensions_mut().insert(token_data.claims);
+                            req.extensions_mut().insert(claims);
                             let res = service.call(req).await?;
                             Ok(res)
                         }
@@ -91,11 +107,27 @@ where
     }
 }
 
-pub struct ProducerAuthMiddleware;
+/// Autentica al productor y exige que su cuenta esté aprobada.
+/// Las rutas de perfil usan `allow_unapproved()` para que una cuenta pendiente
+/// o rechazada pueda consultar su estado.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProducerAuthMiddleware {
+    allow_unapproved: bool,
+}
+
+impl ProducerAuthMiddleware {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow_unapproved() -> Self {
+        Self { allow_unapproved: true }
+    }
+}
 
 impl<S, B> Transform<S, ServiceRequest> for ProducerAuthMiddleware
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + Clone + 'static,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -106,17 +138,21 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(ProducerAuthMiddlewareService { service }))
+        ready(Ok(ProducerAuthMiddlewareService {
+            service: Rc::new(service),
+            allow_unapproved: self.allow_unapproved,
+        }))
     }
 }
 
 pub struct ProducerAuthMiddlewareService<S> {
-    service: S,
+    service: Rc<S>,
+    allow_unapproved: bool,
 }
 
 impl<S, B> Service<ServiceRequest> for ProducerAuthMiddlewareService<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + Clone + 'static,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -140,17 +176,17 @@ where
             .map(|c| c.get_ref().clone());
 
         let service = self.service.clone();
+        let allow_unapproved = self.allow_unapproved;
 
         Box::pin(async move {
             if let Some(token) = auth_header {
                 if let Some(config) = config {
-                    let validation = Validation::default();
-                    let key = DecodingKey::from_secret(config.jwt_secret.as_ref());
-                    
-                    match decode::<Claims>(&token, &key, &validation) {
-                        Ok(token_data) => {
+                    match Claims::decode(&token, &config.jwt_secret) {
+                        Ok(claims) => {
+                            ensure_session_active(&req, &claims)?;
+
                             // Extraer el producer_id del token
-                            let producer_id = match uuid::Uuid::parse_str(&token_data.claims.sub.to_string()) {
+                            let producer_id = match uuid::Uuid::parse_str(&claims.sub.to_string()) {
                                 Ok(id) => id,
                                 Err(_) => {
                                     return Err(ErrorUnauthorized("Invalid producer ID in token"));
@@ -158,7 +194,7 @@ where
                             };
 
                             // Buscar el productor en la base de datos
-                            let pool = req.app_data::<actix_web::web::Data<crate::database::DbPool>>()
+                            let pool = req.app_data::<actix_web::web::Data<DbPool>>()
                                 .ok_or_else(|| ErrorUnauthorized("Database pool not available"))?;
 
                             let mut conn = pool.get()
@@ -172,10 +208,23 @@ where
                                 return Err(ErrorUnauthorized("Producer account is inactive"));
                             }
 
+                            // Verificar que el productor fue aprobado por un administrador
+                            if !allow_unapproved {
+                                match producer.status {
+                                    ProducerStatus::Approved => {}
+                                    ProducerStatus::Pending => {
+                                        return Err(ErrorForbidden("Producer account is pending approval"));
+                                    }
+                                    ProducerStatus::Rejected => {
+                                        return Err(ErrorForbidden("Producer account was rejected"));
+                                    }
+                                }
+                            }
+
                             // Crear un nuevo request con el productor y los claims
                             let req = req;
                             req.extensions_mut().insert(producer);
-                            req.extensions_mut().insert(token_data.claims);
+                            req.extensions_mut().insert(claims);
 
                             // Continuar con el request
                             let res = service.call(req).await?;
@@ -195,10 +244,75 @@ where
     }
 }
 
+/// Restringe una ruta a ciertos roles. Lee los `Claims` que dejó `AuthMiddleware`
+/// o `ProducerAuthMiddleware`, así que debe registrarse *antes* que ellos con `.wrap()`
+/// (actix ejecuta primero el último wrap).
+pub struct RequireRole {
+    roles: Rc<[Role]>,
+}
+
+impl RequireRole {
+    pub fn new(roles: &[Role]) -> Self {
+        Self { roles: roles.into() }
+    }
+
+    pub fn admin() -> Self {
+        Self::new(&[Role::Admin])
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequireRoleService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireRoleService {
+            service,
+            roles: self.roles.clone(),
+        }))
+    }
+}
+
+pub struct RequireRoleService<S> {
+    service: S,
+    roles: Rc<[Role]>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireRoleService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let role = req.extensions().get::<Claims>().map(|claims| claims.role);
+
+        match role {
+            Some(role) if self.roles.contains(&role) => Box::pin(self.service.call(req)),
+            Some(_) => Box::pin(ready(Err(ErrorForbidden("Insufficient role")))),
+            None => Box::pin(ready(Err(ErrorUnauthorized("Missing authentication")))),
+        }
+    }
+}
+
 // Función helper para extraer el productor del request
 pub fn get_producer_from_request(req: &HttpRequest) -> Result<Producer, AppError> {
     req.extensions()
         .get::<Producer>()
         .cloned()
         .ok_or_else(|| AppError::Unauthorized("Producer not found in request".into()))
-}
\ No newline at end of file
+}
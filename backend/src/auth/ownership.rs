@@ -0,0 +1,111 @@
+use uuid::Uuid;
+use crate::{auth::Claims, errors::AppError, models::role::Role};
+
+/// Un productor solo ve sus propios recursos; los administradores ven todos.
+pub fn can_access(claims: &Claims, owner_id: Uuid) -> bool {
+    claims.role == Role::Admin || claims.sub == owner_id
+}
+
+/// Los recursos ajenos se reportan como inexistentes (404) para no revelar qué IDs existen.
+pub fn ensure_owner(claims: &Claims, owner_id: Uuid, resource: &str) -> Result<(), AppError> {
+    if can_access(claims, owner_id) {
+        Ok(())
+    } else {
+        Err(AppError::NotFound(format!("{} not found", resource)))
+    }
+}
+
+/// Resultado de buscar un recurso por id, si quien llama puede verlo. Que no exista
+/// y que sea ajeno dan el mismo 404. Es la base de los `find_owned_*` de los handlers.
+pub fn owned<T, E>(
+    claims: &Claims,
+    found: Result<T, E>,
+    owner_of: impl FnOnce(&T) -> Uuid,
+    resource: &str,
+) -> Result<T, AppError> {
+    let item = found.map_err(|_| AppError::NotFound(format!("{} not found", resource)))?;
+    ensure_owner(claims, owner_of(&item), resource)?;
+    Ok(item)
+}
+
+/// Productor cuyos datos se listan: el propio, u otro solo si quien llama es admin.
+pub fn resolve_owner(claims: &Claims, requested: Option<Uuid>) -> Result<Uuid, AppError> {
+    match requested {
+        None => Ok(claims.sub),
+        Some(owner_id) => {
+            ensure_owner(claims, owner_id, "Producer")?;
+            Ok(owner_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims_for(role: Role) -> Claims {
+        Claims::new(Uuid::new_v4(), Uuid::new_v4(), role, 900)
+    }
+
+    #[test]
+    fn producer_can_access_own_resources() {
+        let claims = claims_for(Role::Producer);
+        assert!(ensure_owner(&claims, claims.sub, "Lot").is_ok());
+        assert_eq!(resolve_owner(&claims, None).unwrap(), claims.sub);
+        assert_eq!(resolve_owner(&claims, Some(claims.sub)).unwrap(), claims.sub);
+    }
+
+    #[test]
+    fn producer_cannot_access_another_producers_resources() {
+        let claims = claims_for(Role::Producer);
+        let other = Uuid::new_v4();
+
+        assert!(!can_access(&claims, other));
+        assert!(matches!(ensure_owner(&claims, other, "Lot"), Err(AppError::NotFound(_))));
+        assert!(matches!(resolve_owner(&claims, Some(other)), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn plain_users_are_scoped_like_producers() {
+        let claims = claims_for(Role::User);
+        assert!(!can_access(&claims, Uuid::new_v4()));
+    }
+
+    /// Dos productores y un lote del primero, como lo devuelve la búsqueda por id.
+    struct FoundLot {
+        producer_id: Uuid,
+    }
+
+    fn find_owned(claims: &Claims, found: diesel::QueryResult<FoundLot>) -> Result<FoundLot, AppError> {
+        owned(claims, found, |lot| lot.producer_id, "Lot")
+    }
+
+    #[test]
+    fn owner_finds_their_lot_and_another_producer_gets_a_404() {
+        let owner = claims_for(Role::Producer);
+        let other = claims_for(Role::Producer);
+        let lot = || Ok(FoundLot { producer_id: owner.sub });
+
+        assert_eq!(find_owned(&owner, lot()).unwrap().producer_id, owner.sub);
+        assert!(find_owned(&claims_for(Role::Admin), lot()).is_ok());
+
+        let denied = match find_owned(&other, lot()) {
+            Err(AppError::NotFound(message)) => message,
+            _ => panic!("another producer must not see the lot"),
+        };
+        let missing = match find_owned(&other, Err(diesel::result::Error::NotFound)) {
+            Err(AppError::NotFound(message)) => message,
+            _ => panic!("a missing lot is a 404"),
+        };
+        assert_eq!(denied, missing);
+    }
+
+    #[test]
+    fn admin_can_access_any_producer() {
+        let claims = claims_for(Role::Admin);
+        let other = Uuid::new_v4();
+
+        assert!(ensure_owner(&claims, other, "Lot").is_ok());
+        assert_eq!(resolve_owner(&claims, Some(other)).unwrap(), other);
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use crate::{
+    auth::{jwt::{generate_secure_token, hash_token}, session},
+    config::AppConfig,
+    errors::AppError,
+    mail::{Mailer, OutgoingMail},
+    models::{
+        account_token::{AccountToken, NewAccountToken, PASSWORD_RESET},
+        producer::Producer,
+    },
+    schema::producers,
+};
+
+const TOKEN_LIFETIME_MINUTES: i64 = 60;
+const RESEND_COOLDOWN_SECONDS: i64 = 60;
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Envía un enlace de reseteo si el email corresponde a una cuenta activa.
+///
+/// El handler responde igual exista o no la cuenta, y antes de llamar aquí para
+/// que el tiempo de respuesta tampoco lo delate; los casos "no existe",
+/// "inactiva" y "demasiado pronto" simplemente no hacen nada.
+pub fn request_password_reset(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    mailer: &dyn Mailer,
+    email: &str,
+) -> Result<(), AppError> {
+    let producer = match Producer::find_by_email(conn, email) {
+        Ok(producer) if producer.is_active => producer,
+        _ => return Ok(()),
+    };
+
+    let since = Utc::now() - Duration::seconds(RESEND_COOLDOWN_SECONDS);
+    if !AccountToken::issued_since(conn, producer.id, PASSWORD_RESET, since)?.is_empty() {
+        return Ok(());
+    }
+
+    let token = generate_secure_token();
+    AccountToken::replace(conn, NewAccountToken {
+        producer_id: producer.id,
+        purpose: PASSWORD_RESET,
+        token_hash: hash_token(&token),
+        expires_at: Utc::now() + Duration::minutes(TOKEN_LIFETIME_MINUTES),
+    })?;
+
+    mailer.send(&OutgoingMail {
+        to: producer.email.clone(),
+        subject: "Restablece tu contraseña de Kairos".to_string(),
+        body: format!(
+            "Hola {},\n\nPara elegir una contraseña nueva abre el siguiente enlace:\n\n{}/reset-password?token={}\n\nEl enlace caduca en {} minutos. Si no lo pediste, ignora este correo.",
+            producer.full_name, config.app_base_url, token, TOKEN_LIFETIME_MINUTES
+        ),
+    })
+}
+
+/// Canjea el token, guarda el nuevo hash y cierra todas las sesiones del productor.
+pub fn confirm_password_reset(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    token: &str,
+    new_password: &str,
+) -> Result<(), AppError> {
+    if new_password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    let password_hash = bcrypt::hash(new_password, bcrypt::DEFAULT_COST)?;
+
+    conn.transaction::<_, AppError, _>(|conn| {
+        let reset_token = AccountToken::consume(conn, PASSWORD_RESET, &hash_token(token))
+            .map_err(|_| AppError::BadRequest("Invalid or expired reset token".into()))?;
+
+        // Haber recibido el enlace demuestra que el email es suyo
+        diesel::update(producers::table.find(reset_token.producer_id))
+            .set((
+                producers::password_hash.eq(&password_hash),
+                producers::email_verified.eq(true),
+            ))
+            .execute(conn)?;
+
+        AccountToken::invalidate(conn, reset_token.producer_id, PASSWORD_RESET)?;
+        session::revoke_all_sessions(conn, config, reset_token.producer_id)?;
+
+        Ok(())
+    })
+}
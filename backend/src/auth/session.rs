@@ -0,0 +1,119 @@
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use uuid::Uuid;
+use kairos_common::TokenResponse;
+use crate::{
+    auth::jwt::{create_token, generate_secure_token, hash_token},
+    config::AppConfig,
+    errors::AppError,
+    models::{
+        producer::Producer,
+        refresh_token::{NewRefreshToken, RefreshToken},
+        revoked_session::RevokedSession,
+        role::Role,
+    },
+};
+
+/// Abre una sesión nueva y devuelve el par access/refresh token.
+pub fn issue_tokens(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    producer: &Producer,
+) -> Result<TokenResponse, AppError> {
+    issue_for_session(conn, config, producer, Uuid::new_v4())
+}
+
+fn issue_for_session(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    producer: &Producer,
+    session_id: Uuid,
+) -> Result<TokenResponse, AppError> {
+    // El rol se lee en cada emisión para que un cambio de rol aplique en el próximo refresh
+    let role = Role::for_producer(conn, producer.id)?;
+
+    let mut token = create_token(
+        &producer.id.to_string(),
+        session_id,
+        &producer.email,
+        role,
+        &config.jwt_secret,
+        config.jwt_expiration,
+    )?;
+
+    let refresh_token = generate_secure_token();
+    RefreshToken::create(conn, NewRefreshToken {
+        producer_id: producer.id,
+        session_id,
+        token_hash: hash_token(&refresh_token),
+        expires_at: Utc::now() + Duration::seconds(config.refresh_token_expiration),
+    })?;
+
+    token.refresh_token = refresh_token;
+    Ok(token)
+}
+
+/// Canjea un refresh token por un par nuevo dentro de la misma sesión.
+///
+/// Cada refresh token es de un solo uso. Si se presenta uno ya canjeado
+/// asumimos que fue robado y revocamos la sesión completa.
+pub fn rotate_refresh_token(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    presented: &str,
+) -> Result<TokenResponse, AppError> {
+    let current = RefreshToken::find_by_hash(conn, &hash_token(presented))
+        .map_err(|_| AppError::Unauthorized("Invalid refresh token".into()))?;
+
+    if current.expires_at <= Utc::now() {
+        return Err(AppError::Unauthorized("Refresh token expired".into()));
+    }
+
+    if RefreshToken::revoke(conn, current.id)? == 0 {
+        tracing::warn!(session_id = %current.session_id, "refresh token reuse detected, revoking session");
+        revoke_session(conn, config, current.producer_id, current.session_id)?;
+        return Err(AppError::Unauthorized("Refresh token reuse detected".into()));
+    }
+
+    let producer = Producer::find_by_id(conn, current.producer_id)
+        .map_err(|_| AppError::Unauthorized("Producer not found".into()))?;
+    if !producer.is_active {
+        return Err(AppError::Unauthorized("Producer account is inactive".into()));
+    }
+
+    issue_for_session(conn, config, &producer, current.session_id)
+}
+
+/// Invalida los refresh tokens de la sesión y la añade a la lista de revocación
+/// para que los access tokens ya emitidos dejen de aceptarse.
+pub fn revoke_session(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    producer_id: Uuid,
+    session_id: Uuid,
+) -> Result<(), AppError> {
+    RefreshToken::revoke_session(conn, session_id)?;
+    RevokedSession::revoke(
+        conn,
+        producer_id,
+        session_id,
+        Utc::now() + Duration::seconds(config.jwt_expiration),
+    )?;
+    Ok(())
+}
+
+/// Cierra todas las sesiones del productor. No mira el estado de los refresh
+/// tokens: también se revocan las sesiones cuyo refresh token ya se usó o caducó,
+/// porque sus access tokens pueden seguir en circulación.
+pub fn revoke_all_sessions(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    producer_id: Uuid,
+) -> Result<(), AppError> {
+    RefreshToken::revoke_producer(conn, producer_id)?;
+    let expires_at = Utc::now() + Duration::seconds(config.jwt_expiration);
+    for session_id in RefreshToken::sessions(conn, producer_id)? {
+        RevokedSession::revoke(conn, producer_id, session_id, expires_at)?;
+    }
+    Ok(())
+}
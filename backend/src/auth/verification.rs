@@ -0,0 +1,82 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use crate::{
+    auth::jwt::{generate_secure_token, hash_token},
+    config::AppConfig,
+    errors::AppError,
+    mail::{Mailer, OutgoingMail},
+    models::{
+        account_token::{AccountToken, NewAccountToken, EMAIL_VERIFICATION},
+        producer::Producer,
+    },
+    schema::producers,
+};
+
+const TOKEN_LIFETIME_HOURS: i64 = 24;
+const RESEND_COOLDOWN_SECONDS: i64 = 60;
+const MAX_SENDS_PER_HOUR: usize = 5;
+
+/// Emite un token de verificación nuevo (invalidando los anteriores) y lo envía por correo.
+pub fn send_verification_email(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    mailer: &dyn Mailer,
+    producer: &Producer,
+) -> Result<(), AppError> {
+    let token = generate_secure_token();
+
+    AccountToken::replace(conn, NewAccountToken {
+        producer_id: producer.id,
+        purpose: EMAIL_VERIFICATION,
+        token_hash: hash_token(&token),
+        expires_at: Utc::now() + Duration::hours(TOKEN_LIFETIME_HOURS),
+    })?;
+
+    mailer.send(&OutgoingMail {
+        to: producer.email.clone(),
+        subject: "Verifica tu correo en Kairos".to_string(),
+        body: format!(
+            "Hola {},\n\nConfirma tu dirección de correo en el siguiente enlace:\n\n{}/verify-email?token={}\n\nEl enlace caduca en {} horas.",
+            producer.full_name, config.app_base_url, token, TOKEN_LIFETIME_HOURS
+        ),
+    })
+}
+
+/// Reenvío a petición del productor: como mucho uno por minuto y cinco por hora.
+pub fn resend_verification_email(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    mailer: &dyn Mailer,
+    producer: &Producer,
+) -> Result<(), AppError> {
+    if producer.email_verified {
+        return Err(AppError::BadRequest("Email is already verified".into()));
+    }
+
+    let now = Utc::now();
+    let recent = AccountToken::issued_since(conn, producer.id, EMAIL_VERIFICATION, now - Duration::hours(1))?;
+
+    if recent.len() >= MAX_SENDS_PER_HOUR {
+        return Err(AppError::TooManyRequests("Too many verification emails, try again later".into()));
+    }
+    if let Some(last) = recent.first() {
+        if now - *last < Duration::seconds(RESEND_COOLDOWN_SECONDS) {
+            return Err(AppError::TooManyRequests("Please wait before requesting another email".into()));
+        }
+    }
+
+    send_verification_email(conn, config, mailer, producer)
+}
+
+pub fn verify_email(conn: &mut PgConnection, token: &str) -> Result<(), AppError> {
+    conn.transaction::<_, AppError, _>(|conn| {
+        let account_token = AccountToken::consume(conn, EMAIL_VERIFICATION, &hash_token(token))
+            .map_err(|_| AppError::BadRequest("Invalid or expired verification token".into()))?;
+
+        diesel::update(producers::table.find(account_token.producer_id))
+            .set(producers::email_verified.eq(true))
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
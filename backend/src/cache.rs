@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Caché en memoria con caducidad fija por entrada. Se comparte entre workers como
+/// `web::Data`, así que todo el estado va detrás de un `Mutex`.
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.get_at(key, Instant::now())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.insert_at(key, value, Instant::now())
+    }
+
+    fn get_at(&self, key: &K, now: Instant) -> Option<V> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|(stored_at, _)| now.duration_since(*stored_at) < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn insert_at(&self, key: K, value: V, now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.max_entries {
+            entries.retain(|_, (stored_at, _)| now.duration_since(*stored_at) < self.ttl);
+        }
+        // Si sigue lleno, se descarta todo antes que crecer sin límite
+        if entries.len() >= self.max_entries {
+            entries.clear();
+        }
+        entries.insert(key, (now, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let cache = TtlCache::new(Duration::from_secs(60), 10);
+        let start = Instant::now();
+        cache.insert_at("LOT-1", 1, start);
+
+        assert_eq!(
+            cache.get_at(&"LOT-1", start + Duration::from_secs(59)),
+            Some(1)
+        );
+        assert_eq!(
+            cache.get_at(&"LOT-1", start + Duration::from_secs(60)),
+            None
+        );
+    }
+
+    #[test]
+    fn cache_never_exceeds_max_entries() {
+        let cache = TtlCache::new(Duration::from_secs(60), 2);
+        let now = Instant::now();
+        for i in 0..5 {
+            cache.insert_at(i, i, now);
+        }
+        assert!(cache.entries.lock().unwrap().len() <= 2);
+        assert_eq!(cache.get_at(&4, now), Some(4));
+    }
+}
@@ -0,0 +1,327 @@
+//! Firma de certificados de trazabilidad y su versión en PDF.
+//!
+//! Las claves se configuran en `CERTIFICATE_SIGNING_KEYS` como pares
+//! `id:semilla` separados por comas, con la semilla Ed25519 de 32 bytes en base64
+//! (`openssl rand -base64 32`). Se firma con `CERTIFICATE_ACTIVE_KEY` (por defecto,
+//! la primera) y el resto se conservan para verificar certificados ya emitidos.
+
+use std::io::BufWriter;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{Signer, SigningKey};
+use kairos_common::certificate::{
+    canonical_json, CertificateSignature, SignedCertificate, TraceabilityCertificate, TrustedKey,
+    ALGORITHM,
+};
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfLayerReference};
+
+use crate::{config::AppConfig, errors::AppError, labels};
+
+pub struct KeyRing {
+    keys: Vec<(String, SigningKey)>,
+    active: usize,
+}
+
+impl KeyRing {
+    pub fn parse(spec: &str, active: Option<&str>) -> Result<Self, String> {
+        let mut keys = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key_id, seed) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("signing key '{}' must be id:base64-seed", entry))?;
+            let seed: [u8; 32] = STANDARD
+                .decode(seed.trim())
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| format!("signing key '{}' is not a 32-byte base64 seed", key_id))?;
+            keys.push((key_id.trim().to_string(), SigningKey::from_bytes(&seed)));
+        }
+
+        let active = match active {
+            Some(id) => keys
+                .iter()
+                .position(|(key_id, _)| key_id == id)
+                .ok_or_else(|| format!("active signing key '{}' is not configured", id))?,
+            None => 0,
+        };
+        Ok(KeyRing { keys, active })
+    }
+
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::parse(
+            &config.certificate_signing_keys,
+            config.certificate_active_key.as_deref(),
+        )
+        .unwrap_or_else(|e| panic!("Invalid CERTIFICATE_SIGNING_KEYS: {}", e))
+    }
+
+    pub fn sign(&self, certificate: TraceabilityCertificate) -> Result<SignedCertificate, AppError> {
+        let (key_id, key) = self.keys.get(self.active).ok_or_else(|| {
+            AppError::InternalServerError("Certificate signing is not configured".into())
+        })?;
+        let payload = serde_json::to_value(&certificate).map_err(|e| {
+            AppError::InternalServerError(format!("Certificate serialization failed: {}", e))
+        })?;
+        let signature = key.sign(canonical_json(&payload).as_bytes());
+
+        Ok(SignedCertificate {
+            certificate,
+            signature: CertificateSignature {
+                algorithm: ALGORITHM.to_string(),
+                key_id: key_id.clone(),
+                public_key: STANDARD.encode(key.verifying_key().as_bytes()),
+                value: STANDARD.encode(signature.to_bytes()),
+            },
+        })
+    }
+
+    /// Claves públicas de todo el llavero, para publicarlas a los verificadores.
+    pub fn trusted_keys(&self) -> Vec<TrustedKey> {
+        self.keys
+            .iter()
+            .map(|(key_id, key)| TrustedKey {
+                key_id: key_id.clone(),
+                algorithm: ALGORITHM.to_string(),
+                public_key: STANDARD.encode(key.verifying_key().as_bytes()),
+            })
+            .collect()
+    }
+}
+
+pub fn verification_url(config: &AppConfig, certificate_id: uuid::Uuid) -> String {
+    format!(
+        "{}/{}",
+        config.certificate_verify_url.trim_end_matches('/'),
+        certificate_id
+    )
+}
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 18.0;
+const LINE: f32 = 5.0;
+const QR_SIZE: f32 = 38.0;
+
+/// Escribe líneas de arriba abajo y abre páginas nuevas cuando se acaba el espacio.
+struct PageWriter<'a> {
+    doc: &'a printpdf::PdfDocumentReference,
+    layer: PdfLayerReference,
+    y: f32,
+}
+
+impl PageWriter<'_> {
+    fn line(&mut self, text: &str, size: f32, font: &IndirectFontRef) {
+        if self.y < MARGIN {
+            let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "content");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+        self.layer.use_text(text, size, Mm(MARGIN), Mm(self.y), font);
+        self.y -= LINE * size / 9.0;
+    }
+
+    fn gap(&mut self) {
+        self.y -= LINE / 2.0;
+    }
+}
+
+/// PDF legible del certificado. Incluye la firma impresa, un QR a la verificación
+/// pública y el JSON firmado completo en el campo `Subject` de los metadatos, de modo
+/// que el PDF basta por sí solo para verificarlo sin conexión.
+pub fn certificate_pdf(signed: &SignedCertificate, verify_url: &str) -> Result<Vec<u8>, AppError> {
+    let pdf_error =
+        |e: printpdf::Error| AppError::InternalServerError(format!("PDF generation failed: {}", e));
+    let certificate = &signed.certificate;
+    let lot = &certificate.lot;
+    let document = serde_json::to_string(signed).map_err(|e| {
+        AppError::InternalServerError(format!("Certificate serialization failed: {}", e))
+    })?;
+
+    let (doc, page, layer) = PdfDocument::new(
+        format!("Traceability certificate {}", lot.lot_code),
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "content",
+    );
+    let doc = doc
+        .with_subject(document)
+        .with_identifier(certificate.certificate_id.to_string())
+        .with_author(certificate.issuer.clone());
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(pdf_error)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(pdf_error)?;
+    let mono = doc.add_builtin_font(BuiltinFont::Courier).map_err(pdf_error)?;
+
+    let first_layer = doc.get_page(page).get_layer(layer);
+    labels::draw_qr(
+        &first_layer,
+        &labels::encode(verify_url)?,
+        PAGE_WIDTH - MARGIN - QR_SIZE,
+        PAGE_HEIGHT - MARGIN - QR_SIZE,
+        QR_SIZE,
+    );
+
+    let mut out = PageWriter {
+        doc: &doc,
+        layer: first_layer,
+        y: PAGE_HEIGHT - MARGIN - 4.0,
+    };
+    out.line("Traceability certificate", 16.0, &bold);
+    out.gap();
+    out.line(&format!("Certificate: {}", certificate.certificate_id), 9.0, &regular);
+    out.line(
+        &format!(
+            "Issued by {} on {}",
+            certificate.issuer,
+            certificate.issued_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+        9.0,
+        &regular,
+    );
+    out.gap();
+    out.line(&format!("Lot {}", lot.lot_code), 12.0, &bold);
+    out.line(&format!("Product: {}", labels::fit(&lot.product_name, 70)), 9.0, &regular);
+    out.line(
+        &format!("Producer: {}", lot.farm_name.as_deref().unwrap_or(&lot.producer_name)),
+        9.0,
+        &regular,
+    );
+    out.line(
+        &format!("Quantity: {} {}  Status: {}", lot.quantity, lot.unit_of_measure, lot.status),
+        9.0,
+        &regular,
+    );
+    out.line(
+        &format!(
+            "Harvest: {}",
+            match lot.actual_harvest_date {
+                Some(date) => date.to_string(),
+                None => format!("{} (estimated)", lot.estimated_harvest_date),
+            }
+        ),
+        9.0,
+        &regular,
+    );
+    for origin in &certificate.origins {
+        out.line(
+            &format!(
+                "Origin: {} ({} {} {}, {} step(s) back)",
+                origin.lot_code,
+                origin.operation.to_str(),
+                origin.quantity,
+                origin.unit_of_measure,
+                origin.depth
+            ),
+            9.0,
+            &regular,
+        );
+    }
+
+    out.gap();
+    out.line(&format!("Event history ({} events)", certificate.events.len()), 12.0, &bold);
+    for event in &certificate.events {
+        out.line(
+            &format!(
+                "#{:<3} {}  {:<22} {}",
+                event.sequence,
+                event.occurred_at.format("%Y-%m-%d %H:%M"),
+                event.event_type,
+                labels::fit(event.description.as_deref().unwrap_or(""), 45)
+            ),
+            8.0,
+            &regular,
+        );
+        out.line(&format!("     {}", event.hash), 7.0, &mono);
+    }
+
+    out.gap();
+    out.line("Signature", 12.0, &bold);
+    out.line(
+        &format!("{} key {}", signed.signature.algorithm, signed.signature.key_id),
+        9.0,
+        &regular,
+    );
+    out.line(
+        &format!("Head hash: {}", certificate.head_hash.as_deref().unwrap_or("-")),
+        7.0,
+        &mono,
+    );
+    for chunk in signed.signature.value.as_bytes().chunks(64) {
+        out.line(&String::from_utf8_lossy(chunk), 7.0, &mono);
+    }
+    out.gap();
+    out.line(&format!("Verify at {}", verify_url), 8.0, &regular);
+
+    let mut pdf = BufWriter::new(Vec::new());
+    doc.save(&mut pdf).map_err(pdf_error)?;
+    pdf.into_inner()
+        .map_err(|e| AppError::InternalServerError(format!("PDF generation failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, Utc};
+    use kairos_common::{
+        certificate::{verify_certificate, CertificateLot},
+        CropType, LotStatus,
+    };
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    const SEED_A: &str = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+    const SEED_B: &str = "AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI=";
+
+    fn certificate() -> TraceabilityCertificate {
+        TraceabilityCertificate {
+            certificate_id: Uuid::new_v4(),
+            issuer: "Kairos".into(),
+            issued_at: Utc::now(),
+            lot: CertificateLot {
+                lot_id: Uuid::new_v4(),
+                lot_code: "LOT-2024-0001".into(),
+                product_name: "Tomates Cherry".into(),
+                crop_type: CropType::OrganicCertified,
+                status: LotStatus::Harvested,
+                quantity: Decimal::new(5000, 1),
+                unit_of_measure: "kg".into(),
+                estimated_harvest_date: NaiveDate::from_ymd_opt(2024, 8, 15).unwrap(),
+                actual_harvest_date: None,
+                producer_name: "Ana Pérez".into(),
+                farm_name: None,
+            },
+            events: Vec::new(),
+            head_hash: None,
+            origins: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn key_ring_signs_with_the_active_key_and_publishes_all() {
+        let spec = format!("old:{}, new:{}", SEED_A, SEED_B);
+        let ring = KeyRing::parse(&spec, Some("new")).unwrap();
+
+        let signed = ring.sign(certificate()).unwrap();
+        assert_eq!(signed.signature.key_id, "new");
+        assert_eq!(ring.trusted_keys().len(), 2);
+
+        let document = serde_json::to_value(&signed).unwrap();
+        assert!(verify_certificate(&document, &ring.trusted_keys()).is_ok());
+    }
+
+    #[test]
+    fn invalid_key_specs_are_rejected() {
+        assert!(KeyRing::parse("missing-seed", None).is_err());
+        assert!(KeyRing::parse("k1:c2hvcnQ=", None).is_err());
+        assert!(KeyRing::parse(&format!("k1:{}", SEED_A), Some("k2")).is_err());
+        assert!(KeyRing::parse("", None).unwrap().sign(certificate()).is_err());
+    }
+
+    #[test]
+    fn certificate_pdf_is_generated() {
+        let ring = KeyRing::parse(&format!("k1:{}", SEED_A), None).unwrap();
+        let signed = ring.sign(certificate()).unwrap();
+
+        let pdf = certificate_pdf(&signed, "https://kairos.example/public/certificates/x").unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+}
@@ -6,8 +6,23 @@ pub struct AppConfig {
     pub database_url: String,
     pub jwt_secret: String,
     pub jwt_expiration: i64,
+    pub refresh_token_expiration: i64,
     pub server_host: String,
     pub server_port: u16,
+    pub app_base_url: String,
+    pub mail_transport: String,
+    pub mail_dir: String,
+    pub mail_from: String,
+    pub public_cache_ttl: u64,
+    pub public_rate_limit: u32,
+    pub trust_forwarded_for: bool,
+    pub public_verify_url: String,
+    pub epcis_digital_link_base: String,
+    pub epcis_gtin: Option<String>,
+    pub certificate_issuer: String,
+    pub certificate_signing_keys: String,
+    pub certificate_active_key: Option<String>,
+    pub certificate_verify_url: String,
 }
 
 impl AppConfig {
@@ -16,14 +31,44 @@ impl AppConfig {
             database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
             jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
             jwt_expiration: env::var("JWT_EXPIRATION")
-                .unwrap_or_else(|_| "86400".to_string())
+                .unwrap_or_else(|_| "900".to_string())
                 .parse()
                 .expect("JWT_EXPIRATION must be a number"),
+            refresh_token_expiration: env::var("REFRESH_TOKEN_EXPIRATION")
+                .unwrap_or_else(|_| "2592000".to_string())
+                .parse()
+                .expect("REFRESH_TOKEN_EXPIRATION must be a number"),
             server_host: env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
             server_port: env::var("SERVER_PORT")
                 .unwrap_or_else(|_| "8080".to_string())
                 .parse()
                 .expect("SERVER_PORT must be a number"),
+            app_base_url: env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:8081".to_string()),
+            mail_transport: env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "log".to_string()),
+            mail_dir: env::var("MAIL_DIR").unwrap_or_else(|_| "./mail_outbox".to_string()),
+            mail_from: env::var("MAIL_FROM").unwrap_or_else(|_| "Kairos <no-reply@kairos.local>".to_string()),
+            public_cache_ttl: env::var("PUBLIC_CACHE_TTL")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("PUBLIC_CACHE_TTL must be a number"),
+            public_rate_limit: env::var("PUBLIC_RATE_LIMIT")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("PUBLIC_RATE_LIMIT must be a number"),
+            trust_forwarded_for: env::var("TRUST_FORWARDED_FOR")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            public_verify_url: env::var("PUBLIC_VERIFY_URL")
+                .unwrap_or_else(|_| "http://localhost:8080/public/lots".to_string()),
+            epcis_digital_link_base: env::var("EPCIS_DIGITAL_LINK_BASE")
+                .unwrap_or_else(|_| "https://id.gs1.org".to_string()),
+            epcis_gtin: env::var("EPCIS_GTIN").ok(),
+            certificate_issuer: env::var("CERTIFICATE_ISSUER")
+                .unwrap_or_else(|_| "Kairos".to_string()),
+            certificate_signing_keys: env::var("CERTIFICATE_SIGNING_KEYS").unwrap_or_default(),
+            certificate_active_key: env::var("CERTIFICATE_ACTIVE_KEY").ok(),
+            certificate_verify_url: env::var("CERTIFICATE_VERIFY_URL")
+                .unwrap_or_else(|_| "http://localhost:8080/public/certificates".to_string()),
         }
     }
 } 
\ No newline at end of file
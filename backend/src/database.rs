@@ -37,4 +37,17 @@ impl Database {
 
         Self { pool }
     }
-} 
\ No newline at end of file
+} 
+/// Conexión para las pruebas que necesitan PostgreSQL, con las migraciones al día y
+/// dentro de una transacción que nunca se confirma. Sin `TEST_DATABASE_URL` esas
+/// pruebas no hacen nada.
+#[cfg(test)]
+pub fn test_connection() -> Option<PgConnection> {
+    use diesel::Connection;
+
+    let database_url = env::var("TEST_DATABASE_URL").ok()?;
+    let mut conn = PgConnection::establish(&database_url).expect("Failed to connect to TEST_DATABASE_URL");
+    run_migrations(&mut conn);
+    conn.begin_test_transaction().expect("Failed to begin test transaction");
+    Some(conn)
+}
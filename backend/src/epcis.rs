@@ -0,0 +1,690 @@
+//! Conversión entre los eventos de Kairos y documentos GS1 EPCIS 2.0 (JSON-LD).
+//!
+//! Los lotes se identifican a nivel de clase (GTIN + lote) con GS1 Digital Link,
+//! `https://id.gs1.org/01/{gtin}/10/{lot_code}`. Los eventos de campo se exportan
+//! como `ObjectEvent` y las divisiones/fusiones como `TransformationEvent`. Lo que
+//! no tiene equivalente en el CBV viaja con el prefijo `kairos:`.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use kairos_common::{CropType, EventType, GenealogyOperation, Point};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{event_chain::ChainedEvent, event_edit::is_retraction},
+};
+
+pub const EPCIS_CONTEXT: &str = "https://ref.gs1.org/standards/epcis/2.0.0/epcis-context.jsonld";
+const KAIROS_NAMESPACE: &str = "urn:kairos:epcis:";
+const KAIROS_BIZ_STEP: &str = "urn:kairos:epcis:bizstep:";
+
+/// Comprueba longitud y dígito de control de un GTIN-8/12/13/14.
+pub fn is_valid_gtin(gtin: &str) -> bool {
+    if !matches!(gtin.len(), 8 | 12 | 13 | 14) || !gtin.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let digits: Vec<u32> = gtin.bytes().map(|b| u32::from(b - b'0')).collect();
+    let (body, check) = digits.split_at(digits.len() - 1);
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d * 3 } else { *d })
+        .sum();
+    (10 - sum % 10) % 10 == check[0]
+}
+
+/// Caracteres que el AI 10 admite tal cual en una URI; el resto va codificado.
+fn encode_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn decode_segment(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// URI GS1 Digital Link de la clase GTIN + lote. El GTIN se normaliza a 14 dígitos.
+pub fn digital_link(base: &str, gtin: &str, lot_code: &str) -> String {
+    format!(
+        "{}/01/{:0>14}/10/{}",
+        base.trim_end_matches('/'),
+        gtin,
+        encode_segment(lot_code)
+    )
+}
+
+/// Extrae el lote de un identificador Digital Link (`/01/{gtin}/10/{lote}`) o
+/// `urn:epc:class:lgtin:{prefijo}.{referencia}.{lote}`.
+pub fn lot_code_from_identifier(id: &str) -> Option<String> {
+    if let Some(lgtin) = id.strip_prefix("urn:epc:class:lgtin:") {
+        return lgtin.splitn(3, '.').nth(2).and_then(decode_segment);
+    }
+    let path = id.split(['?', '#']).next()?;
+    let segments: Vec<&str> = path.split('/').collect();
+    segments
+        .windows(2)
+        .rev()
+        .find(|pair| pair[0] == "10")
+        .filter(|_| segments.contains(&"01"))
+        .and_then(|pair| decode_segment(pair[1]))
+        .filter(|lot| !lot.is_empty())
+}
+
+/// Código UN/ECE Rec. 20 de las unidades habituales; `None` si no se reconoce.
+pub fn uom_code(unit: &str) -> Option<&'static str> {
+    match unit.trim().to_lowercase().as_str() {
+        "kg" | "kgs" | "kilogram" | "kilograms" | "kilogramo" | "kilogramos" => Some("KGM"),
+        "g" | "gram" | "grams" | "gramo" | "gramos" => Some("GRM"),
+        "t" | "ton" | "tonne" | "tonnes" | "tonelada" | "toneladas" => Some("TNE"),
+        "lb" | "lbs" | "pound" | "pounds" => Some("LBR"),
+        "l" | "lt" | "liter" | "liters" | "litre" | "litro" | "litros" => Some("LTR"),
+        "u" | "unit" | "units" | "unidad" | "unidades" | "pcs" => Some("H87"),
+        _ => None,
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn quantity_element(epc_class: &str, quantity: Option<Decimal>, unit: &str) -> Value {
+    let mut element = json!({ "epcClass": epc_class });
+    if let Some(quantity) = quantity.and_then(|q| q.to_f64()) {
+        element["quantity"] = json!(quantity);
+        if let Some(uom) = uom_code(unit) {
+            element["uom"] = json!(uom);
+        }
+    }
+    element
+}
+
+/// Paso de negocio y disposición de cada tipo de evento.
+fn biz_step(event_type: EventType) -> (Option<String>, Option<&'static str>) {
+    let kairos = |step: &str| Some(format!("{}{}", KAIROS_BIZ_STEP, step));
+    match event_type {
+        EventType::LotRegistered => (Some("commissioning".into()), Some("active")),
+        EventType::FertilizerApplication => (kairos("fertilizing"), None),
+        EventType::Irrigation => (kairos("irrigating"), None),
+        EventType::PestControl => (kairos("treating"), None),
+        EventType::HarvestStarted => (kairos("harvesting"), None),
+        EventType::HarvestCompleted => (kairos("harvesting"), Some("active")),
+        EventType::QualityInspection => (Some("inspecting".into()), None),
+        EventType::LotUpdated => (None, None),
+    }
+}
+
+/// Tipo de evento de Kairos para un evento importado. Lo que no es una labor de campo
+/// reconocible queda como `LOT_UPDATED`.
+pub fn event_type_for_biz_step(biz_step: Option<&str>) -> EventType {
+    let step = biz_step
+        .map(|s| s.rsplit(['-', ':']).next().unwrap_or(s).to_lowercase())
+        .unwrap_or_default();
+    match step.as_str() {
+        "inspecting" => EventType::QualityInspection,
+        "fertilizing" => EventType::FertilizerApplication,
+        "irrigating" => EventType::Irrigation,
+        "treating" => EventType::PestControl,
+        "harvesting" => EventType::HarvestStarted,
+        _ => EventType::LotUpdated,
+    }
+}
+
+/// Datos del lote que se incluyen en la exportación.
+pub struct ExportLot<'a> {
+    pub epc_class: String,
+    pub product_name: &'a str,
+    pub crop_type: CropType,
+    pub unit_of_measure: &'a str,
+    pub quantity: Decimal,
+}
+
+/// Enlace de genealogía ya resuelto a identificadores EPCIS.
+pub struct TransformationLink {
+    pub id: Uuid,
+    pub operation: GenealogyOperation,
+    pub parent_class: String,
+    pub child_class: String,
+    pub quantity: Decimal,
+    pub unit_of_measure: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// POINT guarda longitud en `x` y latitud en `y`; la URI geo va al revés.
+fn read_point(coordinates: Option<Point>) -> Option<Value> {
+    coordinates.map(|p| json!({ "id": format!("geo:{},{}", p.y, p.x) }))
+}
+
+/// `eventID` con el que se exporta un evento: el original del socio si vino de una
+/// importación, para que el socio lo reconozca.
+fn exported_event_id(event: &ChainedEvent) -> String {
+    event
+        .metadata
+        .as_ref()
+        .and_then(|m| m.pointer("/epcis/event_id"))
+        .and_then(Value::as_str)
+        .map(String::from)
+        .unwrap_or_else(|| format!("urn:uuid:{}", event.id))
+}
+
+/// Hora y zona del evento: las del socio si vino de una importación; si no, cuándo
+/// se registró en Kairos.
+fn exported_event_time(event: &ChainedEvent) -> (String, String) {
+    let epcis = event.metadata.as_ref().and_then(|m| m.get("epcis"));
+    let time = epcis
+        .and_then(|e| e.get("event_time"))
+        .and_then(|t| serde_json::from_value::<DateTime<Utc>>(t.clone()).ok())
+        .unwrap_or(event.created_at);
+    let offset = epcis
+        .and_then(|e| e.pointer("/document/eventTimeZoneOffset"))
+        .and_then(Value::as_str)
+        .unwrap_or("+00:00");
+    (timestamp(time), offset.to_string())
+}
+
+fn object_event(lot: &ExportLot, event: &ChainedEvent) -> Value {
+    let event_type = event.event_type.0;
+    let metadata = event.metadata.as_ref();
+    let shipment = metadata.and_then(|m| m.get("shipment"));
+
+    let (step, disposition) = match shipment {
+        Some(_) => (Some("shipping".to_string()), Some("in_transit")),
+        None => biz_step(event_type),
+    };
+    let quantity = match (event_type, shipment) {
+        (_, Some(s)) => s
+            .get("quantity")
+            .and_then(|q| serde_json::from_value::<Decimal>(q.clone()).ok()),
+        (EventType::LotRegistered, None) => Some(lot.quantity),
+        _ => None,
+    };
+
+    let (event_time, offset) = exported_event_time(event);
+    let mut out = json!({
+        "type": "ObjectEvent",
+        "eventID": exported_event_id(event),
+        "eventTime": event_time,
+        "eventTimeZoneOffset": offset,
+        "action": if event_type == EventType::LotRegistered { "ADD" } else { "OBSERVE" },
+        "quantityList": [quantity_element(&lot.epc_class, quantity, lot.unit_of_measure)],
+        "kairos:eventType": event_type.to_str(),
+    });
+    if let Some(step) = step {
+        out["bizStep"] = json!(step);
+    }
+    if let Some(disposition) = disposition {
+        out["disposition"] = json!(disposition);
+    }
+    if let Some(point) = read_point(event.coordinates.map(|c| c.0)) {
+        out["readPoint"] = point;
+    }
+    if let Some(reference) = shipment
+        .and_then(|s| s.get("reference"))
+        .and_then(Value::as_str)
+    {
+        out["bizTransactionList"] = json!([{ "type": "desadv", "bizTransaction": reference }]);
+    }
+    if event_type == EventType::LotRegistered && event.corrects_event_id.is_none() {
+        out["ilmd"] = json!({
+            "kairos:productName": lot.product_name,
+            "kairos:cropType": lot.crop_type,
+        });
+    }
+    if let Some(description) = &event.description {
+        out["kairos:description"] = json!(description);
+    }
+    if let Some(location) = &event.event_location {
+        out["kairos:location"] = json!(location);
+    }
+    if let (Some(sequence), Some(hash)) = (event.sequence, &event.hash) {
+        out["kairos:sequence"] = json!(sequence);
+        out["kairos:hash"] = json!(hash);
+    }
+    out
+}
+
+/// Las divisiones y fusiones hechas en una misma operación comparten `created_at`
+/// (la hora de la transacción) y se agrupan en un único `TransformationEvent`.
+fn transformation_events(links: &[TransformationLink]) -> Vec<(DateTime<Utc>, Value)> {
+    let mut groups: BTreeMap<(DateTime<Utc>, &str), Vec<&TransformationLink>> = BTreeMap::new();
+    for link in links {
+        groups
+            .entry((link.created_at, link.operation.to_str()))
+            .or_default()
+            .push(link);
+    }
+
+    groups
+        .into_iter()
+        .map(|((created_at, operation), group)| {
+            let mut inputs: BTreeMap<&str, (Decimal, &str)> = BTreeMap::new();
+            let mut outputs: BTreeMap<&str, (Decimal, &str)> = BTreeMap::new();
+            for link in &group {
+                inputs
+                    .entry(&link.parent_class)
+                    .or_insert((Decimal::ZERO, &link.unit_of_measure))
+                    .0 += link.quantity;
+                outputs
+                    .entry(&link.child_class)
+                    .or_insert((Decimal::ZERO, &link.unit_of_measure))
+                    .0 += link.quantity;
+            }
+            let list = |side: BTreeMap<&str, (Decimal, &str)>| -> Vec<Value> {
+                side.into_iter()
+                    .map(|(class, (quantity, unit))| quantity_element(class, Some(quantity), unit))
+                    .collect()
+            };
+            let id = group
+                .iter()
+                .map(|l| l.id)
+                .min()
+                .expect("groups are never empty");
+
+            let event = json!({
+                "type": "TransformationEvent",
+                "eventID": format!("urn:uuid:{}", id),
+                "eventTime": timestamp(created_at),
+                "eventTimeZoneOffset": "+00:00",
+                "bizStep": "repackaging",
+                "inputQuantityList": list(inputs),
+                "outputQuantityList": list(outputs),
+                "kairos:operation": operation,
+            });
+            (created_at, event)
+        })
+        .collect()
+}
+
+/// Documento EPCIS con la historia del lote. Las correcciones se exportan como
+/// `errorDeclaration` sobre el evento original (la del socio, si vino importada); los
+/// eventos de división/fusión se sustituyen por los `TransformationEvent` de la
+/// genealogía.
+pub fn export_document(
+    lot: &ExportLot,
+    chain: &[ChainedEvent],
+    links: &[TransformationLink],
+) -> Value {
+    let mut events: Vec<(DateTime<Utc>, Value)> = Vec::new();
+
+    for event in chain {
+        let metadata = event.metadata.as_ref();
+        if is_retraction(metadata) || metadata.is_some_and(|m| m.get("genealogy").is_some()) {
+            continue;
+        }
+        let mut exported = object_event(lot, event);
+
+        if let Some(correction) = chain.iter().find(|c| c.corrects_event_id == Some(event.id)) {
+            let declared = correction
+                .metadata
+                .as_ref()
+                .and_then(|m| m.pointer("/epcis/error_declaration"));
+            let mut declaration = json!({ "declarationTime": timestamp(correction.created_at) });
+            if let Some(declared) = declared {
+                declaration = declared.clone();
+            } else if is_retraction(correction.metadata.as_ref()) {
+                declaration["reason"] = json!("did_not_occur");
+            } else {
+                declaration["reason"] = json!("incorrect_data");
+                declaration["correctiveEventIDs"] = json!([exported_event_id(correction)]);
+            }
+            exported["errorDeclaration"] = declaration;
+        }
+        events.push((event.created_at, exported));
+    }
+    events.extend(transformation_events(links));
+    events.sort_by_key(|(time, _)| *time);
+
+    json!({
+        "@context": [EPCIS_CONTEXT, { "kairos": KAIROS_NAMESPACE }],
+        "type": "EPCISDocument",
+        "schemaVersion": "2.0",
+        "creationDate": timestamp(Utc::now()),
+        "epcisBody": {
+            "eventList": events.into_iter().map(|(_, event)| event).collect::<Vec<_>>(),
+        },
+    })
+}
+
+/// Evento de un socio listo para adjuntarse a un lote.
+#[derive(Debug, Clone)]
+pub struct PartnerEvent {
+    pub event_id: String,
+    pub epcis_type: String,
+    pub event_time: DateTime<Utc>,
+    pub biz_step: Option<String>,
+    pub lot_codes: Vec<String>,
+    /// El socio declara erróneo el evento con este `eventID` que ya envió antes.
+    pub error_declaration: Option<Value>,
+    pub raw: Value,
+}
+
+/// Evento leído, o el `eventID` (si lo tiene) y el motivo por el que no se pudo leer.
+pub type ParsedEvent = Result<PartnerEvent, (Option<String>, String)>;
+
+/// Identificador estable de un evento sin `eventID`: un UUID (versión 8) con los
+/// primeros bytes del SHA-256 del evento. No es el hash canónico de EPCIS, que exige
+/// normalizar el evento según el CBV; basta para que reenviar el mismo evento no lo
+/// duplique. `serde_json` serializa las claves ordenadas, así que su orden en el
+/// documento no importa.
+fn derived_event_id(event: &Value) -> String {
+    let digest = Sha256::digest(event.to_string());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    format!("urn:uuid:{}", uuid::Builder::from_custom_bytes(bytes).into_uuid())
+}
+
+fn collect_identifiers(event: &Map<String, Value>) -> Vec<String> {
+    let mut ids = Vec::new();
+    for key in ["epcList", "inputEPCList", "outputEPCList", "childEPCs"] {
+        if let Some(list) = event.get(key).and_then(Value::as_array) {
+            ids.extend(list.iter().filter_map(Value::as_str).map(String::from));
+        }
+    }
+    for key in [
+        "quantityList",
+        "inputQuantityList",
+        "outputQuantityList",
+        "childQuantityList",
+    ] {
+        if let Some(list) = event.get(key).and_then(Value::as_array) {
+            ids.extend(
+                list.iter()
+                    .filter_map(|q| q.get("epcClass"))
+                    .filter_map(Value::as_str)
+                    .map(String::from),
+            );
+        }
+    }
+    if let Some(parent) = event.get("parentID").and_then(Value::as_str) {
+        ids.push(parent.to_string());
+    }
+    ids
+}
+
+fn parse_event(value: &Value) -> Result<PartnerEvent, String> {
+    let event = value.as_object().ok_or("event is not a JSON object")?;
+    let epcis_type = event
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or("missing type")?
+        .to_string();
+    let event_time = event
+        .get("eventTime")
+        .and_then(Value::as_str)
+        .ok_or("missing eventTime")?;
+    let event_time = DateTime::parse_from_rfc3339(event_time)
+        .map_err(|_| "eventTime is not an RFC 3339 timestamp")?
+        .with_timezone(&Utc);
+
+    let mut lot_codes: Vec<String> = collect_identifiers(event)
+        .iter()
+        .filter_map(|id| lot_code_from_identifier(id))
+        .collect();
+    lot_codes.sort();
+    lot_codes.dedup();
+
+    Ok(PartnerEvent {
+        event_id: event
+            .get("eventID")
+            .and_then(Value::as_str)
+            .map(String::from)
+            .unwrap_or_else(|| derived_event_id(value)),
+        epcis_type,
+        event_time,
+        biz_step: event
+            .get("bizStep")
+            .and_then(Value::as_str)
+            .map(String::from),
+        lot_codes,
+        error_declaration: event
+            .get("errorDeclaration")
+            .filter(|d| d.is_object())
+            .cloned(),
+        raw: value.clone(),
+    })
+}
+
+/// Lee la lista de eventos de un `EPCISDocument` o de un `EPCISQueryDocument`.
+/// Cada evento se devuelve por separado para que uno mal formado no invalide el resto.
+pub fn parse_document(document: &Value) -> Result<Vec<ParsedEvent>, AppError> {
+    let body = document
+        .get("epcisBody")
+        .ok_or_else(|| AppError::BadRequest("Not an EPCIS document: missing epcisBody".into()))?;
+    let list = body
+        .get("eventList")
+        .or_else(|| body.pointer("/queryResults/resultsBody/eventList"))
+        .and_then(Value::as_array)
+        .ok_or_else(|| AppError::BadRequest("EPCIS document has no eventList".into()))?;
+
+    Ok(list
+        .iter()
+        .map(|value| {
+            parse_event(value).map_err(|reason| {
+                let id = value
+                    .get("eventID")
+                    .and_then(Value::as_str)
+                    .map(String::from);
+                (id, reason.to_string())
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::sql_enums::DbEventType;
+
+    fn lot() -> ExportLot<'static> {
+        ExportLot {
+            epc_class: digital_link("https://id.gs1.org", "09506000134352", "LOT-2024-0001"),
+            product_name: "Tomates Cherry",
+            crop_type: CropType::OrganicCertified,
+            unit_of_measure: "kg",
+            quantity: Decimal::new(5000, 1),
+        }
+    }
+
+    fn event(event_type: EventType, metadata: Option<Value>) -> ChainedEvent {
+        ChainedEvent {
+            id: Uuid::new_v4(),
+            lot_id: Uuid::new_v4(),
+            sequence: Some(1),
+            event_type: DbEventType(event_type),
+            description: Some("Aplicación de compost".into()),
+            event_location: None,
+            coordinates: None,
+            metadata,
+            created_at: Utc::now(),
+            corrects_event_id: None,
+            prev_hash: None,
+            hash: Some("abc".into()),
+        }
+    }
+
+    #[test]
+    fn gtin_check_digit_is_validated() {
+        assert!(is_valid_gtin("09506000134352"));
+        assert!(is_valid_gtin("9506000134352"));
+        assert!(!is_valid_gtin("09506000134353"));
+        assert!(!is_valid_gtin("0950600013435A"));
+    }
+
+    #[test]
+    fn digital_links_round_trip_the_lot_code() {
+        let link = digital_link("https://id.gs1.org/", "9506000134352", "LOT 24/01");
+        assert_eq!(
+            link,
+            "https://id.gs1.org/01/09506000134352/10/LOT%2024%2F01"
+        );
+        assert_eq!(
+            lot_code_from_identifier(&link).as_deref(),
+            Some("LOT 24/01")
+        );
+
+        assert_eq!(
+            lot_code_from_identifier("urn:epc:class:lgtin:4012345.012345.LOT-7").as_deref(),
+            Some("LOT-7")
+        );
+        assert_eq!(lot_code_from_identifier("https://example.com/10/LOT"), None);
+    }
+
+    #[test]
+    fn corrections_become_error_declarations() {
+        let original = event(EventType::FertilizerApplication, None);
+        let mut amend = event(
+            EventType::FertilizerApplication,
+            Some(json!({ "correction": { "action": "amend" } })),
+        );
+        amend.corrects_event_id = Some(original.id);
+        let retracted = event(EventType::Irrigation, None);
+        let mut retract = event(
+            EventType::Irrigation,
+            Some(json!({ "correction": { "action": "retract" } })),
+        );
+        retract.corrects_event_id = Some(retracted.id);
+
+        let document = export_document(
+            &lot(),
+            &[original.clone(), amend.clone(), retracted, retract],
+            &[],
+        );
+        let events = document["epcisBody"]["eventList"].as_array().unwrap();
+
+        assert_eq!(events.len(), 3);
+        let declared = events
+            .iter()
+            .find(|e| e["eventID"] == json!(format!("urn:uuid:{}", original.id)))
+            .unwrap();
+        assert_eq!(declared["errorDeclaration"]["reason"], "incorrect_data");
+        assert_eq!(
+            declared["errorDeclaration"]["correctiveEventIDs"][0],
+            json!(format!("urn:uuid:{}", amend.id))
+        );
+        assert!(events
+            .iter()
+            .any(|e| e["errorDeclaration"]["reason"] == "did_not_occur"));
+    }
+
+    #[test]
+    fn imported_events_keep_the_partners_id_and_time() {
+        let original = event(
+            EventType::QualityInspection,
+            Some(json!({ "epcis": {
+                "event_id": "urn:uuid:0b5e7c1a-3f4d-4e2a-9c61-2a7d8e9f1b20",
+                "event_time": "2024-08-20T08:00:00Z",
+                "document": { "eventTimeZoneOffset": "+02:00" },
+            }})),
+        );
+        let mut declared = event(
+            EventType::QualityInspection,
+            Some(json!({
+                "correction": { "action": "retract" },
+                "epcis": {
+                    "event_id": "urn:uuid:0b5e7c1a-3f4d-4e2a-9c61-2a7d8e9f1b20",
+                    "error_declaration": { "declarationTime": "2024-08-21T09:00:00.000Z", "reason": "incorrect_data" },
+                },
+            })),
+        );
+        declared.corrects_event_id = Some(original.id);
+
+        let document = export_document(&lot(), &[original, declared], &[]);
+        let events = document["epcisBody"]["eventList"].as_array().unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["eventID"], "urn:uuid:0b5e7c1a-3f4d-4e2a-9c61-2a7d8e9f1b20");
+        assert_eq!(events[0]["eventTime"], "2024-08-20T08:00:00.000Z");
+        assert_eq!(events[0]["eventTimeZoneOffset"], "+02:00");
+        assert_eq!(events[0]["errorDeclaration"]["reason"], "incorrect_data");
+    }
+
+    #[test]
+    fn one_split_is_one_transformation_event() {
+        let created_at = Utc::now();
+        let link = |child: &str, quantity: i64| TransformationLink {
+            id: Uuid::new_v4(),
+            operation: GenealogyOperation::Split,
+            parent_class: "P".into(),
+            child_class: child.into(),
+            quantity: Decimal::from(quantity),
+            unit_of_measure: "kg".into(),
+            created_at,
+        };
+
+        let events = transformation_events(&[link("A", 300), link("B", 200)]);
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0].1;
+        assert_eq!(event["inputQuantityList"][0]["quantity"], json!(500.0));
+        assert_eq!(event["inputQuantityList"][0]["uom"], "KGM");
+        assert_eq!(event["outputQuantityList"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn partner_documents_are_parsed_event_by_event() {
+        let document = json!({
+            "type": "EPCISDocument",
+            "epcisBody": { "eventList": [
+                {
+                    "type": "ObjectEvent",
+                    "eventID": "urn:uuid:1",
+                    "eventTime": "2024-08-20T10:00:00.000+02:00",
+                    "action": "OBSERVE",
+                    "bizStep": "https://ref.gs1.org/cbv/BizStep-inspecting",
+                    "quantityList": [{ "epcClass": "https://id.gs1.org/01/09506000134352/10/LOT-1" }]
+                },
+                { "type": "ObjectEvent" }
+            ]}
+        });
+
+        let events = parse_document(&document).unwrap();
+        let first = events[0].as_ref().unwrap();
+
+        assert_eq!(first.lot_codes, vec!["LOT-1"]);
+        assert_eq!(
+            event_type_for_biz_step(first.biz_step.as_deref()),
+            EventType::QualityInspection
+        );
+        assert_eq!(first.event_time.to_rfc3339(), "2024-08-20T08:00:00+00:00");
+        assert!(events[1].is_err());
+        assert!(parse_document(&json!({})).is_err());
+    }
+
+    #[test]
+    fn events_without_an_id_get_a_stable_uuid() {
+        let event = json!({ "type": "ObjectEvent", "eventTime": "2024-08-20T10:00:00Z", "action": "OBSERVE" });
+        let reordered = json!({ "action": "OBSERVE", "eventTime": "2024-08-20T10:00:00Z", "type": "ObjectEvent" });
+
+        let id = derived_event_id(&event);
+        assert!(id.starts_with("urn:uuid:"));
+        assert!(Uuid::parse_str(&id["urn:uuid:".len()..]).is_ok());
+        assert_eq!(id, derived_event_id(&reordered));
+        assert_ne!(id, derived_event_id(&json!({ "type": "ObjectEvent" })));
+    }
+}
@@ -4,6 +4,7 @@ use bcrypt::BcryptError;
 use diesel::result::Error as DieselError;
 use r2d2;
 use jsonwebtoken::errors::Error as JwtError;
+use kairos_common::ApiError;
 use serde::Serialize;
 use std::fmt;
 use uuid::Error as UuidError; // Añadido para manejar errores de UUID
@@ -19,6 +20,10 @@ pub enum AppError {
     Unauthorized(String),
     Forbidden(String),
     NotFound(String),
+    Conflict(ApiError), // Violación de reglas de negocio con detalle estructurado
+    // If-Match no coincide: se devuelve la representación actual con su ETag
+    PreconditionFailed { etag: String, current: serde_json::Value },
+    TooManyRequests(String),
     InternalServerError(String),
     DatabaseError(DieselError),
     PoolError(r2d2::Error), // Cambiado a la ruta canónica
@@ -35,6 +40,11 @@ impl fmt::Display for AppError {
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             AppError::NotFound(msg) => write!(f, "Not Found: {}", msg),
+            AppError::Conflict(err) => write!(f, "Conflict: {} ({})", err.message, err.code),
+            AppError::PreconditionFailed { etag, .. } => {
+                write!(f, "Precondition Failed: current ETag is {}", etag)
+            }
+            AppError::TooManyRequests(msg) => write!(f, "Too Many Requests: {}", msg),
             AppError::InternalServerError(msg) => write!(f, "Internal Server Error: {}", msg),
             AppError::DatabaseError(err) => write!(f, "Database Error: {}", err),
             AppError::PoolError(err) => write!(f, "Pool Error: {}", err),
@@ -108,6 +118,15 @@ impl ResponseError for AppError {
                     error: msg.to_string(),
                 })
             }
+            AppError::Conflict(err) => HttpResponse::Conflict().json(err),
+            AppError::PreconditionFailed { etag, current } => HttpResponse::PreconditionFailed()
+                .insert_header((actix_web::http::header::ETAG, etag.as_str()))
+                .json(current),
+            AppError::TooManyRequests(msg) => {
+                HttpResponse::TooManyRequests().json(ErrorResponse {
+                    error: msg.to_string(),
+                })
+            }
             AppError::InternalServerError(msg) => {
                 HttpResponse::InternalServerError().json(ErrorResponse {
                     error: msg.to_string(),
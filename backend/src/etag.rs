@@ -0,0 +1,87 @@
+//! ETags de los recursos editables y comprobación de `If-Match`, para que dos
+//! ediciones simultáneas no se pisen sin enterarse (concurrencia optimista).
+
+use actix_web::{http::header, HttpRequest};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::errors::AppError;
+
+/// ETag fuerte a partir de la última modificación, en microsegundos, que es la
+/// precisión de TIMESTAMPTZ.
+pub fn entity_tag(updated_at: DateTime<Utc>) -> String {
+    format!("\"{:x}\"", updated_at.timestamp_micros())
+}
+
+/// Comparación fuerte (RFC 9110): una ETag débil nunca coincide.
+fn matches(if_match: &str, current: &str) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == current)
+}
+
+/// Sin `If-Match` la petición sigue adelante. Con él, tiene que coincidir con la
+/// versión actual; si no, 412 con la representación actual y su ETag.
+pub fn check_if_match<T: Serialize>(
+    req: &HttpRequest,
+    current_tag: &str,
+    current: &T,
+) -> Result<(), AppError> {
+    let Some(if_match) = req.headers().get(header::IF_MATCH) else {
+        return Ok(());
+    };
+    let if_match = if_match
+        .to_str()
+        .map_err(|_| AppError::BadRequest("Invalid If-Match header".into()))?;
+    if matches(if_match, current_tag) {
+        return Ok(());
+    }
+
+    Err(AppError::PreconditionFailed {
+        etag: current_tag.to_string(),
+        current: serde_json::to_value(current)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use chrono::TimeZone;
+
+    #[test]
+    fn tags_change_with_every_microsecond() {
+        let at = Utc.with_ymd_and_hms(2025, 7, 28, 9, 0, 0).unwrap();
+        assert_eq!(entity_tag(at), entity_tag(at));
+        assert_ne!(entity_tag(at), entity_tag(at + chrono::Duration::microseconds(1)));
+    }
+
+    #[test]
+    fn any_listed_tag_or_wildcard_matches() {
+        assert!(matches("\"a\", \"b\"", "\"b\""));
+        assert!(matches("*", "\"b\""));
+        assert!(!matches("W/\"b\"", "\"b\""));
+        assert!(!matches("\"a\"", "\"b\""));
+    }
+
+    #[test]
+    fn stale_tag_is_rejected_with_the_current_representation() {
+        let current = serde_json::json!({ "estimated_quantity": "450.000" });
+
+        let req = TestRequest::default().to_http_request();
+        assert!(check_if_match(&req, "\"2\"", &current).is_ok());
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, "\"1\""))
+            .to_http_request();
+        match check_if_match(&req, "\"2\"", &current) {
+            Err(AppError::PreconditionFailed { etag, current: body }) => {
+                assert_eq!(etag, "\"2\"");
+                assert_eq!(body, current);
+            }
+            other => panic!("expected 412, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,563 @@
+//! Exportación de tablas a CSV, XLSX y Parquet sin cargar el resultado en memoria.
+//!
+//! Las filas llegan por tandas y cada formato las escribe según llegan. CSV y Parquet
+//! salen hacia el cliente mientras se generan; XLSX necesita el fichero completo para
+//! cerrar el ZIP, así que se compone en un temporal en modo de memoria constante.
+
+use std::io::{self, BufWriter, Seek, Write};
+use std::sync::Arc;
+
+use actix_web::web::Bytes;
+use arrow_array::{
+    builder::{
+        Date32Builder, Decimal128Builder, Float64Builder, StringBuilder,
+        TimestampMicrosecondBuilder,
+    },
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use futures::{channel::mpsc, executor::block_on, SinkExt, Stream};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+
+/// Filas que se piden a la base de datos en cada consulta.
+pub const BATCH_SIZE: i64 = 1000;
+/// Las cantidades son `NUMERIC(12,3)` en la base de datos.
+const DECIMAL_PRECISION: u8 = 12;
+const DECIMAL_SCALE: i8 = 3;
+const PARQUET_ROW_GROUP_SIZE: usize = 10_000;
+/// Límite de filas de una hoja de Excel, cabecera incluida.
+const XLSX_MAX_ROWS: u32 = 1_048_576;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "xlsx" => Ok(ExportFormat::Xlsx),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(format!(
+                "Unsupported format '{}', expected csv, xlsx or parquet",
+                value
+            )),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Decimal,
+    Float,
+    Date,
+    Timestamp,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: Kind,
+}
+
+/// Valor de una celda; su variante debe coincidir con el `Kind` de la columna.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Null,
+    Text(String),
+    Decimal(Decimal),
+    Float(f64),
+    Date(NaiveDate),
+    Timestamp(DateTime<Utc>),
+}
+
+pub type Row = Vec<Cell>;
+
+impl From<Option<String>> for Cell {
+    fn from(value: Option<String>) -> Self {
+        value.map_or(Cell::Null, Cell::Text)
+    }
+}
+
+impl From<Option<NaiveDate>> for Cell {
+    fn from(value: Option<NaiveDate>) -> Self {
+        value.map_or(Cell::Null, Cell::Date)
+    }
+}
+
+impl From<Option<f64>> for Cell {
+    fn from(value: Option<f64>) -> Self {
+        value.map_or(Cell::Null, Cell::Float)
+    }
+}
+
+impl Cell {
+    fn to_text(&self) -> String {
+        match self {
+            Cell::Null => String::new(),
+            Cell::Text(value) => value.clone(),
+            Cell::Decimal(value) => value.to_string(),
+            Cell::Float(value) => value.to_string(),
+            Cell::Date(value) => value.format("%Y-%m-%d").to_string(),
+            Cell::Timestamp(value) => value.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+/// Escribe filas en un formato concreto; `finish` cierra el fichero.
+pub trait Encoder {
+    fn write_rows(&mut self, rows: &[Row]) -> Result<(), String>;
+    fn finish(self: Box<Self>) -> Result<(), String>;
+}
+
+pub type Output<'a> = &'a mut (dyn Write + Send);
+
+pub fn encoder<'a>(
+    format: ExportFormat,
+    columns: &'static [Column],
+    out: Output<'a>,
+) -> Result<Box<dyn Encoder + 'a>, String> {
+    Ok(match format {
+        ExportFormat::Csv => Box::new(CsvEncoder::new(columns, out)?),
+        ExportFormat::Xlsx => Box::new(XlsxEncoder::new(columns, out)?),
+        ExportFormat::Parquet => Box::new(ParquetEncoder::new(columns, out)?),
+    })
+}
+
+struct CsvEncoder<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> CsvEncoder<W> {
+    fn new(columns: &[Column], out: W) -> Result<Self, String> {
+        let mut writer = csv::Writer::from_writer(out);
+        writer
+            .write_record(columns.iter().map(|c| c.name))
+            .map_err(|e| format!("CSV export failed: {}", e))?;
+        Ok(CsvEncoder { writer })
+    }
+}
+
+impl<W: Write> Encoder for CsvEncoder<W> {
+    fn write_rows(&mut self, rows: &[Row]) -> Result<(), String> {
+        for row in rows {
+            self.writer
+                .write_record(row.iter().map(Cell::to_text))
+                .map_err(|e| format!("CSV export failed: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("CSV export failed: {}", e))
+    }
+}
+
+struct XlsxEncoder<W: Write> {
+    workbook: Workbook,
+    worksheet: Worksheet,
+    columns: &'static [Column],
+    next_row: u32,
+    decimal: Format,
+    date: Format,
+    timestamp: Format,
+    out: W,
+}
+
+impl<W: Write> XlsxEncoder<W> {
+    fn new(columns: &'static [Column], out: W) -> Result<Self, String> {
+        let xlsx_error = |e: rust_xlsxwriter::XlsxError| format!("XLSX export failed: {}", e);
+        let mut workbook = Workbook::new();
+        let mut worksheet = workbook.new_worksheet_with_constant_memory();
+        let header = Format::new().set_bold();
+
+        for (index, column) in columns.iter().enumerate() {
+            let index = index as u16;
+            let width = match column.kind {
+                Kind::Text | Kind::Timestamp => 20.0,
+                Kind::Decimal | Kind::Float | Kind::Date => 12.0,
+            };
+            worksheet
+                .set_column_width(index, width)
+                .and_then(|ws| ws.write_string_with_format(0, index, column.name, &header))
+                .map_err(xlsx_error)?;
+        }
+        worksheet.set_freeze_panes(1, 0).map_err(xlsx_error)?;
+
+        Ok(XlsxEncoder {
+            workbook,
+            worksheet,
+            columns,
+            next_row: 1,
+            decimal: Format::new().set_num_format("0.000"),
+            date: Format::new().set_num_format("yyyy-mm-dd"),
+            timestamp: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+            out,
+        })
+    }
+}
+
+impl<W: Write> Encoder for XlsxEncoder<W> {
+    fn write_rows(&mut self, rows: &[Row]) -> Result<(), String> {
+        let xlsx_error = |e: rust_xlsxwriter::XlsxError| format!("XLSX export failed: {}", e);
+        for row in rows {
+            if self.next_row >= XLSX_MAX_ROWS {
+                return Err(format!(
+                    "XLSX supports at most {} rows, use CSV or Parquet",
+                    XLSX_MAX_ROWS - 1
+                ));
+            }
+            for (index, cell) in row.iter().enumerate() {
+                let (r, c) = (self.next_row, index as u16);
+                let ws = &mut self.worksheet;
+                let result = match cell {
+                    Cell::Null => continue,
+                    Cell::Text(value) => ws.write_string(r, c, value),
+                    Cell::Decimal(value) => ws.write_number_with_format(
+                        r,
+                        c,
+                        value.to_f64().unwrap_or_default(),
+                        &self.decimal,
+                    ),
+                    Cell::Float(value) => ws.write_number(r, c, *value),
+                    Cell::Date(value) => ws.write_datetime_with_format(r, c, value, &self.date),
+                    Cell::Timestamp(value) => {
+                        ws.write_datetime_with_format(r, c, value.naive_utc(), &self.timestamp)
+                    }
+                };
+                result.map_err(xlsx_error)?;
+            }
+            self.next_row += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        let xlsx_error = |e: rust_xlsxwriter::XlsxError| format!("XLSX export failed: {}", e);
+        let io_error = |e: io::Error| format!("XLSX export failed: {}", e);
+        let XlsxEncoder {
+            mut workbook,
+            mut worksheet,
+            columns,
+            next_row,
+            mut out,
+            ..
+        } = *self;
+
+        worksheet
+            .autofilter(0, 0, next_row - 1, columns.len() as u16 - 1)
+            .map_err(xlsx_error)?;
+        workbook.push_worksheet(worksheet);
+
+        let mut file = tempfile::tempfile().map_err(io_error)?;
+        workbook.save_to_writer(&mut file).map_err(xlsx_error)?;
+        file.rewind().map_err(io_error)?;
+        io::copy(&mut file, &mut out).map_err(io_error)?;
+        Ok(())
+    }
+}
+
+struct ParquetEncoder<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    schema: SchemaRef,
+    columns: &'static [Column],
+}
+
+fn arrow_type(kind: Kind) -> DataType {
+    match kind {
+        Kind::Text => DataType::Utf8,
+        Kind::Decimal => DataType::Decimal128(DECIMAL_PRECISION, DECIMAL_SCALE),
+        Kind::Float => DataType::Float64,
+        Kind::Date => DataType::Date32,
+        Kind::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+    }
+}
+
+/// Mantisa del decimal reescalado a `DECIMAL_SCALE` cifras.
+fn decimal_mantissa(value: Decimal) -> i128 {
+    let value = value.round_dp(DECIMAL_SCALE as u32);
+    value.mantissa() * 10i128.pow(DECIMAL_SCALE as u32 - value.scale())
+}
+
+fn column_array(kind: Kind, rows: &[Row], index: usize) -> Result<ArrayRef, String> {
+    let cells = rows.iter().map(|row| &row[index]);
+    let mismatch = || format!("Unexpected value in a {:?} column", kind);
+
+    Ok(match kind {
+        Kind::Text => {
+            let mut builder = StringBuilder::new();
+            for cell in cells {
+                match cell {
+                    Cell::Text(value) => builder.append_value(value),
+                    Cell::Null => builder.append_null(),
+                    _ => return Err(mismatch()),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        Kind::Decimal => {
+            let mut builder = Decimal128Builder::new()
+                .with_precision_and_scale(DECIMAL_PRECISION, DECIMAL_SCALE)
+                .map_err(|e| e.to_string())?;
+            for cell in cells {
+                match cell {
+                    Cell::Decimal(value) => builder.append_value(decimal_mantissa(*value)),
+                    Cell::Null => builder.append_null(),
+                    _ => return Err(mismatch()),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        Kind::Float => {
+            let mut builder = Float64Builder::new();
+            for cell in cells {
+                match cell {
+                    Cell::Float(value) => builder.append_value(*value),
+                    Cell::Null => builder.append_null(),
+                    _ => return Err(mismatch()),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        Kind::Date => {
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+            let mut builder = Date32Builder::new();
+            for cell in cells {
+                match cell {
+                    Cell::Date(value) => builder.append_value((*value - epoch).num_days() as i32),
+                    Cell::Null => builder.append_null(),
+                    _ => return Err(mismatch()),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        Kind::Timestamp => {
+            let mut builder = TimestampMicrosecondBuilder::new().with_timezone("UTC");
+            for cell in cells {
+                match cell {
+                    Cell::Timestamp(value) => builder.append_value(value.timestamp_micros()),
+                    Cell::Null => builder.append_null(),
+                    _ => return Err(mismatch()),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    })
+}
+
+impl<W: Write + Send> ParquetEncoder<W> {
+    fn new(columns: &'static [Column], out: W) -> Result<Self, String> {
+        let schema: SchemaRef = Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|c| Field::new(c.name, arrow_type(c.kind), true))
+                .collect::<Vec<_>>(),
+        ));
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(PARQUET_ROW_GROUP_SIZE)
+            .build();
+        let writer = ArrowWriter::try_new(out, schema.clone(), Some(properties))
+            .map_err(|e| format!("Parquet export failed: {}", e))?;
+
+        Ok(ParquetEncoder {
+            writer,
+            schema,
+            columns,
+        })
+    }
+}
+
+impl<W: Write + Send> Encoder for ParquetEncoder<W> {
+    fn write_rows(&mut self, rows: &[Row]) -> Result<(), String> {
+        let arrays = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| column_array(column.kind, rows, index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)
+            .map_err(|e| format!("Parquet export failed: {}", e))?;
+        self.writer
+            .write(&batch)
+            .map_err(|e| format!("Parquet export failed: {}", e))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        self.writer
+            .close()
+            .map(|_| ())
+            .map_err(|e| format!("Parquet export failed: {}", e))
+    }
+}
+
+/// Lado de escritura del cuerpo de la respuesta. Si el cliente se desconecta,
+/// las escrituras fallan con `BrokenPipe` y la exportación se detiene.
+struct BodyWriter {
+    tx: mpsc::Sender<Result<Bytes, io::Error>>,
+}
+
+impl Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        block_on(self.tx.send(Ok(Bytes::copy_from_slice(buf))))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Ejecuta `job` en el pool de hilos bloqueantes y devuelve lo que escribe como
+/// cuerpo de la respuesta. El canal está acotado, así que un cliente lento frena
+/// la exportación en lugar de acumular datos en memoria.
+pub fn stream<F>(job: F) -> impl Stream<Item = Result<Bytes, io::Error>>
+where
+    F: FnOnce(Output<'_>) -> Result<(), String> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(4);
+
+    actix_web::rt::task::spawn_blocking(move || {
+        let mut errors = tx.clone();
+        let mut out = BufWriter::with_capacity(CHUNK_SIZE, BodyWriter { tx });
+        let result = job(&mut out).and_then(|_| out.flush().map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            if !errors.is_closed() {
+                tracing::error!("export failed: {}", e);
+                // Un error en el cuerpo corta la respuesta para que no parezca completa
+                let _ = block_on(errors.send(Err(io::Error::other(e))));
+            }
+        }
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLUMNS: &[Column] = &[
+        Column {
+            name: "lot_code",
+            kind: Kind::Text,
+        },
+        Column {
+            name: "quantity",
+            kind: Kind::Decimal,
+        },
+        Column {
+            name: "harvest_date",
+            kind: Kind::Date,
+        },
+        Column {
+            name: "created_at",
+            kind: Kind::Timestamp,
+        },
+    ];
+
+    fn rows() -> Vec<Row> {
+        vec![
+            vec![
+                Cell::Text("KAI-ABC-2025-0001".into()),
+                Cell::Decimal(Decimal::new(12505, 1)),
+                Cell::Date(NaiveDate::from_ymd_opt(2025, 9, 1).unwrap()),
+                Cell::Timestamp(DateTime::from_timestamp(1_753_351_200, 0).unwrap()),
+            ],
+            vec![
+                Cell::Text("KAI-DEF-2025-0002, \"B\"".into()),
+                Cell::Decimal(Decimal::new(3, 0)),
+                Cell::Null,
+                Cell::Timestamp(DateTime::from_timestamp(1_753_351_200, 0).unwrap()),
+            ],
+        ]
+    }
+
+    fn export(format: ExportFormat) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = encoder(format, COLUMNS, &mut out).unwrap();
+        // Dos tandas, como llegan de la base de datos
+        let rows = rows();
+        encoder.write_rows(&rows[..1]).unwrap();
+        encoder.write_rows(&rows[1..]).unwrap();
+        encoder.finish().unwrap();
+        out
+    }
+
+    #[test]
+    fn csv_has_a_header_and_one_line_per_row() {
+        let csv = String::from_utf8(export(ExportFormat::Csv)).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                "lot_code,quantity,harvest_date,created_at",
+                "KAI-ABC-2025-0001,1250.5,2025-09-01,2025-07-24T10:00:00Z",
+                "\"KAI-DEF-2025-0002, \"\"B\"\"\",3,,2025-07-24T10:00:00Z",
+            ]
+        );
+    }
+
+    #[test]
+    fn parquet_keeps_types_and_all_batches() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let data = export(ExportFormat::Parquet);
+        let reader = SerializedFileReader::new(Bytes::from(data)).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 2);
+
+        let schema = parquet::arrow::parquet_to_arrow_schema(
+            metadata.schema_descr(),
+            metadata.key_value_metadata(),
+        )
+        .unwrap();
+        assert_eq!(
+            schema.field(1).data_type(),
+            &DataType::Decimal128(DECIMAL_PRECISION, DECIMAL_SCALE)
+        );
+        assert_eq!(schema.field(2).data_type(), &DataType::Date32);
+        assert_eq!(decimal_mantissa(Decimal::new(12505, 1)), 1_250_500);
+    }
+
+    #[test]
+    fn xlsx_is_a_zip_workbook() {
+        let data = export(ExportFormat::Xlsx);
+        assert_eq!(&data[..2], b"PK");
+    }
+
+    #[test]
+    fn formats_are_parsed_case_insensitively() {
+        assert_eq!(ExportFormat::parse("XLSX").unwrap(), ExportFormat::Xlsx);
+        assert!(ExportFormat::parse("json").is_err());
+    }
+}
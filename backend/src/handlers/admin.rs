@@ -0,0 +1,239 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use diesel::{Connection, OptionalExtension};
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::{
+    auth::{middleware::{AuthMiddleware, RequireRole}, Claims},
+    database::DbPool,
+    errors::AppError,
+    etag,
+    mail::Mailer,
+    models::{
+        lot::Lot,
+        notification::{self, NewNotification, Notification},
+        producer::Producer,
+        producer_review::{NewProducerReview, ProducerReview},
+        recall::RecallNotification,
+        role::Role,
+    },
+};
+use kairos_common::{PaginationParams, ProducerStatus, ReviewProducerRequest};
+
+pub fn configure() -> impl actix_web::dev::HttpServiceFactory {
+    // RequireRole necesita los claims, por eso AuthMiddleware se registra al final
+    web::scope("/admin")
+        .wrap(RequireRole::admin())
+        .wrap(AuthMiddleware)
+        .route("/producers/pending", web::get().to(list_pending_producers))
+        .route("/producers/{id}/approve", web::post().to(approve_producer))
+        .route("/producers/{id}/reject", web::post().to(reject_producer))
+        .route("/producers/{id}", web::get().to(get_producer))
+        .route("/producers/{id}/reviews", web::get().to(list_producer_reviews))
+        .route("/producers/{id}/role", web::put().to(assign_role))
+        .route("/recall-notifications/dispatch", web::post().to(dispatch_recall_notifications))
+        .route("/lots/{id}/restore", web::post().to(restore_lot))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DispatchQuery {
+    pub batch: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRoleRequest {
+    pub role: Role,
+}
+
+pub async fn get_producer(
+    pool: web::Data<DbPool>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let producer = Producer::find_by_id(&mut conn, id.into_inner())
+        .map_err(|_| AppError::NotFound("Producer not found".into()))?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag::entity_tag(producer.updated_at)))
+        .json(producer))
+}
+
+/// Con `If-Match`, el rol solo cambia si el productor sigue como se leyó.
+pub async fn assign_role(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    id: web::Path<Uuid>,
+    request: web::Json<AssignRoleRequest>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let producer_id = id.into_inner();
+
+    let producer = conn.transaction::<_, AppError, _>(|conn| {
+        Role::lock_for_producer(conn, producer_id)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound("Producer not found".into()))?;
+        let current = Producer::find_by_id(conn, producer_id)?;
+        etag::check_if_match(&req, &etag::entity_tag(current.updated_at), &current)?;
+
+        Role::assign(conn, producer_id, request.role)?;
+        Ok(Producer::find_by_id(conn, producer_id)?)
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag::entity_tag(producer.updated_at)))
+        .json(serde_json::json!({
+        "producer": producer,
+        "role": request.role,
+    })))
+}
+
+pub async fn list_pending_producers(
+    pool: web::Data<DbPool>,
+    pagination: web::Query<PaginationParams>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+
+    let page = pagination.page.unwrap_or(1).max(1);
+    let per_page = pagination.per_page.unwrap_or(10).clamp(1, 100);
+    let offset = (page - 1) * per_page;
+
+    let (producers, total) = ProducerReview::pending(&mut conn, offset, per_page)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "producers": producers,
+        "page": page,
+        "per_page": per_page,
+        "total": total
+    })))
+}
+
+/// Motivo de la revisión sin espacios alrededor. Uno en blanco es como no darlo; si
+/// se da, necesita al menos 3 caracteres, como exige `producer_reviews`.
+fn review_reason(reason: Option<String>) -> Result<Option<String>, AppError> {
+    let Some(reason) = reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty()) else {
+        return Ok(None);
+    };
+    if reason.chars().count() < 3 {
+        return Err(AppError::BadRequest("reason must be at least 3 characters".into()));
+    }
+    Ok(Some(reason))
+}
+
+pub async fn approve_producer(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: Option<web::Json<ReviewProducerRequest>>,
+) -> Result<HttpResponse, AppError> {
+    let reason = review_reason(request.and_then(|r| r.into_inner().reason))?;
+    review_producer(&pool, claims.sub, id.into_inner(), true, reason)
+}
+
+pub async fn reject_producer(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: web::Json<ReviewProducerRequest>,
+) -> Result<HttpResponse, AppError> {
+    let reason = review_reason(request.into_inner().reason)?
+        .ok_or_else(|| AppError::BadRequest("A rejection reason is required".into()))?;
+    review_producer(&pool, claims.sub, id.into_inner(), false, Some(reason))
+}
+
+pub async fn list_producer_reviews(
+    pool: web::Data<DbPool>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+
+    let reviews = ProducerReview::find_by_producer(&mut conn, id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(reviews))
+}
+
+// Cambia el estado, guarda la decisión y notifica al productor en una sola transacción
+fn review_producer(
+    pool: &DbPool,
+    reviewer_id: Uuid,
+    producer_id: Uuid,
+    approved: bool,
+    reason: Option<String>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+
+    let review = conn.transaction::<_, AppError, _>(|conn| {
+        let producer = Producer::find_by_id(conn, producer_id)?;
+
+        let target = if approved { ProducerStatus::Approved } else { ProducerStatus::Rejected };
+        if producer.status == target {
+            return Err(AppError::BadRequest(format!("Producer is already {:?}", target).to_lowercase()));
+        }
+
+        let review = ProducerReview::record(conn, NewProducerReview {
+            producer_id,
+            reviewed_by: reviewer_id,
+            approved,
+            reason: reason.clone(),
+        })?;
+
+        let (title, notification_type) = if approved {
+            ("Your producer account was approved", notification::PRODUCER_APPROVED)
+        } else {
+            ("Your producer account was rejected", notification::PRODUCER_REJECTED)
+        };
+
+        Notification::create(conn, NewNotification {
+            user_id: producer_id,
+            title: title.to_string(),
+            message: reason.unwrap_or_else(|| title.to_string()),
+            notification_type: notification_type.to_string(),
+            is_read: false,
+            metadata: Some(serde_json::json!({ "review_id": review.id })),
+        })?;
+
+        Ok(review)
+    })?;
+
+    Ok(HttpResponse::Ok().json(review))
+}
+
+/// Envía una tanda de avisos de retirada pendientes. También puede ejecutarse de
+/// forma periódica con `RecallNotification::dispatch_pending`.
+pub async fn dispatch_recall_notifications(
+    pool: web::Data<DbPool>,
+    mailer: web::Data<dyn Mailer>,
+    query: web::Query<DispatchQuery>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+
+    let batch = query.batch.unwrap_or(100).clamp(1, 1000);
+    let summary = RecallNotification::dispatch_pending(&mut conn, mailer.get_ref(), batch)?;
+
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+/// Deshace el borrado de un lote junto con sus eventos.
+pub async fn restore_lot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+
+    let lot = Lot::restore(&mut conn, id.into_inner(), claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(lot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn review_reasons_are_trimmed_and_need_three_characters() {
+        assert_eq!(review_reason(None).unwrap(), None);
+        assert_eq!(review_reason(Some("   ".into())).unwrap(), None);
+        assert_eq!(review_reason(Some("  Finca visitada ".into())).unwrap().as_deref(), Some("Finca visitada"));
+        assert!(matches!(review_reason(Some(" ok ".into())), Err(AppError::BadRequest(_))));
+        assert!(matches!(review_reason(Some("añ".into())), Err(AppError::BadRequest(_))));
+    }
+}
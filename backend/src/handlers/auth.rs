@@ -1,16 +1,20 @@
 use actix_web::{web, HttpResponse};
 
 use crate::{
+    config::AppConfig,
     database::DbPool,
     errors::AppError,
+    mail::Mailer,
     models::producer::Producer,
-    auth::jwt::create_token,
+    auth::{session, verification},
 };
 // Apuntar a los DTOs de kairos_common, corrigiendo la ruta
-use kairos_common::{LoginRequest, RegisterProducerRequest, TokenResponse};
+use kairos_common::{LoginRequest, RegisterProducerRequest};
 
 pub async fn register(
     pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    mailer: web::Data<dyn Mailer>,
     create_request: web::Json<RegisterProducerRequest>,
 ) -> Result<HttpResponse, AppError> {
     let mut conn = pool.get()?;
@@ -23,20 +27,21 @@ pub async fn register(
     
     // Crear el productor
     let producer = Producer::create(&mut conn, create_request.into_inner())?;
+
+    // Enviar el enlace de verificación; si falla, el productor puede pedir el reenvío
+    if let Err(e) = verification::send_verification_email(&mut conn, &config, mailer.get_ref(), &producer) {
+        tracing::error!(producer_id = %producer.id, "failed to send verification email: {}", e);
+    }
     
-    // Generar token JWT
-    let token = create_token(
-        &producer.id.to_string(),
-        &producer.email,
-        "producer",
-        "your-secret-key", // ¡Esto debería venir de la configuración!
-    )?;
+    // Generar access + refresh token
+    let token = session::issue_tokens(&mut conn, &config, &producer)?;
     
     Ok(HttpResponse::Created().json(token))
 }
 
 pub async fn login(
     pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
     request: web::Json<LoginRequest>,
 ) -> Result<HttpResponse, AppError> {
     let mut conn = pool.get()?;
@@ -45,13 +50,8 @@ pub async fn login(
     let producer = Producer::authenticate(&mut conn, &request.email, &request.password)
         .map_err(|_| AppError::Unauthorized("Invalid credentials".to_string()))?;
     
-    // Generar token JWT
-    let token = create_token(
-        &producer.id.to_string(),
-        &producer.email,
-        "producer",
-        "your-secret-key", // ¡Esto debería venir de la configuración!
-    )?;
+    // Generar access + refresh token
+    let token = session::issue_tokens(&mut conn, &config, &producer)?;
     
     Ok(HttpResponse::Ok().json(token))
 }
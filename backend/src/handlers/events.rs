@@ -1,58 +1,188 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use diesel::{pg::PgConnection, Connection};
 use uuid::Uuid;
 use crate::{
-    models::event::Event,
+    auth::{middleware::ProducerAuthMiddleware, ownership, Claims},
+    handlers::lots::{ensure_can_list_deleted, find_owned_lot, find_owned_lot_or_deleted, LotOwnerQuery},
+    models::{
+        event::Event,
+        event_chain::seal_pending,
+        event_edit::{validate_event_fields, OwnedEvent},
+        lot_genealogy::lock_lots,
+        pagination::Page,
+    },
     errors::AppError,
-    database::DbPool
+    database::DbPool,
+    etag,
 };
 // Apuntar a los DTOs de kairos_common en lugar de los schemas internos
-use kairos_common::{CreateEventRequest, UpdateEventRequest};
+use kairos_common::{ApiResponse, CreateEventRequest, EventQuery, UpdateEventRequest};
 
 
-pub fn configure() -> actix_web::Scope {
-    // Los eventos ahora se anidan bajo los lotes para tener el contexto del lot_id
-    web::scope("/lots/{lot_id}/events")
-        .route("", web::post().to(create_event))
-        // Estas rutas probablemente deberían estar en un scope /events/{id} global
-        // pero por ahora las mantenemos simples para la compilación.
-        // .route("/{id}", web::get().to(get_event))
-        // .route("/{id}", web::put().to(update_event))
-        // .route("/{id}", web::delete().to(delete_event))
+pub fn configure() -> impl actix_web::dev::HttpServiceFactory {
+    (
+        // Alta y listado en el contexto de un lote
+        web::scope("/lots/{lot_id}/events")
+            .wrap(ProducerAuthMiddleware::new())
+            .route("", web::post().to(create_event))
+            .route("", web::get().to(list_lot_events)),
+        // Consulta global de los eventos del productor y operaciones por id
+        web::scope("/events")
+            .wrap(ProducerAuthMiddleware::new())
+            .route("", web::get().to(list_events))
+            .route("/{id}", web::get().to(get_event))
+            .route("/{id}", web::put().to(update_event))
+            .route("/{id}", web::delete().to(delete_event)),
+    )
+}
+
+/// Carga un evento comprobando que su lote pertenece a quien llama (o que es admin).
+fn find_owned_event(
+    conn: &mut PgConnection,
+    claims: &Claims,
+    event_id: Uuid,
+) -> Result<OwnedEvent, AppError> {
+    ownership::owned(claims, Event::find_with_owner(conn, event_id), |owned| owned.producer_id, "Event")
+}
+
+fn paginated(events: Vec<Event>, total: i64, query: &EventQuery) -> HttpResponse {
+    let page = Page::new(query.page, query.per_page);
+    HttpResponse::Ok().json(ApiResponse {
+        data: events,
+        meta: Some(page.meta(total)),
+    })
 }
 
 pub async fn create_event(
     pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
     path: web::Path<Uuid>, // Extraer el lot_id de la ruta
     request: web::Json<CreateEventRequest>,
 ) -> Result<HttpResponse, AppError> {
     let lot_id = path.into_inner();
     let mut conn = pool.get()?;
 
-    let event = Event::create(&mut conn, lot_id, request.into_inner())?;
+    // Solo el dueño del lote (o un admin) puede registrarle eventos
+    let lot = find_owned_lot(&mut conn, &claims, lot_id)?;
+
+    let request = request.into_inner();
+    validate_event_fields(request.event_type, request.description.as_deref())?;
+
+    // El evento se sella en la cadena del lote dentro de la misma transacción
+    let event = conn.transaction::<_, AppError, _>(|conn| {
+        let event = Event::create(conn, lot.id, request)?;
+        seal_pending(conn, lot.id)?;
+        Ok(event)
+    })?;
     Ok(HttpResponse::Created().json(event))
 }
 
-/*
-// Estas funciones necesitan ser re-evaluadas en el contexto de las nuevas rutas
+pub async fn list_lot_events(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    path: web::Path<Uuid>,
+    query: web::Query<EventQuery>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+
+    let lot = find_owned_lot_or_deleted(&mut conn, &claims, path.into_inner(), query.include_deleted)?;
+
+    let query = EventQuery {
+        lot_id: Some(lot.id),
+        ..query.into_inner()
+    };
+    let (events, total) = Event::search(&mut conn, lot.producer_id, &query)?;
+
+    Ok(paginated(events, total, &query))
+}
+
+pub async fn list_events(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    query: web::Query<EventQuery>,
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+    ensure_can_list_deleted(&claims, query.include_deleted)?;
+
+    let (events, total) = Event::search(&mut conn, producer_id, &query)?;
+
+    Ok(paginated(events, total, &query))
+}
+
+/// ETag de un evento: cambia cuando se corrige o se anula, aunque el evento
+/// original no se toque nunca. Se calcula con la última revisión de la cadena.
+fn event_tag(conn: &mut PgConnection, event: &Event) -> Result<(String, Option<Event>), AppError> {
+    let latest = Event::latest_correction(conn, event.id)?;
+    let created_at = latest.as_ref().map_or(event.created_at, |latest| latest.created_at);
+    Ok((etag::entity_tag(created_at), latest))
+}
+
 pub async fn get_event(
     pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
     id: web::Path<Uuid>,
-) -> impl Responder {
-// ...
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+
+    let owned = find_owned_event(&mut conn, &claims, id.into_inner())?;
+    let (tag, _) = event_tag(&mut conn, &owned.event)?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, tag))
+        .json(owned.event))
+}
+
+/// Bloquea el lote del evento y comprueba `If-Match` contra su última revisión,
+/// para que nadie lo corrija entre la comprobación y el cambio.
+fn lock_and_check_if_match(
+    conn: &mut PgConnection,
+    req: &HttpRequest,
+    claims: &Claims,
+    event_id: Uuid,
+) -> Result<OwnedEvent, AppError> {
+    let owned = find_owned_event(conn, claims, event_id)?;
+    lock_lots(conn, &[owned.event.lot_id])?;
+    // Releída con el lote bloqueado por si cambió su estado mientras tanto
+    let owned = find_owned_event(conn, claims, event_id)?;
+    let (tag, latest) = event_tag(conn, &owned.event)?;
+    etag::check_if_match(req, &tag, latest.as_ref().unwrap_or(&owned.event))?;
+    Ok(owned)
 }
 
 pub async fn update_event(
+    req: HttpRequest,
     pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
     id: web::Path<Uuid>,
     request: web::Json<UpdateEventRequest>,
-) -> impl Responder {
-// ...
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let event_id = id.into_inner();
+
+    // Los eventos no se reescriben: la corrección se añade como un evento nuevo
+    let correction = conn.transaction::<_, AppError, _>(|conn| {
+        let owned = lock_and_check_if_match(conn, &req, &claims, event_id)?;
+        Event::correct(conn, &owned, request.into_inner(), claims.sub)
+    })?;
+
+    Ok(HttpResponse::Created().json(correction))
 }
 
 pub async fn delete_event(
+    req: HttpRequest,
     pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
     id: web::Path<Uuid>,
-) -> impl Responder {
-// ...
-    }
-*/ 
\ No newline at end of file
+) -> Result<HttpResponse, AppError> {
+    let mut conn = pool.get()?;
+    let event_id = id.into_inner();
+
+    let retraction = conn.transaction::<_, AppError, _>(|conn| {
+        let owned = lock_and_check_if_match(conn, &req, &claims, event_id)?;
+        Event::retract(conn, &owned, claims.sub)
+    })?;
+
+    Ok(HttpResponse::Created().json(retraction))
+}
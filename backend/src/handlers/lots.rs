@@ -1,19 +1,158 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use diesel::{pg::PgConnection, Connection};
+use futures_util::TryStreamExt;
+use serde::Deserialize;
 use uuid::Uuid;
 use crate::{
-    models::{lot::Lot, producer::Producer},
+    auth::{middleware::ProducerAuthMiddleware, ownership, Claims},
+    certificates::KeyRing,
+    handlers::plots::find_owned_plot,
+    models::{
+        certificate::Certificate, event_chain, lot::Lot, lot_genealogy::{lock_lots, MAX_GENEALOGY_DEPTH},
+        lot_export::LotExport, lot_geo, lot_import::MAX_IMPORT_BYTES, pagination::Page, plot::Plot, producer::Producer, role::Role,
+        shipment::Shipment,
+    },
     errors::AppError
 };
-use kairos_common::{CreateLotRequest, UpdateLotRequest};
+use kairos_common::{
+    geo::LotGeoQuery,
+    harvest::{RecordHarvestRequest, RecordLossRequest, ReconciliationQuery},
+    history::{LotAsOfQuery, VersionDiffQuery},
+    plot::AssignPlotRequest, units::QuantityQuery, ApiResponse, ArchiveSeasonRequest, CreateLotRequest, CreateShipmentRequest,
+    EventQuery, EventType, LotQuery, LotTransitionRequest, MergeLotsRequest, SplitLotRequest, UpdateLotRequest,
+};
 use crate::database::DbPool;
+use crate::{config::AppConfig, etag, exports, labels};
 
-pub fn configure() -> actix_web::Scope {
+pub fn configure() -> impl actix_web::dev::HttpServiceFactory {
     web::scope("/lots")
+        .wrap(ProducerAuthMiddleware::new())
         .route("", web::post().to(create_lot))
         .route("", web::get().to(list_lots))
+        .route("/merge", web::post().to(merge_lots))
+        .route("/archive", web::post().to(archive_season))
+        .route("/geo", web::get().to(search_lots_by_location))
+        .route("/import", web::post().to(import_lots))
+        .route("/export", web::get().to(export_lots))
+        .route("/summary", web::get().to(get_lots_summary))
+        .route("/reconciliation", web::get().to(get_harvest_reconciliation))
         .route("/{id}", web::get().to(get_lot))
         .route("/{id}", web::put().to(update_lot))
         .route("/{id}", web::delete().to(delete_lot))
+        .route("/{id}/archive", web::post().to(archive_lot))
+        .route("/{id}/unarchive", web::post().to(unarchive_lot))
+        .route("/{id}/transitions", web::get().to(get_lot_transitions))
+        .route("/{id}/transitions", web::post().to(transition_lot))
+        .route("/{id}/verify", web::get().to(verify_lot_events))
+        .route("/{id}/labels.pdf", web::get().to(get_lot_labels))
+        .route("/{id}/split", web::post().to(split_lot))
+        .route("/{id}/genealogy", web::get().to(get_lot_genealogy))
+        .route("/{id}/shipments", web::post().to(create_shipment))
+        .route("/{id}/shipments", web::get().to(list_shipments))
+        .route("/{id}/epcis", web::get().to(export_lot_epcis))
+        .route("/{id}/epcis", web::post().to(import_lot_epcis))
+        .route("/{id}/certificates", web::post().to(issue_certificate))
+        .route("/{id}/plot", web::get().to(get_lot_plot))
+        .route("/{id}/plot", web::put().to(assign_lot_plot))
+        .route("/{id}/harvest", web::get().to(get_lot_harvest))
+        .route("/{id}/harvest", web::put().to(record_lot_harvest))
+        .route("/{id}/losses", web::post().to(record_lot_loss))
+        .route("/{id}/versions", web::get().to(list_lot_versions))
+        .route("/{id}/versions/diff", web::get().to(diff_lot_versions))
+        .route("/{id}/as-of", web::get().to(get_lot_as_of))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LotOwnerQuery {
+    /// Solo los administradores pueden listar lotes de otro productor.
+    pub producer_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EpcisQuery {
+    /// GTIN del producto; por defecto, `EPCIS_GTIN`.
+    pub gtin: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LotImportQuery {
+    /// Solo valida y devuelve el informe, sin crear lotes.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LotExportQuery {
+    /// `csv`, `xlsx` o `parquet`.
+    pub format: String,
+    /// `lots` (por defecto) o `events`.
+    pub dataset: Option<String>,
+}
+
+/// Filtros de `EventQuery` en la exportación. Los que coinciden con los de `LotQuery`
+/// llevan el prefijo `event_` para que se puedan dar por separado.
+#[derive(Debug, Deserialize)]
+pub struct ExportEventQuery {
+    pub event_type: Option<EventType>,
+    pub lot_id: Option<Uuid>,
+    #[serde(default)]
+    pub event_include_archived: bool,
+    /// Solo para administradores.
+    #[serde(default)]
+    pub event_include_deleted: bool,
+}
+
+impl From<ExportEventQuery> for EventQuery {
+    fn from(query: ExportEventQuery) -> Self {
+        EventQuery {
+            event_type: query.event_type,
+            lot_id: query.lot_id,
+            include_archived: query.event_include_archived,
+            include_deleted: query.event_include_deleted,
+            ..EventQuery::default()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GenealogyQuery {
+    /// Pasos a recorrer en cada sentido; por defecto, toda la genealogía.
+    pub max_depth: Option<i32>,
+}
+
+/// Carga un lote comprobando que pertenece a quien llama (o que es admin).
+pub(crate) fn find_owned_lot(
+    conn: &mut PgConnection,
+    claims: &Claims,
+    lot_id: Uuid,
+) -> Result<Lot, AppError> {
+    ownership::owned(claims, Lot::find_live(conn, lot_id), |lot| lot.producer_id, "Lot")
+}
+
+/// Los lotes y eventos borrados solo se listan para los administradores.
+pub(crate) fn ensure_can_list_deleted(claims: &Claims, include_deleted: bool) -> Result<(), AppError> {
+    if include_deleted && claims.role != Role::Admin {
+        return Err(AppError::Forbidden(
+            "include_deleted is only available to administrators".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Como `find_owned_lot`; con `include_deleted` (solo admins) también encuentra
+/// lotes borrados.
+pub(crate) fn find_owned_lot_or_deleted(
+    conn: &mut PgConnection,
+    claims: &Claims,
+    lot_id: Uuid,
+    include_deleted: bool,
+) -> Result<Lot, AppError> {
+    ensure_can_list_deleted(claims, include_deleted)?;
+    if !include_deleted {
+        return find_owned_lot(conn, claims, lot_id);
+    }
+    ownership::owned(claims, Lot::find_by_id(conn, lot_id), |lot| lot.producer_id, "Lot")
 }
 
 pub async fn create_lot(
@@ -25,55 +164,664 @@ pub async fn create_lot(
     let producer_id = producer.into_inner().id;
 
     let lot = Lot::create(conn, producer_id, request.into_inner())?;
-    
+
     Ok(HttpResponse::Created().json(lot))
 }
 
 pub async fn list_lots(
     pool: web::Data<DbPool>,
-    producer_id: web::Query<Uuid>,
-) -> impl Responder {
-    let conn = &mut pool.get().unwrap();
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    query: web::Query<LotQuery>,
+    quantity: web::Query<QuantityQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+    ensure_can_list_deleted(&claims, query.include_deleted)?;
+    let target = quantity.target().map_err(AppError::BadRequest)?;
+
+    let (lots, total) = Lot::search(conn, producer_id, &query)?;
+    let summary = Lot::quantity_summary(conn, producer_id, &query, target)?;
+    let page = Page::new(query.page, query.per_page);
+
+    // Los totales abarcan todas las coincidencias, no solo la página
+    let mut meta = page.meta(total);
+    meta["totals"] = serde_json::to_value(&summary.totals)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        data: lots,
+        meta: Some(meta),
+    }))
+}
+
+/// Cantidades de los lotes filtrados, por producto y en total, en `normalize_to`.
+pub async fn get_lots_summary(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    query: web::Query<LotQuery>,
+    quantity: web::Query<QuantityQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+    ensure_can_list_deleted(&claims, query.include_deleted)?;
+    let target = quantity.target().map_err(AppError::BadRequest)?;
+
+    let summary = Lot::quantity_summary(conn, producer_id, &query, target)?;
+
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+/// Estimado frente a cosechado por cultivo o por productor, en `normalize_to`. Sin
+/// `producer_id`, un admin ve a todos los productores.
+pub async fn get_harvest_reconciliation(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    query: web::Query<ReconciliationQuery>,
+    quantity: web::Query<QuantityQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    let producer_id = match owner.producer_id {
+        None if claims.role == Role::Admin => None,
+        requested => Some(ownership::resolve_owner(&claims, requested)?),
+    };
+    let target = quantity.target().map_err(AppError::BadRequest)?;
+
+    let report = Lot::reconciliation(conn, producer_id, query.group_by.unwrap_or_default(), target)?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Contenido del campo `file` de un formulario multipart.
+async fn read_csv_upload(mut payload: Multipart) -> Result<Vec<u8>, AppError> {
+    let invalid = |e: actix_multipart::MultipartError| {
+        AppError::BadRequest(format!("Invalid multipart body: {}", e))
+    };
+
+    while let Some(mut field) = payload.try_next().await.map_err(invalid)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(invalid)? {
+            if data.len() + chunk.len() > MAX_IMPORT_BYTES {
+                return Err(AppError::BadRequest(format!(
+                    "CSV file must be at most {} bytes",
+                    MAX_IMPORT_BYTES
+                )));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        return Ok(data);
+    }
+    Err(AppError::BadRequest("multipart field 'file' is required".into()))
+}
+
+pub async fn import_lots(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    options: web::Query<LotImportQuery>,
+    payload: Multipart,
+) -> Result<HttpResponse, AppError> {
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+    let data = read_csv_upload(payload).await?;
+    let conn = &mut pool.get()?;
+
+    let result = Lot::import_csv(conn, producer_id, &data, options.dry_run, claims.sub)?;
+
+    Ok(if !result.errors.is_empty() && !options.dry_run {
+        HttpResponse::UnprocessableEntity().json(result)
+    } else if result.created.is_empty() {
+        HttpResponse::Ok().json(result)
+    } else {
+        HttpResponse::Created().json(result)
+    })
+}
 
-    match Lot::find_by_producer(conn, producer_id.into_inner(), 1, 10) {
-        Ok(lots) => HttpResponse::Ok().json(lots),
-        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+/// Los filtros de `LotQuery` y `ExportEventQuery` se aplican; orden y paginación se
+/// rechazan, la exportación incluye todas las coincidencias.
+pub async fn export_lots(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    options: web::Query<LotExportQuery>,
+    lots: web::Query<LotQuery>,
+    events: web::Query<ExportEventQuery>,
+) -> Result<HttpResponse, AppError> {
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+    ensure_can_list_deleted(&claims, lots.include_deleted || events.event_include_deleted)?;
+    let export = LotExport::new(
+        producer_id,
+        &options.format,
+        options.dataset.as_deref(),
+        lots.into_inner(),
+        events.into_inner().into(),
+    )?;
+    let pool = pool.get_ref().clone();
+
+    Ok(HttpResponse::Ok()
+        .content_type(export.format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", export.file_name()),
+        ))
+        .streaming(exports::stream(move |out| {
+            let conn = &mut pool.get().map_err(|e| e.to_string())?;
+            export.run(conn, out)
+        })))
+}
+
+pub async fn search_lots_by_location(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    query: web::Query<LotQuery>,
+    geo: web::Query<LotGeoQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    // Sin producer_id, un admin (logística) busca entre los lotes de todos
+    let producer_id = match owner.producer_id {
+        None if claims.role == Role::Admin => None,
+        requested => Some(ownership::resolve_owner(&claims, requested)?),
+    };
+    ensure_can_list_deleted(&claims, query.include_deleted)?;
+
+    let geojson = match geo.format.as_deref() {
+        None | Some("json") => false,
+        Some("geojson") => true,
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid format '{}', expected 'json' or 'geojson'",
+                other
+            )))
+        }
+    };
+
+    let (lots, total) = Lot::geo_search(conn, producer_id, &query, &geo)?;
+
+    if geojson {
+        return Ok(HttpResponse::Ok()
+            .content_type("application/geo+json")
+            .json(lot_geo::feature_collection(&lots)?));
     }
+    let meta = match geo.nearest {
+        Some(_) => serde_json::json!({ "total": total }),
+        None => Page::new(query.page, query.per_page).meta(total),
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        data: lots,
+        meta: Some(meta),
+    }))
 }
 
 pub async fn get_lot(
     pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
     id: web::Path<Uuid>,
-) -> impl Responder {
-    let conn = &mut pool.get().unwrap();
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
 
-    match Lot::find_by_id(conn, id.into_inner()) {
-        Ok(lot) => HttpResponse::Ok().json(lot),
-        Err(e) => HttpResponse::NotFound().json(e.to_string()),
-    }
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag::entity_tag(lot.updated_at)))
+        .json(lot))
 }
 
+/// Con `If-Match`, solo se aplica si nadie ha editado el lote desde que se leyó.
 pub async fn update_lot(
+    req: HttpRequest,
     pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
     id: web::Path<Uuid>,
     request: web::Json<UpdateLotRequest>,
-) -> impl Responder {
-    let conn = &mut pool.get().unwrap();
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let request = request.into_inner();
+    if request.current_status.is_some() {
+        return Err(AppError::BadRequest(
+            "current_status cannot be set directly, use POST /lots/{id}/transitions".into(),
+        ));
+    }
 
-    match Lot::update(conn, id.into_inner(), request.into_inner().into()) {
-        Ok(lot) => HttpResponse::Ok().json(lot),
-        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    if request.location_coordinates.is_some() && Plot::find_by_lot(conn, lot.id)?.is_some() {
+        return Err(AppError::BadRequest(
+            "location_coordinates follows the lot's plot, use PUT /lots/{id}/plot".into(),
+        ));
     }
+    // La edición y su versión se guardan juntas, con la fila bloqueada
+    let lot = conn.transaction::<_, AppError, _>(|conn| {
+        let previous = lock_lots(conn, &[lot.id])?
+            .pop()
+            .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+        etag::check_if_match(&req, &etag::entity_tag(previous.updated_at), &previous)?;
+        let before = Lot::snapshot(conn, previous.id)?;
+        let updated = Lot::update(conn, previous.id, request.into())?;
+        Lot::record_version(conn, before, claims.sub)?;
+        Ok(updated)
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag::entity_tag(lot.updated_at)))
+        .json(lot))
 }
 
 pub async fn delete_lot(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    // El lote y sus eventos se conservan; un admin lo puede restaurar
+    conn.transaction::<_, AppError, _>(|conn| {
+        let current = lock_lots(conn, &[lot.id])?
+            .pop()
+            .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+        etag::check_if_match(&req, &etag::entity_tag(current.updated_at), &current)?;
+        Lot::soft_delete(conn, current.id, claims.sub)
+    })?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn archive_lot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let archived_at = Lot::archive(conn, lot.id, claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "lot_id": lot.id,
+        "archived_at": archived_at,
+    })))
+}
+
+pub async fn unarchive_lot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    Lot::unarchive(conn, lot.id, claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "lot_id": lot.id,
+        "archived_at": null,
+    })))
+}
+
+/// Archiva de una vez los lotes cerrados de una temporada terminada.
+pub async fn archive_season(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    request: web::Json<ArchiveSeasonRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+
+    let archived = Lot::archive_season(conn, producer_id, request.season_end, claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "archived": archived.len(),
+        "lot_ids": archived,
+    })))
+}
+
+pub async fn get_lot_transitions(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "current_status": lot.current_status,
+        "allowed_transitions": lot.current_status.allowed_transitions(),
+    })))
+}
+
+pub async fn transition_lot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: web::Json<LotTransitionRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let lot = Lot::transition(conn, &lot, &request, claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(lot))
+}
+
+pub async fn verify_lot_events(
     pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
     id: web::Path<Uuid>,
-) -> impl Responder {
-    let conn = &mut pool.get().unwrap();
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let report = event_chain::verify_lot(conn, lot.id)?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub async fn get_lot_labels(
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    // Las etiquetas salen en el idioma del productor dueño del lote, no del que las pide
+    let producer = Producer::find_by_id(conn, lot.producer_id)?;
+
+    let label = labels::LabelData {
+        harvest_is_estimate: lot.actual_harvest_date.is_none(),
+        harvest_date: lot.actual_harvest_date.unwrap_or(lot.estimated_harvest_date),
+        farm_name: producer.farm_name,
+        product_name: lot.product_name,
+        lot_code: lot.lot_code,
+    };
+    let url = labels::verification_url(&config, &label.lot_code);
+    let pdf = labels::label_sheet_pdf(&label, &url, producer.language_preference)?;
 
-    match Lot::delete(conn, id.into_inner()) {
-        Ok(_) => HttpResponse::NoContent().finish(),
-        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("inline; filename=\"labels-{}.pdf\"", label.lot_code),
+        ))
+        .body(pdf))
+}
+
+pub async fn split_lot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: web::Json<SplitLotRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let children = Lot::split(conn, lot.id, &request, claims.sub)?;
+
+    Ok(HttpResponse::Created().json(children))
+}
+
+pub async fn merge_lots(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    request: web::Json<MergeLotsRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    for source in &request.sources {
+        find_owned_lot(conn, &claims, source.lot_id)?;
     }
-} 
\ No newline at end of file
+    let lot = Lot::merge(conn, &request, claims.sub)?;
+
+    Ok(HttpResponse::Created().json(lot))
+}
+
+pub async fn get_lot_genealogy(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    query: web::Query<GenealogyQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let genealogy = Lot::genealogy(conn, &lot, query.max_depth.unwrap_or(MAX_GENEALOGY_DEPTH))?;
+
+    Ok(HttpResponse::Ok().json(genealogy))
+}
+
+pub async fn create_shipment(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: web::Json<CreateShipmentRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let shipment = Shipment::create(conn, lot.id, &request, claims.sub)?;
+
+    Ok(HttpResponse::Created().json(shipment))
+}
+
+pub async fn list_shipments(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let shipments = Shipment::find_by_lot(conn, lot.id)?;
+
+    Ok(HttpResponse::Ok().json(shipments))
+}
+
+pub async fn export_lot_epcis(
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    query: web::Query<EpcisQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let gtin = query
+        .gtin
+        .as_deref()
+        .or(config.epcis_gtin.as_deref())
+        .ok_or_else(|| AppError::BadRequest("gtin is required (or set EPCIS_GTIN)".into()))?;
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let document = Lot::epcis_document(conn, &lot, &config.epcis_digital_link_base, gtin)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/ld+json")
+        .json(document))
+}
+
+pub async fn import_lot_epcis(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    document: web::Json<serde_json::Value>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let result = Lot::import_epcis(conn, &lot, &document, claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn issue_certificate(
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<KeyRing>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let certificate = Certificate::issue(conn, &lot, &keys, &config.certificate_issuer, claims.sub)?;
+
+    Ok(HttpResponse::Created().json(certificate))
+}
+
+pub async fn get_lot_plot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let plot = Plot::find_by_lot(conn, lot.id)?
+        .ok_or_else(|| AppError::NotFound("Lot has no plot".into()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/geo+json")
+        .json(plot.feature()))
+}
+
+pub async fn assign_lot_plot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: web::Json<AssignPlotRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let plot = match request.plot_id {
+        Some(plot_id) => {
+            let plot = find_owned_plot(conn, &claims, plot_id)?;
+            // Un admin tampoco puede mezclar parcelas y lotes de productores distintos
+            if plot.producer_id != lot.producer_id {
+                return Err(AppError::BadRequest(
+                    "Plot belongs to a different producer than the lot".into(),
+                ));
+            }
+            Some(plot)
+        }
+        None => None,
+    };
+    Plot::assign(conn, lot.id, plot.as_ref(), claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "lot_id": lot.id,
+        "plot": plot.as_ref().map(Plot::summary),
+    })))
+}
+
+pub async fn get_lot_harvest(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let harvest = Lot::harvest(conn, &lot)?;
+
+    Ok(HttpResponse::Ok().json(harvest))
+}
+
+/// Registra o corrige la cantidad realmente cosechada.
+pub async fn record_lot_harvest(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: web::Json<RecordHarvestRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let harvest = Lot::record_harvest(conn, lot.id, &request, claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(harvest))
+}
+
+pub async fn record_lot_loss(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: web::Json<RecordLossRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let loss = Lot::record_loss(conn, lot.id, &request, claims.sub)?;
+
+    Ok(HttpResponse::Created().json(loss))
+}
+
+pub async fn list_lot_versions(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let versions = Lot::versions(conn, lot.id)?;
+
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+/// Diferencias campo a campo entre dos versiones, en cualquier orden.
+pub async fn diff_lot_versions(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    query: web::Query<VersionDiffQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let diff = Lot::version_diff(conn, lot.id, query.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(diff))
+}
+
+pub async fn get_lot_as_of(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    query: web::Query<LotAsOfQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let lot = find_owned_lot(conn, &claims, id.into_inner())?;
+    let as_of = Lot::as_of(conn, &lot, query.at)?;
+
+    Ok(HttpResponse::Ok().json(as_of))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims_for(role: Role) -> Claims {
+        Claims::new(Uuid::new_v4(), Uuid::new_v4(), role, 900)
+    }
+
+    #[test]
+    fn only_admins_can_list_deleted_lots() {
+        assert!(ensure_can_list_deleted(&claims_for(Role::Admin), true).is_ok());
+        assert!(matches!(
+            ensure_can_list_deleted(&claims_for(Role::Producer), true),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(ensure_can_list_deleted(&claims_for(Role::Producer), false).is_ok());
+    }
+}
@@ -1,4 +1,8 @@
+pub mod admin;
 pub mod auth;
 pub mod lots;
+pub mod plots;
+pub mod packaging_weights;
 pub mod events;
-pub mod public; 
\ No newline at end of file
+pub mod public;
+pub mod recalls; 
\ No newline at end of file
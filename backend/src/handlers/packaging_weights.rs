@@ -0,0 +1,60 @@
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+use crate::{
+    auth::{middleware::ProducerAuthMiddleware, ownership, Claims},
+    handlers::lots::LotOwnerQuery,
+    models::packaging_weight::PackagingWeight,
+    errors::AppError,
+    database::DbPool
+};
+use kairos_common::units::SetPackagingWeightRequest;
+
+pub fn configure() -> impl actix_web::dev::HttpServiceFactory {
+    web::scope("/packaging-weights")
+        .wrap(ProducerAuthMiddleware::new())
+        .route("", web::get().to(list_packaging_weights))
+        .route("", web::put().to(set_packaging_weight))
+        .route("/{id}", web::delete().to(delete_packaging_weight))
+}
+
+pub async fn list_packaging_weights(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+
+    let weights = PackagingWeight::find_by_producer(conn, producer_id)?;
+
+    Ok(HttpResponse::Ok().json(weights))
+}
+
+/// Crea o reemplaza el peso del envase de un producto.
+pub async fn set_packaging_weight(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+    request: web::Json<SetPackagingWeightRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+
+    let weight = PackagingWeight::set(conn, producer_id, &request)?;
+
+    Ok(HttpResponse::Ok().json(weight))
+}
+
+pub async fn delete_packaging_weight(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let weight = PackagingWeight::find_by_id(conn, id.into_inner())?;
+    ownership::ensure_owner(&claims, weight.producer_id, "Packaging weight")?;
+    PackagingWeight::delete(conn, weight.id)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
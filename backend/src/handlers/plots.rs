@@ -0,0 +1,140 @@
+use actix_web::{web, HttpResponse};
+use uuid::Uuid;
+use crate::{
+    auth::{middleware::ProducerAuthMiddleware, ownership, Claims},
+    handlers::lots::LotOwnerQuery,
+    models::plot::{self, Plot},
+    errors::AppError,
+    database::DbPool
+};
+use kairos_common::plot::{CreatePlotRequest, UpdatePlotRequest};
+
+const GEOJSON: &str = "application/geo+json";
+
+pub fn configure() -> impl actix_web::dev::HttpServiceFactory {
+    web::scope("/plots")
+        .wrap(ProducerAuthMiddleware::new())
+        .route("", web::post().to(create_plot))
+        .route("", web::get().to(list_plots))
+        .route("/import", web::post().to(import_plots))
+        .route("/geojson", web::get().to(export_plots))
+        .route("/{id}", web::get().to(get_plot))
+        .route("/{id}", web::put().to(update_plot))
+        .route("/{id}", web::delete().to(delete_plot))
+        .route("/{id}/geojson", web::get().to(get_plot_geojson))
+}
+
+/// Carga una parcela comprobando que pertenece a quien llama (o que es admin).
+pub(crate) fn find_owned_plot(
+    conn: &mut diesel::PgConnection,
+    claims: &Claims,
+    id: Uuid,
+) -> Result<Plot, AppError> {
+    ownership::owned(claims, Plot::find_by_id(conn, id), |plot| plot.producer_id, "Plot")
+}
+
+pub async fn create_plot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    request: web::Json<CreatePlotRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let plot = Plot::create(conn, claims.sub, &request)?;
+
+    Ok(HttpResponse::Created().json(plot))
+}
+
+pub async fn list_plots(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+
+    let plots: Vec<_> = Plot::find_by_producer(conn, producer_id)?
+        .iter()
+        .map(Plot::summary)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(plots))
+}
+
+pub async fn import_plots(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    document: web::Json<serde_json::Value>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let result = Plot::import(conn, claims.sub, &document)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn export_plots(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    owner: web::Query<LotOwnerQuery>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+    let producer_id = ownership::resolve_owner(&claims, owner.producer_id)?;
+
+    let plots = Plot::find_by_producer(conn, producer_id)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(GEOJSON)
+        .json(plot::feature_collection(&plots)))
+}
+
+pub async fn get_plot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let plot = find_owned_plot(conn, &claims, id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(plot))
+}
+
+pub async fn update_plot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+    request: web::Json<UpdatePlotRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let plot = find_owned_plot(conn, &claims, id.into_inner())?;
+    let plot = Plot::update(conn, &plot, &request, claims.sub)?;
+
+    Ok(HttpResponse::Ok().json(plot))
+}
+
+pub async fn delete_plot(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let plot = find_owned_plot(conn, &claims, id.into_inner())?;
+    Plot::delete(conn, plot.id)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_plot_geojson(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let plot = find_owned_plot(conn, &claims, id.into_inner())?;
+
+    Ok(HttpResponse::Ok().content_type(GEOJSON).json(plot.feature()))
+}
@@ -0,0 +1,251 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use kairos_common::PublicLotView;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    cache::TtlCache,
+    certificates::{self, KeyRing},
+    config::AppConfig,
+    database::DbPool,
+    errors::AppError,
+    labels,
+    models::{certificate::Certificate, lot::Lot},
+    rate_limit::{client_ip, RateLimiter},
+};
+
+/// Vistas públicas ya construidas, por código de lote.
+pub type PublicLotCache = TtlCache<String, Arc<PublicLotView>>;
+
+pub fn public_lot_cache(config: &AppConfig) -> PublicLotCache {
+    TtlCache::new(Duration::from_secs(config.public_cache_ttl), 10_000)
+}
+
+/// Rutas sin autenticación para quien escanea una etiqueta. Requieren registrar en
+/// la app una única instancia de `PublicLotCache` (`public_lot_cache`) y de
+/// `RateLimiter` (`RateLimiter::per_minute(config.public_rate_limit)`) como `web::Data`;
+/// las de certificados, además, el `KeyRing` del servidor.
+pub fn configure() -> actix_web::Scope {
+    web::scope("/public")
+        .route("/lots/{lot_code}", web::get().to(get_public_lot))
+        .route(
+            "/lots/{lot_code}/events",
+            web::get().to(get_public_lot_events),
+        )
+        .route("/lots/{lot_code}/qr.svg", web::get().to(get_lot_qr_svg))
+        .route("/lots/{lot_code}/qr.png", web::get().to(get_lot_qr_png))
+        .route("/certificates/keys", web::get().to(get_certificate_keys))
+        .route("/certificates/verify", web::post().to(verify_certificate))
+        .route("/certificates/{id}", web::get().to(get_certificate))
+        .route(
+            "/certificates/{id}/certificate.pdf",
+            web::get().to(get_certificate_pdf),
+        )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QrQuery {
+    /// Lado mínimo en píxeles (128–1024, 256 por defecto).
+    pub size: Option<u32>,
+}
+
+impl QrQuery {
+    fn size(&self) -> u32 {
+        self.size.unwrap_or(256).clamp(128, 1024)
+    }
+}
+
+/// Aplica el límite de peticiones por IP.
+fn check_rate_limit(
+    req: &HttpRequest,
+    config: &AppConfig,
+    limiter: &RateLimiter,
+) -> Result<(), AppError> {
+    match client_ip(req, config.trust_forwarded_for) {
+        Some(ip) => limiter.check(ip),
+        None => Err(AppError::BadRequest(
+            "Unable to determine client address".into(),
+        )),
+    }
+}
+
+/// Aplica el límite por IP y sirve la vista desde la caché si está fresca.
+fn load_view(
+    req: &HttpRequest,
+    pool: &DbPool,
+    config: &AppConfig,
+    limiter: &RateLimiter,
+    cache: &PublicLotCache,
+    lot_code: String,
+) -> Result<Arc<PublicLotView>, AppError> {
+    check_rate_limit(req, config, limiter)?;
+
+    if let Some(view) = cache.get(&lot_code) {
+        return Ok(view);
+    }
+
+    let conn = &mut pool.get()?;
+    let view = Arc::new(Lot::find_public(conn, &lot_code)?);
+    cache.insert(lot_code, view.clone());
+    Ok(view)
+}
+
+fn cached_response(cache: &PublicLotCache) -> actix_web::HttpResponseBuilder {
+    let mut response = HttpResponse::Ok();
+    response.insert_header((
+        header::CACHE_CONTROL,
+        format!("public, max-age={}", cache.ttl().as_secs()),
+    ));
+    response
+}
+
+pub async fn get_public_lot(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    limiter: web::Data<RateLimiter>,
+    cache: web::Data<PublicLotCache>,
+    lot_code: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let view = load_view(
+        &req,
+        &pool,
+        &config,
+        &limiter,
+        &cache,
+        lot_code.into_inner(),
+    )?;
+
+    Ok(cached_response(&cache).json(view.as_ref()))
+}
+
+pub async fn get_public_lot_events(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    limiter: web::Data<RateLimiter>,
+    cache: web::Data<PublicLotCache>,
+    lot_code: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let view = load_view(
+        &req,
+        &pool,
+        &config,
+        &limiter,
+        &cache,
+        lot_code.into_inner(),
+    )?;
+
+    Ok(cached_response(&cache).json(&view.timeline))
+}
+
+pub async fn get_lot_qr_svg(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    limiter: web::Data<RateLimiter>,
+    cache: web::Data<PublicLotCache>,
+    lot_code: web::Path<String>,
+    query: web::Query<QrQuery>,
+) -> Result<HttpResponse, AppError> {
+    // Solo se generan QR de lotes publicados
+    let view = load_view(
+        &req,
+        &pool,
+        &config,
+        &limiter,
+        &cache,
+        lot_code.into_inner(),
+    )?;
+    let url = labels::verification_url(&config, &view.lot_code);
+
+    Ok(cached_response(&cache)
+        .content_type("image/svg+xml")
+        .body(labels::qr_svg(&url, query.size())?))
+}
+
+pub async fn get_lot_qr_png(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    limiter: web::Data<RateLimiter>,
+    cache: web::Data<PublicLotCache>,
+    lot_code: web::Path<String>,
+    query: web::Query<QrQuery>,
+) -> Result<HttpResponse, AppError> {
+    let view = load_view(
+        &req,
+        &pool,
+        &config,
+        &limiter,
+        &cache,
+        lot_code.into_inner(),
+    )?;
+    let url = labels::verification_url(&config, &view.lot_code);
+
+    Ok(cached_response(&cache)
+        .content_type("image/png")
+        .body(labels::qr_png(&url, query.size())?))
+}
+
+/// Claves públicas con las que verificar certificados sin conexión.
+pub async fn get_certificate_keys(keys: web::Data<KeyRing>) -> HttpResponse {
+    HttpResponse::Ok().json(keys.trusted_keys())
+}
+
+pub async fn verify_certificate(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    limiter: web::Data<RateLimiter>,
+    keys: web::Data<KeyRing>,
+    document: web::Json<serde_json::Value>,
+) -> Result<HttpResponse, AppError> {
+    check_rate_limit(&req, &config, &limiter)?;
+    let conn = &mut pool.get()?;
+
+    let verification = Certificate::verify(conn, &keys, &document)?;
+
+    Ok(HttpResponse::Ok().json(verification))
+}
+
+pub async fn get_certificate(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    limiter: web::Data<RateLimiter>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    check_rate_limit(&req, &config, &limiter)?;
+    let conn = &mut pool.get()?;
+
+    let certificate = Certificate::find_by_id(conn, id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(certificate.document))
+}
+
+pub async fn get_certificate_pdf(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    config: web::Data<AppConfig>,
+    limiter: web::Data<RateLimiter>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    check_rate_limit(&req, &config, &limiter)?;
+    let conn = &mut pool.get()?;
+
+    let certificate = Certificate::find_by_id(conn, id.into_inner())?;
+    let url = certificates::verification_url(&config, certificate.id);
+    let pdf = certificates::certificate_pdf(&certificate.signed()?, &url)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("inline; filename=\"certificate-{}.pdf\"", certificate.id),
+        ))
+        .body(pdf))
+}
@@ -0,0 +1,96 @@
+use actix_web::{http::header, web, HttpResponse};
+use uuid::Uuid;
+use crate::{
+    auth::{middleware::ProducerAuthMiddleware, ownership, Claims},
+    models::{
+        recall::{self, Recall, RecallNotification},
+        role::Role,
+    },
+    errors::AppError,
+    database::DbPool
+};
+use kairos_common::CreateRecallRequest;
+
+pub fn configure() -> impl actix_web::dev::HttpServiceFactory {
+    web::scope("/recalls")
+        .wrap(ProducerAuthMiddleware::new())
+        .route("", web::post().to(create_recall))
+        .route("", web::get().to(list_recalls))
+        .route("/{id}", web::get().to(get_recall))
+        .route("/{id}/report.csv", web::get().to(get_recall_csv))
+}
+
+/// Un productor solo traza desde sus propios lotes; un admin, desde cualquiera.
+fn producer_scope(claims: &Claims) -> Option<Uuid> {
+    (claims.role != Role::Admin).then_some(claims.sub)
+}
+
+fn find_owned_recall(
+    conn: &mut diesel::PgConnection,
+    claims: &Claims,
+    id: Uuid,
+) -> Result<Recall, AppError> {
+    ownership::owned(claims, Recall::find_by_id(conn, id), |recall| recall.initiated_by, "Recall")
+}
+
+pub async fn create_recall(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    request: web::Json<CreateRecallRequest>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let report = Recall::initiate(conn, &request, producer_scope(&claims), claims.sub)?;
+
+    Ok(HttpResponse::Created().json(report))
+}
+
+pub async fn list_recalls(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let reports = Recall::find_by_initiator(conn, producer_scope(&claims))?
+        .iter()
+        .map(Recall::report)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(HttpResponse::Ok().json(reports))
+}
+
+pub async fn get_recall(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let recall = find_owned_recall(conn, &claims, id.into_inner())?;
+    let notifications = RecallNotification::find_by_recall(conn, recall.id)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "report": recall.report()?,
+        "notifications": notifications,
+    })))
+}
+
+pub async fn get_recall_csv(
+    pool: web::Data<DbPool>,
+    claims: web::ReqData<Claims>,
+    id: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let conn = &mut pool.get()?;
+
+    let recall = find_owned_recall(conn, &claims, id.into_inner())?;
+    let notifications = RecallNotification::find_by_recall(conn, recall.id)?;
+    let csv = recall::report_csv(&recall.report()?, &notifications)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"recall-{}.csv\"", recall.id),
+        ))
+        .body(csv))
+}
@@ -0,0 +1,330 @@
+//! Códigos QR y hojas de etiquetas imprimibles para los lotes.
+//!
+//! El QR apunta a la URL pública de verificación del lote. La hoja PDF usa las
+//! fuentes estándar de PDF (WinAnsi), así que los idiomas sin alfabeto latino
+//! (ruso, chino, japonés, coreano) se imprimen con los textos en inglés.
+
+use std::io::{BufWriter, Cursor};
+
+use chrono::NaiveDate;
+use image::{ImageFormat, Luma};
+use kairos_common::Language;
+use printpdf::{
+    path::PaintMode, BuiltinFont, Color, Greyscale, IndirectFontRef, Mm, PdfDocument,
+    PdfLayerReference, Rect,
+};
+use qrcode::{render::svg, Color as ModuleColor, QrCode};
+
+use crate::{config::AppConfig, errors::AppError};
+
+/// Módulos de margen alrededor del QR que exige la especificación.
+const QUIET_ZONE: usize = 4;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const LABEL_COLUMNS: usize = 2;
+const LABEL_ROWS: usize = 5;
+const LABEL_WIDTH: f32 = 95.0;
+const LABEL_HEIGHT: f32 = 55.0;
+const QR_SIZE: f32 = 42.0;
+
+pub const LABELS_PER_SHEET: usize = LABEL_COLUMNS * LABEL_ROWS;
+
+/// Datos que se imprimen en cada etiqueta.
+#[derive(Debug, Clone)]
+pub struct LabelData {
+    pub lot_code: String,
+    pub product_name: String,
+    pub farm_name: Option<String>,
+    pub harvest_date: NaiveDate,
+    /// `true` si el lote aún no se cosechó y la fecha es la estimada.
+    pub harvest_is_estimate: bool,
+}
+
+pub fn verification_url(config: &AppConfig, lot_code: &str) -> String {
+    format!(
+        "{}/{}",
+        config.public_verify_url.trim_end_matches('/'),
+        lot_code
+    )
+}
+
+pub(crate) fn encode(data: &str) -> Result<QrCode, AppError> {
+    QrCode::new(data.as_bytes())
+        .map_err(|e| AppError::InternalServerError(format!("QR encoding failed: {}", e)))
+}
+
+pub fn qr_svg(data: &str, size: u32) -> Result<String, AppError> {
+    Ok(encode(data)?
+        .render::<svg::Color>()
+        .min_dimensions(size, size)
+        .build())
+}
+
+pub fn qr_png(data: &str, size: u32) -> Result<Vec<u8>, AppError> {
+    let image = encode(data)?
+        .render::<Luma<u8>>()
+        .min_dimensions(size, size)
+        .build();
+
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| AppError::InternalServerError(format!("PNG encoding failed: {}", e)))?;
+    Ok(png.into_inner())
+}
+
+struct LabelText {
+    title: &'static str,
+    lot: &'static str,
+    product: &'static str,
+    harvested: &'static str,
+    estimated_harvest: &'static str,
+    scan: &'static str,
+}
+
+fn label_text(language: Language) -> LabelText {
+    match language {
+        Language::Spanish => LabelText {
+            title: "Lotes de producción",
+            lot: "Lote",
+            product: "Producto",
+            harvested: "Cosecha",
+            estimated_harvest: "Cosecha estimada",
+            scan: "Escanee para verificar el origen",
+        },
+        Language::Portuguese => LabelText {
+            title: "Lotes de produção",
+            lot: "Lote",
+            product: "Produto",
+            harvested: "Colheita",
+            estimated_harvest: "Colheita prevista",
+            scan: "Escaneie para verificar a origem",
+        },
+        Language::French => LabelText {
+            title: "Lots de production",
+            lot: "Lot",
+            product: "Produit",
+            harvested: "Récolte",
+            estimated_harvest: "Récolte prévue",
+            scan: "Scannez pour vérifier l'origine",
+        },
+        Language::German => LabelText {
+            title: "Produktionschargen",
+            lot: "Charge",
+            product: "Produkt",
+            harvested: "Ernte",
+            estimated_harvest: "Voraussichtliche Ernte",
+            scan: "Scannen, um die Herkunft zu prüfen",
+        },
+        Language::Italian => LabelText {
+            title: "Lotti di produzione",
+            lot: "Lotto",
+            product: "Prodotto",
+            harvested: "Raccolta",
+            estimated_harvest: "Raccolta prevista",
+            scan: "Scansiona per verificare l'origine",
+        },
+        Language::English
+        | Language::Russian
+        | Language::Chinese
+        | Language::Japanese
+        | Language::Korean => LabelText {
+            title: "Production lots",
+            lot: "Lot",
+            product: "Product",
+            harvested: "Harvest",
+            estimated_harvest: "Estimated harvest",
+            scan: "Scan to verify origin",
+        },
+    }
+}
+
+fn format_date(date: NaiveDate, language: Language) -> String {
+    let pattern = match language {
+        Language::English => "%Y-%m-%d",
+        Language::German | Language::Russian => "%d.%m.%Y",
+        Language::Chinese | Language::Japanese | Language::Korean => "%Y-%m-%d",
+        Language::Spanish | Language::Portuguese | Language::French | Language::Italian => {
+            "%d/%m/%Y"
+        }
+    };
+    date.format(pattern).to_string()
+}
+
+/// Recorta un texto a `max` caracteres para que no se salga de la etiqueta.
+pub(crate) fn fit(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let mut cut: String = text.chars().take(max - 1).collect();
+        cut.push('…');
+        cut
+    }
+}
+
+/// Dibuja el QR como rectángulos vectoriales (nítido a cualquier escala de impresión).
+pub(crate) fn draw_qr(layer: &PdfLayerReference, code: &QrCode, x: f32, y: f32, size: f32) {
+    let width = code.width();
+    let module = size / (width + 2 * QUIET_ZONE) as f32;
+    let colors = code.to_colors();
+
+    for row in 0..width {
+        let mut col = 0;
+        while col < width {
+            if colors[row * width + col] != ModuleColor::Dark {
+                col += 1;
+                continue;
+            }
+            // Une los módulos oscuros consecutivos de la fila en un solo rectángulo
+            let start = col;
+            while col < width && colors[row * width + col] == ModuleColor::Dark {
+                col += 1;
+            }
+            let left = x + (start + QUIET_ZONE) as f32 * module;
+            let top = y + size - (row + QUIET_ZONE) as f32 * module;
+            layer.add_rect(Rect::new(
+                Mm(left),
+                Mm(top - module),
+                Mm(left + (col - start) as f32 * module),
+                Mm(top),
+            ));
+        }
+    }
+}
+
+/// Hoja A4 con `LABELS_PER_SHEET` etiquetas iguales del lote, en el idioma del productor.
+pub fn label_sheet_pdf(
+    label: &LabelData,
+    url: &str,
+    language: Language,
+) -> Result<Vec<u8>, AppError> {
+    let pdf_error =
+        |e: printpdf::Error| AppError::InternalServerError(format!("PDF generation failed: {}", e));
+    let text = label_text(language);
+    let code = encode(url)?;
+
+    let (doc, page, layer) = PdfDocument::new(
+        format!("{} {}", text.lot, label.lot_code),
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "labels",
+    );
+    let regular = doc
+        .add_builtin_font(BuiltinFont::Helvetica)
+        .map_err(pdf_error)?;
+    let bold = doc
+        .add_builtin_font(BuiltinFont::HelveticaBold)
+        .map_err(pdf_error)?;
+    let layer = doc.get_page(page).get_layer(layer);
+
+    layer.set_fill_color(Color::Greyscale(Greyscale::new(0.0, None)));
+    layer.set_outline_color(Color::Greyscale(Greyscale::new(0.6, None)));
+    layer.set_outline_thickness(0.3);
+
+    let harvest_label = if label.harvest_is_estimate {
+        text.estimated_harvest
+    } else {
+        text.harvested
+    };
+    let lines: [(&IndirectFontRef, f32, String); 4] = [
+        (&bold, 11.0, format!("{}: {}", text.lot, label.lot_code)),
+        (
+            &regular,
+            9.0,
+            format!("{}: {}", text.product, fit(&label.product_name, 26)),
+        ),
+        (
+            &regular,
+            9.0,
+            format!(
+                "{}: {}",
+                harvest_label,
+                format_date(label.harvest_date, language)
+            ),
+        ),
+        (
+            &regular,
+            8.0,
+            fit(label.farm_name.as_deref().unwrap_or(text.title), 32),
+        ),
+    ];
+
+    let margin_x = (PAGE_WIDTH - LABEL_COLUMNS as f32 * LABEL_WIDTH) / 2.0;
+    let margin_y = (PAGE_HEIGHT - LABEL_ROWS as f32 * LABEL_HEIGHT) / 2.0;
+
+    for row in 0..LABEL_ROWS {
+        for column in 0..LABEL_COLUMNS {
+            let x = margin_x + column as f32 * LABEL_WIDTH;
+            let y = PAGE_HEIGHT - margin_y - (row + 1) as f32 * LABEL_HEIGHT;
+
+            // Guía de corte
+            layer.add_rect(
+                Rect::new(Mm(x), Mm(y), Mm(x + LABEL_WIDTH), Mm(y + LABEL_HEIGHT))
+                    .with_mode(PaintMode::Stroke),
+            );
+
+            let qr_y = y + (LABEL_HEIGHT - QR_SIZE) / 2.0;
+            draw_qr(&layer, &code, x + 2.0, qr_y, QR_SIZE);
+
+            let text_x = Mm(x + QR_SIZE + 5.0);
+            let mut text_y = y + LABEL_HEIGHT - 12.0;
+            for (font, size, line) in &lines {
+                layer.use_text(line.as_str(), *size, text_x, Mm(text_y), font);
+                text_y -= 8.0;
+            }
+            layer.use_text(text.scan, 6.5, text_x, Mm(y + 6.0), &regular);
+        }
+    }
+
+    let mut pdf = BufWriter::new(Vec::new());
+    doc.save(&mut pdf).map_err(pdf_error)?;
+    pdf.into_inner()
+        .map_err(|e| AppError::InternalServerError(format!("PDF generation failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label() -> LabelData {
+        LabelData {
+            lot_code: "LOT-2024-0001".into(),
+            product_name: "Tomates Cherry".into(),
+            farm_name: Some("Finca La Esperanza".into()),
+            harvest_date: NaiveDate::from_ymd_opt(2024, 8, 15).unwrap(),
+            harvest_is_estimate: true,
+        }
+    }
+
+    #[test]
+    fn qr_outputs_are_valid_svg_and_png() {
+        let url = "https://kairos.example/public/lots/LOT-2024-0001";
+
+        let svg = qr_svg(url, 256).unwrap();
+        assert!(svg.contains("<svg"));
+
+        let png = qr_png(url, 256).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn label_sheet_is_a_pdf() {
+        let pdf = label_sheet_pdf(&label(), "https://kairos.example/x", Language::Spanish).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn dates_follow_the_producer_language() {
+        let date = NaiveDate::from_ymd_opt(2024, 8, 15).unwrap();
+        assert_eq!(format_date(date, Language::Spanish), "15/08/2024");
+        assert_eq!(format_date(date, Language::German), "15.08.2024");
+        assert_eq!(format_date(date, Language::English), "2024-08-15");
+    }
+
+    #[test]
+    fn long_text_is_truncated() {
+        assert_eq!(fit("Tomates", 26), "Tomates");
+        assert_eq!(fit("abcdefghij", 5).chars().count(), 5);
+    }
+}
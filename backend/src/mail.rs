@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{config::AppConfig, errors::AppError};
+
+#[derive(Debug, Clone)]
+pub struct OutgoingMail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Salida de correo. Los handlers la reciben como `web::Data<dyn Mailer>` para que
+/// el transporte (log, archivo, SMTP) se elija al arrancar.
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: &OutgoingMail) -> Result<(), AppError>;
+}
+
+/// Escribe cada correo en el log. Útil en desarrollo y en tests.
+pub struct LogMailer {
+    from: String,
+}
+
+impl Mailer for LogMailer {
+    fn send(&self, mail: &OutgoingMail) -> Result<(), AppError> {
+        tracing::info!(
+            from = %self.from,
+            to = %mail.to,
+            subject = %mail.subject,
+            "outgoing mail:\n{}",
+            mail.body
+        );
+        Ok(())
+    }
+}
+
+/// Guarda cada correo como un `.eml` en un directorio, a modo de bandeja de salida local.
+pub struct FileMailer {
+    from: String,
+    dir: PathBuf,
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &OutgoingMail) -> Result<(), AppError> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| AppError::InternalServerError(format!("Mail outbox unavailable: {}", e)))?;
+
+        let now = Utc::now();
+        let path = self
+            .dir
+            .join(format!("{}-{}.eml", now.format("%Y%m%dT%H%M%S"), Uuid::new_v4()));
+        let contents = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            self.from,
+            mail.to,
+            mail.subject,
+            now.to_rfc2822(),
+            mail.body
+        );
+
+        fs::write(&path, contents)
+            .map_err(|e| AppError::InternalServerError(format!("Failed to write mail: {}", e)))
+    }
+}
+
+pub fn mailer_from_config(config: &AppConfig) -> Arc<dyn Mailer> {
+    match config.mail_transport.as_str() {
+        "file" => Arc::new(FileMailer {
+            from: config.mail_from.clone(),
+            dir: PathBuf::from(&config.mail_dir),
+        }),
+        "log" => Arc::new(LogMailer {
+            from: config.mail_from.clone(),
+        }),
+        other => panic!("Unsupported MAIL_TRANSPORT '{}', expected 'log' or 'file'", other),
+    }
+}
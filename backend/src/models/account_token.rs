@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::schema::account_tokens;
+
+pub const EMAIL_VERIFICATION: &str = "email_verification";
+pub const PASSWORD_RESET: &str = "password_reset";
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = account_tokens)]
+pub struct AccountToken {
+    pub id: Uuid,
+    pub producer_id: Uuid,
+    pub purpose: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = account_tokens)]
+pub struct NewAccountToken<'a> {
+    pub producer_id: Uuid,
+    pub purpose: &'a str,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AccountToken {
+    pub fn create(conn: &mut PgConnection, new_token: NewAccountToken) -> QueryResult<Self> {
+        diesel::insert_into(account_tokens::table)
+            .values(&new_token)
+            .returning(AccountToken::as_returning())
+            .get_result(conn)
+    }
+
+    /// Invalida los tokens pendientes del mismo propósito y guarda el nuevo,
+    /// de modo que solo el último enlace enviado sea válido.
+    pub fn replace(conn: &mut PgConnection, new_token: NewAccountToken) -> QueryResult<Self> {
+        conn.transaction(|conn| {
+            Self::invalidate(conn, new_token.producer_id, new_token.purpose)?;
+            Self::create(conn, new_token)
+        })
+    }
+
+    /// Marca el token como usado si sigue vigente y lo devuelve.
+    /// El UPDATE condicional evita que dos peticiones simultáneas consuman el mismo token.
+    pub fn consume(conn: &mut PgConnection, purpose: &str, hash: &str) -> QueryResult<Self> {
+        diesel::update(
+            account_tokens::table
+                .filter(account_tokens::purpose.eq(purpose))
+                .filter(account_tokens::token_hash.eq(hash))
+                .filter(account_tokens::used_at.is_null())
+                .filter(account_tokens::expires_at.gt(Utc::now())),
+        )
+        .set(account_tokens::used_at.eq(Utc::now()))
+        .returning(AccountToken::as_returning())
+        .get_result(conn)
+    }
+
+    /// Invalida los tokens pendientes de un productor para un propósito.
+    pub fn invalidate(conn: &mut PgConnection, producer_id: Uuid, purpose: &str) -> QueryResult<usize> {
+        diesel::update(
+            account_tokens::table
+                .filter(account_tokens::producer_id.eq(producer_id))
+                .filter(account_tokens::purpose.eq(purpose))
+                .filter(account_tokens::used_at.is_null()),
+        )
+        .set(account_tokens::used_at.eq(Utc::now()))
+        .execute(conn)
+    }
+
+    /// Fechas de emisión desde `since`, las más recientes primero (para limitar reenvíos).
+    pub fn issued_since(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        purpose: &str,
+        since: DateTime<Utc>,
+    ) -> QueryResult<Vec<DateTime<Utc>>> {
+        account_tokens::table
+            .filter(account_tokens::producer_id.eq(producer_id))
+            .filter(account_tokens::purpose.eq(purpose))
+            .filter(account_tokens::created_at.gt(since))
+            .order(account_tokens::created_at.desc())
+            .select(account_tokens::created_at)
+            .load(conn)
+    }
+}
@@ -0,0 +1,187 @@
+//! Emisión y verificación de certificados de trazabilidad (ver `crate::certificates`).
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use kairos_common::{
+    certificate::{
+        verify_certificate, CertificateEvent, CertificateLot, CertificateVerification,
+        SignedCertificate, TraceabilityCertificate,
+    },
+    ApiError,
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    certificates::KeyRing,
+    errors::AppError,
+    models::{
+        event_chain::{seal_pending, verify_lot, ChainedEvent},
+        lot::Lot,
+        lot_genealogy::MAX_GENEALOGY_DEPTH,
+        producer::Producer,
+    },
+    schema::{certificates, events},
+};
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = certificates)]
+pub struct Certificate {
+    pub id: Uuid,
+    pub lot_id: Uuid,
+    pub key_id: String,
+    pub head_hash: Option<String>,
+    pub document: Value,
+    pub issued_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+fn json_error(e: serde_json::Error) -> AppError {
+    AppError::InternalServerError(format!("Invalid certificate document: {}", e))
+}
+
+fn certificate_event(event: ChainedEvent) -> CertificateEvent {
+    CertificateEvent {
+        event_id: event.id,
+        sequence: event.sequence.unwrap_or_default(),
+        event_type: event.event_type.0,
+        description: event.description,
+        event_location: event.event_location,
+        occurred_at: event.created_at,
+        corrects_event_id: event.corrects_event_id,
+        prev_hash: event.prev_hash,
+        hash: event.hash.unwrap_or_default(),
+    }
+}
+
+/// Hash del último evento sellado del lote.
+fn current_head(conn: &mut PgConnection, lot_id: Uuid) -> QueryResult<Option<String>> {
+    events::table
+        .filter(events::lot_id.eq(lot_id))
+        .filter(events::sequence.is_not_null())
+        .order(events::sequence.desc())
+        .select(events::hash)
+        .first::<Option<String>>(conn)
+        .optional()
+        .map(Option::flatten)
+}
+
+impl Certificate {
+    /// Firma la historia completa del lote tal como está ahora. Solo se certifican
+    /// cadenas íntegras: si la verificación falla, se devuelve 409 `CHAIN_INVALID`.
+    pub fn issue(
+        conn: &mut PgConnection,
+        lot: &Lot,
+        keys: &KeyRing,
+        issuer: &str,
+        actor_id: Uuid,
+    ) -> Result<SignedCertificate, AppError> {
+        conn.transaction::<_, AppError, _>(|conn| {
+            seal_pending(conn, lot.id)?;
+            let verification = verify_lot(conn, lot.id)?;
+            if !verification.valid {
+                return Err(AppError::Conflict(ApiError {
+                    code: "CHAIN_INVALID".into(),
+                    message: "Lot event chain failed verification and cannot be certified".into(),
+                    details: Some(serde_json::json!({
+                        "first_broken_link": verification.first_broken_link,
+                    })),
+                }));
+            }
+
+            let chain = events::table
+                .filter(events::lot_id.eq(lot.id))
+                .filter(events::sequence.is_not_null())
+                .order(events::sequence.asc())
+                .select(ChainedEvent::as_select())
+                .load(conn)?;
+            let producer = Producer::find_by_id(conn, lot.producer_id)?;
+            let origins = Lot::genealogy(conn, lot, MAX_GENEALOGY_DEPTH)?.ancestors;
+
+            let certificate = TraceabilityCertificate {
+                certificate_id: Uuid::new_v4(),
+                issuer: issuer.to_string(),
+                issued_at: Utc::now(),
+                lot: CertificateLot {
+                    lot_id: lot.id,
+                    lot_code: lot.lot_code.clone(),
+                    product_name: lot.product_name.clone(),
+                    crop_type: lot.crop_type,
+                    status: lot.current_status,
+                    quantity: lot.estimated_quantity,
+                    unit_of_measure: lot.unit_of_measure.clone(),
+                    estimated_harvest_date: lot.estimated_harvest_date,
+                    actual_harvest_date: lot.actual_harvest_date,
+                    producer_name: producer.full_name,
+                    farm_name: producer.farm_name,
+                },
+                events: chain.into_iter().map(certificate_event).collect(),
+                head_hash: verification.head_hash,
+                origins,
+            };
+            let signed = keys.sign(certificate)?;
+
+            diesel::insert_into(certificates::table)
+                .values((
+                    certificates::id.eq(signed.certificate.certificate_id),
+                    certificates::lot_id.eq(lot.id),
+                    certificates::key_id.eq(&signed.signature.key_id),
+                    certificates::head_hash.eq(&signed.certificate.head_hash),
+                    certificates::document.eq(serde_json::to_value(&signed).map_err(json_error)?),
+                    certificates::issued_by.eq(actor_id),
+                ))
+                .execute(conn)?;
+
+            Ok(signed)
+        })
+    }
+
+    pub fn find_by_id(conn: &mut PgConnection, id: Uuid) -> Result<Certificate, AppError> {
+        certificates::table
+            .find(id)
+            .select(Certificate::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound("Certificate not found".into()))
+    }
+
+    pub fn signed(&self) -> Result<SignedCertificate, AppError> {
+        serde_json::from_value(self.document.clone()).map_err(json_error)
+    }
+
+    /// Verifica un certificado presentado por un tercero con las claves del servidor
+    /// y avisa si el lote ha registrado eventos después de la emisión.
+    pub fn verify(
+        conn: &mut PgConnection,
+        keys: &KeyRing,
+        document: &Value,
+    ) -> Result<CertificateVerification, AppError> {
+        let certificate = match verify_certificate(document, &keys.trusted_keys()) {
+            Ok(certificate) => certificate,
+            Err(e) => {
+                return Ok(CertificateVerification {
+                    valid: false,
+                    certificate_id: None,
+                    lot_code: None,
+                    key_id: None,
+                    reason: Some(e.to_string()),
+                    history_extended: false,
+                })
+            }
+        };
+
+        let history_extended = match current_head(conn, certificate.lot.lot_id)? {
+            Some(head) => certificate.head_hash.as_deref() != Some(head.as_str()),
+            None => false,
+        };
+
+        Ok(CertificateVerification {
+            valid: true,
+            certificate_id: Some(certificate.certificate_id),
+            lot_code: Some(certificate.lot.lot_code),
+            key_id: document["signature"]["key_id"].as_str().map(String::from),
+            reason: None,
+            history_extended,
+        })
+    }
+}
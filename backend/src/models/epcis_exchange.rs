@@ -0,0 +1,271 @@
+//! Exportación e importación EPCIS de los eventos de un lote (ver `crate::epcis`).
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::{exists, sql};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Numeric, Text, Timestamptz, Uuid as SqlUuid};
+use kairos_common::{EpcisImportResult, ImportedEpcisEvent, SkippedEpcisEvent};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    epcis::{self, ExportLot, TransformationLink},
+    errors::AppError,
+    models::{
+        event::Event,
+        event_chain::{seal_pending, ChainedEvent},
+        event_edit::RETRACT,
+        lot::Lot,
+        lot_genealogy::parse_operation,
+        sql_enums::DbEventType,
+    },
+    schema::events,
+};
+
+#[derive(QueryableByName)]
+struct LinkRow {
+    #[diesel(sql_type = SqlUuid)]
+    id: Uuid,
+    #[diesel(sql_type = Text)]
+    operation: String,
+    #[diesel(sql_type = Text)]
+    parent_code: String,
+    #[diesel(sql_type = Text)]
+    child_code: String,
+    #[diesel(sql_type = Numeric)]
+    quantity: Decimal,
+    #[diesel(sql_type = Text)]
+    unit_of_measure: String,
+    #[diesel(sql_type = Timestamptz)]
+    created_at: DateTime<Utc>,
+}
+
+impl Lot {
+    /// Historia del lote como `EPCISDocument`, con la clase del lote en Digital Link.
+    pub fn epcis_document(
+        conn: &mut PgConnection,
+        lot: &Lot,
+        link_base: &str,
+        gtin: &str,
+    ) -> Result<Value, AppError> {
+        if !epcis::is_valid_gtin(gtin) {
+            return Err(AppError::BadRequest(format!(
+                "'{}' is not a valid GTIN",
+                gtin
+            )));
+        }
+
+        let chain = events::table
+            .filter(events::lot_id.eq(lot.id))
+            .order((events::created_at, events::id))
+            .select(ChainedEvent::as_select())
+            .load(conn)?;
+
+        // Enlaces en los que el lote es origen o destino, con los códigos de ambos extremos
+        let rows: Vec<LinkRow> = diesel::sql_query(
+            "SELECT g.id, g.operation, p.lot_code AS parent_code, c.lot_code AS child_code,
+                    g.quantity, c.unit_of_measure, g.created_at
+             FROM lot_genealogy g
+             JOIN lots p ON p.id = g.parent_lot_id
+             JOIN lots c ON c.id = g.child_lot_id
+             WHERE g.parent_lot_id = $1 OR g.child_lot_id = $1",
+        )
+        .bind::<SqlUuid, _>(lot.id)
+        .load(conn)?;
+
+        let links: Vec<TransformationLink> = rows
+            .into_iter()
+            .map(|row| TransformationLink {
+                id: row.id,
+                operation: parse_operation(&row.operation),
+                parent_class: epcis::digital_link(link_base, gtin, &row.parent_code),
+                child_class: epcis::digital_link(link_base, gtin, &row.child_code),
+                quantity: row.quantity,
+                unit_of_measure: row.unit_of_measure,
+                created_at: row.created_at,
+            })
+            .collect();
+
+        let export = ExportLot {
+            epc_class: epcis::digital_link(link_base, gtin, &lot.lot_code),
+            product_name: &lot.product_name,
+            crop_type: lot.crop_type,
+            unit_of_measure: &lot.unit_of_measure,
+            quantity: lot.estimated_quantity,
+        };
+
+        Ok(epcis::export_document(&export, &chain, &links))
+    }
+
+    /// Adjunta al lote los eventos del documento que lo referencian. Los eventos ya
+    /// importados (mismo `eventID`) se omiten, así que reenviar un documento es seguro.
+    /// Un evento con `errorDeclaration` no se añade como evento nuevo sino como la
+    /// anulación del que se importó con su `eventID`, igual que una corrección local.
+    pub fn import_epcis(
+        conn: &mut PgConnection,
+        lot: &Lot,
+        document: &Value,
+        actor_id: Uuid,
+    ) -> Result<EpcisImportResult, AppError> {
+        let parsed = epcis::parse_document(document)?;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let mut result = EpcisImportResult::default();
+
+            for entry in parsed {
+                let partner = match entry {
+                    Ok(partner) => partner,
+                    Err((epcis_event_id, reason)) => {
+                        result.skipped.push(SkippedEpcisEvent {
+                            epcis_event_id,
+                            reason,
+                        });
+                        continue;
+                    }
+                };
+                let skip = |reason: String| SkippedEpcisEvent {
+                    epcis_event_id: Some(partner.event_id.clone()),
+                    reason,
+                };
+
+                if !partner.lot_codes.contains(&lot.lot_code) {
+                    result.skipped.push(skip(format!(
+                        "event does not reference lot {}",
+                        lot.lot_code
+                    )));
+                    continue;
+                }
+
+                if let Some(declaration) = &partner.error_declaration {
+                    match import_error_declaration(conn, lot, &partner, declaration, actor_id)? {
+                        Ok(imported) => result.imported.push(imported),
+                        Err(reason) => result.skipped.push(skip(reason)),
+                    }
+                    continue;
+                }
+
+                let already_imported: bool = diesel::select(exists(
+                    events::table.filter(events::lot_id.eq(lot.id)).filter(
+                        sql::<Bool>("metadata->'epcis'->>'event_id' = ")
+                            .bind::<Text, _>(&partner.event_id),
+                    ),
+                ))
+                .get_result(conn)?;
+                if already_imported {
+                    result
+                        .skipped
+                        .push(skip("event was already imported".into()));
+                    continue;
+                }
+
+                let event_type = epcis::event_type_for_biz_step(partner.biz_step.as_deref());
+                let description = match &partner.biz_step {
+                    Some(step) => format!("EPCIS {} ({}) from partner", partner.epcis_type, step),
+                    None => format!("EPCIS {} from partner", partner.epcis_type),
+                };
+
+                let event_id: Uuid = diesel::insert_into(events::table)
+                    .values((
+                        events::lot_id.eq(lot.id),
+                        events::event_type.eq(DbEventType(event_type)),
+                        events::description.eq(description),
+                        events::metadata.eq(serde_json::json!({
+                            "epcis": {
+                                "event_id": partner.event_id,
+                                "type": partner.epcis_type,
+                                "event_time": partner.event_time,
+                                "biz_step": partner.biz_step,
+                                "document": partner.raw,
+                            },
+                            "actor_id": actor_id,
+                        })),
+                    ))
+                    .returning(events::id)
+                    .get_result(conn)?;
+
+                result.imported.push(ImportedEpcisEvent {
+                    epcis_event_id: partner.event_id,
+                    event_id,
+                    event_type,
+                });
+            }
+
+            if !result.imported.is_empty() {
+                seal_pending(conn, lot.id)?;
+            }
+            Ok(result)
+        })
+    }
+}
+
+/// Evento importado con este `eventID` del socio. Su anulación lleva el mismo
+/// `eventID`; el original es el que no corrige a ningún otro.
+fn find_imported(
+    conn: &mut PgConnection,
+    lot_id: Uuid,
+    epcis_event_id: &str,
+    declared: bool,
+) -> QueryResult<Option<(Uuid, DbEventType)>> {
+    let query = events::table
+        .filter(events::lot_id.eq(lot_id))
+        .filter(sql::<Bool>("metadata->'epcis'->>'event_id' = ").bind::<Text, _>(epcis_event_id))
+        .select((events::id, events::event_type))
+        .into_boxed();
+    let query = if declared {
+        query.filter(events::corrects_event_id.is_not_null())
+    } else {
+        query.filter(events::corrects_event_id.is_null())
+    };
+    query.first(conn).optional()
+}
+
+/// Anula el evento importado al que se refiere la declaración. Si ya tenía
+/// correcciones, se anula la última, que es la que está en vigor.
+fn import_error_declaration(
+    conn: &mut PgConnection,
+    lot: &Lot,
+    partner: &epcis::PartnerEvent,
+    declaration: &Value,
+    actor_id: Uuid,
+) -> Result<Result<ImportedEpcisEvent, String>, AppError> {
+    let Some((original_id, event_type)) = find_imported(conn, lot.id, &partner.event_id, false)? else {
+        return Ok(Err("error declaration refers to an event that was not imported".into()));
+    };
+    if find_imported(conn, lot.id, &partner.event_id, true)?.is_some() {
+        return Ok(Err("error declaration was already imported".into()));
+    }
+    let corrects = Event::latest_correction(conn, original_id)?
+        .map_or(original_id, |latest| latest.id);
+    let reason = declaration
+        .get("reason")
+        .and_then(Value::as_str)
+        .unwrap_or("unspecified");
+
+    let event_id: Uuid = diesel::insert_into(events::table)
+        .values((
+            events::lot_id.eq(lot.id),
+            events::event_type.eq(event_type),
+            events::description.eq(format!("EPCIS error declaration from partner ({})", reason)),
+            events::corrects_event_id.eq(corrects),
+            events::metadata.eq(serde_json::json!({
+                "correction": { "action": RETRACT, "actor_id": actor_id },
+                "epcis": {
+                    "event_id": partner.event_id,
+                    "type": partner.epcis_type,
+                    "event_time": partner.event_time,
+                    "error_declaration": declaration,
+                    "document": partner.raw,
+                },
+                "actor_id": actor_id,
+            })),
+        ))
+        .returning(events::id)
+        .get_result(conn)?;
+
+    Ok(Ok(ImportedEpcisEvent {
+        epcis_event_id: partner.event_id.clone(),
+        event_id,
+        event_type: event_type.0,
+    }))
+}
@@ -0,0 +1,270 @@
+//! Cadena de hashes de los eventos de cada lote.
+//!
+//! Los eventos se insertan sin sellar y `seal_pending` les asigna posición, `prev_hash`
+//! y `hash` dentro de la misma transacción. El hash se calcula sobre los valores tal y
+//! como los devuelve la base de datos, así `verify_lot` puede recalcularlo igual.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::prelude::*;
+use kairos_common::{BrokenLink, ChainBreak, ChainVerification, EventType, Point};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    models::sql_enums::{DbEventType, DbPoint},
+    schema::{events, lots},
+};
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = events)]
+pub struct ChainedEvent {
+    pub id: Uuid,
+    pub lot_id: Uuid,
+    pub sequence: Option<i64>,
+    pub event_type: DbEventType,
+    pub description: Option<String>,
+    pub event_location: Option<String>,
+    pub coordinates: Option<DbPoint>,
+    pub metadata: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    pub corrects_event_id: Option<Uuid>,
+    pub prev_hash: Option<String>,
+    pub hash: Option<String>,
+}
+
+/// Representación canónica: orden de campos fijo, claves de `metadata` ordenadas y
+/// fecha con precisión de microsegundos (la de `timestamptz`).
+#[derive(Serialize)]
+struct CanonicalEvent<'a> {
+    id: Uuid,
+    lot_id: Uuid,
+    sequence: i64,
+    event_type: EventType,
+    description: Option<&'a str>,
+    event_location: Option<&'a str>,
+    coordinates: Option<Point>,
+    metadata: Option<Value>,
+    created_at: String,
+    corrects_event_id: Option<Uuid>,
+    prev_hash: Option<&'a str>,
+}
+
+fn sorted(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.clone(), sorted(v)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sorted).collect()),
+        other => other.clone(),
+    }
+}
+
+impl ChainedEvent {
+    pub fn canonical_json(&self, sequence: i64, prev_hash: Option<&str>) -> String {
+        let canonical = CanonicalEvent {
+            id: self.id,
+            lot_id: self.lot_id,
+            sequence,
+            event_type: self.event_type.0,
+            description: self.description.as_deref(),
+            event_location: self.event_location.as_deref(),
+            coordinates: self.coordinates.map(|p| p.0),
+            metadata: self.metadata.as_ref().map(sorted),
+            created_at: self.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            corrects_event_id: self.corrects_event_id,
+            prev_hash,
+        };
+        serde_json::to_string(&canonical).expect("canonical event is always serializable")
+    }
+
+    pub fn compute_hash(&self, sequence: i64, prev_hash: Option<&str>) -> String {
+        hex::encode(Sha256::digest(
+            self.canonical_json(sequence, prev_hash).as_bytes(),
+        ))
+    }
+}
+
+/// Sella los eventos pendientes del lote a continuación del último sellado.
+/// Debe llamarse dentro de una transacción: bloquea la fila del lote para que dos
+/// altas concurrentes no compitan por la misma posición.
+pub fn seal_pending(conn: &mut PgConnection, lot_id: Uuid) -> QueryResult<usize> {
+    lots::table
+        .find(lot_id)
+        .select(lots::id)
+        .for_update()
+        .first::<Uuid>(conn)?;
+
+    let mut tail: Option<(i64, String)> = events::table
+        .filter(events::lot_id.eq(lot_id))
+        .filter(events::sequence.is_not_null())
+        .order(events::sequence.desc())
+        .select((
+            events::sequence.assume_not_null(),
+            events::hash.assume_not_null(),
+        ))
+        .first(conn)
+        .optional()?;
+
+    let pending = events::table
+        .filter(events::lot_id.eq(lot_id))
+        .filter(events::hash.is_null())
+        .order((events::created_at.asc(), events::id.asc()))
+        .select(ChainedEvent::as_select())
+        .load(conn)?;
+
+    for event in &pending {
+        let sequence = tail.as_ref().map_or(1, |(seq, _)| seq + 1);
+        let prev_hash = tail.as_ref().map(|(_, hash)| hash.as_str());
+        let hash = event.compute_hash(sequence, prev_hash);
+
+        diesel::update(events::table.find(event.id))
+            .set((
+                events::sequence.eq(sequence),
+                events::prev_hash.eq(prev_hash),
+                events::hash.eq(&hash),
+            ))
+            .execute(conn)?;
+
+        tail = Some((sequence, hash));
+    }
+
+    Ok(pending.len())
+}
+
+/// Recorre los eventos sellados en orden y devuelve el primer eslabón roto.
+pub fn verify_chain(sealed: &[ChainedEvent]) -> Result<Option<String>, BrokenLink> {
+    let mut prev_hash: Option<&str> = None;
+
+    for (index, event) in sealed.iter().enumerate() {
+        let expected_sequence = index as i64 + 1;
+        let sequence = event.sequence.unwrap_or_default();
+        let broken = |reason| BrokenLink {
+            event_id: event.id,
+            sequence,
+            reason,
+        };
+
+        if sequence != expected_sequence {
+            return Err(broken(ChainBreak::SequenceGap));
+        }
+        if event.prev_hash.as_deref() != prev_hash {
+            return Err(broken(ChainBreak::PrevHashMismatch));
+        }
+        if event.hash.as_deref() != Some(event.compute_hash(sequence, prev_hash).as_str()) {
+            return Err(broken(ChainBreak::HashMismatch));
+        }
+        prev_hash = event.hash.as_deref();
+    }
+
+    Ok(prev_hash.map(str::to_owned))
+}
+
+/// Recalcula la cadena completa de un lote sin modificar nada.
+pub fn verify_lot(conn: &mut PgConnection, lot_id: Uuid) -> QueryResult<ChainVerification> {
+    let sealed = events::table
+        .filter(events::lot_id.eq(lot_id))
+        .filter(events::sequence.is_not_null())
+        .order(events::sequence.asc())
+        .select(ChainedEvent::as_select())
+        .load(conn)?;
+
+    let unsealed_events = events::table
+        .filter(events::lot_id.eq(lot_id))
+        .filter(events::hash.is_null())
+        .count()
+        .get_result(conn)?;
+
+    let (head_hash, first_broken_link) = match verify_chain(&sealed) {
+        Ok(head) => (head, None),
+        Err(link) => (None, Some(link)),
+    };
+
+    Ok(ChainVerification {
+        lot_id,
+        valid: first_broken_link.is_none(),
+        events_checked: sealed.len() as i64,
+        unsealed_events,
+        head_hash,
+        first_broken_link,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(metadata: Value) -> ChainedEvent {
+        ChainedEvent {
+            id: Uuid::new_v4(),
+            lot_id: Uuid::nil(),
+            sequence: None,
+            event_type: DbEventType(EventType::Irrigation),
+            description: Some("Riego por goteo".into()),
+            event_location: None,
+            coordinates: None,
+            metadata: Some(metadata),
+            created_at: Utc::now(),
+            corrects_event_id: None,
+            prev_hash: None,
+            hash: None,
+        }
+    }
+
+    fn chain(len: usize) -> Vec<ChainedEvent> {
+        let mut prev: Option<String> = None;
+        (1..=len as i64)
+            .map(|sequence| {
+                let mut e = event(serde_json::json!({ "liters": sequence * 100 }));
+                let hash = e.compute_hash(sequence, prev.as_deref());
+                e.sequence = Some(sequence);
+                e.prev_hash = prev.replace(hash.clone());
+                e.hash = Some(hash);
+                e
+            })
+            .collect()
+    }
+
+    #[test]
+    fn metadata_key_order_does_not_change_the_hash() {
+        let a = event(serde_json::json!({ "a": 1, "b": { "y": 2, "x": 1 } }));
+        let mut b = a.clone();
+        b.metadata = Some(serde_json::json!({ "b": { "x": 1, "y": 2 }, "a": 1 }));
+        assert_eq!(a.compute_hash(1, None), b.compute_hash(1, None));
+    }
+
+    #[test]
+    fn intact_chain_verifies_and_returns_head() {
+        let events = chain(3);
+        assert_eq!(verify_chain(&events).unwrap(), events[2].hash);
+        assert_eq!(verify_chain(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn tampered_content_is_reported_at_the_first_broken_link() {
+        let mut events = chain(3);
+        events[1].description = Some("Riego editado".into());
+
+        let link = verify_chain(&events).unwrap_err();
+        assert_eq!(link.event_id, events[1].id);
+        assert_eq!(link.reason, ChainBreak::HashMismatch);
+    }
+
+    #[test]
+    fn removed_event_is_reported_as_a_gap() {
+        let mut events = chain(3);
+        events.remove(1);
+
+        let link = verify_chain(&events).unwrap_err();
+        assert_eq!(link.sequence, 3);
+        assert_eq!(link.reason, ChainBreak::SequenceGap);
+    }
+}
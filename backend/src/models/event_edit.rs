@@ -0,0 +1,255 @@
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use kairos_common::{ApiError, EventType, LotStatus, Point, UpdateEventRequest};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        event::Event,
+        event_chain::seal_pending,
+        sql_enums::{DbEventType, DbLotStatus, DbPoint},
+    },
+    schema::{events, lots},
+};
+
+/// Valores de `metadata.correction.action` en los eventos de corrección.
+pub const AMEND: &str = "amend";
+pub const RETRACT: &str = "retract";
+
+/// Indica si un evento es la anulación de otro.
+pub fn is_retraction(metadata: Option<&serde_json::Value>) -> bool {
+    metadata
+        .and_then(|m| m.pointer("/correction/action"))
+        .and_then(|action| action.as_str())
+        == Some(RETRACT)
+}
+
+/// Evento junto con los datos del lote necesarios para autorizar y validar cambios.
+pub struct OwnedEvent {
+    pub event: Event,
+    pub producer_id: Uuid,
+    pub lot_status: LotStatus,
+}
+
+/// Valida tipo y descripción de un evento antes de guardarlo; los tipos que genera
+/// el sistema no se pueden registrar a mano.
+pub fn validate_event_fields(
+    event_type: EventType,
+    description: Option<&str>,
+) -> Result<(), AppError> {
+    if event_type.is_system_generated() {
+        return Err(AppError::BadRequest(format!(
+            "{} events are recorded automatically and cannot be created or assigned manually",
+            event_type
+        )));
+    }
+    let description_len = description.map(|d| d.trim().chars().count());
+    if matches!(description_len, Some(len) if len < 3) {
+        return Err(AppError::BadRequest(
+            "description must be at least 3 characters long".into(),
+        ));
+    }
+    if event_type.requires_description() && description_len.is_none() {
+        return Err(AppError::BadRequest(format!(
+            "{} events require a description",
+            event_type
+        )));
+    }
+    Ok(())
+}
+
+fn ensure_editable(owned: &OwnedEvent) -> Result<(), AppError> {
+    let event_type = owned.event.event_type;
+    if !event_type.is_editable() {
+        return Err(AppError::Conflict(ApiError {
+            code: "EVENT_NOT_EDITABLE".into(),
+            message: format!(
+                "{} events are part of the lot history and cannot be corrected",
+                event_type
+            ),
+            details: Some(serde_json::json!({ "event_type": event_type })),
+        }));
+    }
+    if owned.lot_status.is_terminal() {
+        return Err(AppError::Conflict(ApiError {
+            code: "LOT_CLOSED".into(),
+            message: format!("Events of a {} lot cannot be corrected", owned.lot_status),
+            details: Some(serde_json::json!({ "lot_status": owned.lot_status })),
+        }));
+    }
+    Ok(())
+}
+
+impl Event {
+    pub fn find_with_owner(conn: &mut PgConnection, event_id: Uuid) -> QueryResult<OwnedEvent> {
+        let (event, producer_id, lot_status) = events::table
+            .inner_join(lots::table)
+            .filter(events::id.eq(event_id))
+            .filter(events::deleted_at.is_null())
+            .select((Event::as_select(), lots::producer_id, lots::current_status))
+            .first::<(Event, Uuid, DbLotStatus)>(conn)?;
+
+        Ok(OwnedEvent {
+            event,
+            producer_id,
+            lot_status: lot_status.0,
+        })
+    }
+
+    /// Última corrección o anulación del evento siguiendo la cadena
+    /// `corrects_event_id`; `None` si nunca se ha corregido.
+    pub fn latest_correction(conn: &mut PgConnection, event_id: Uuid) -> QueryResult<Option<Event>> {
+        let mut latest: Option<Event> = None;
+        let mut current = event_id;
+        while let Some(next) = events::table
+            .filter(events::corrects_event_id.eq(current))
+            .select(Event::as_select())
+            .first::<Event>(conn)
+            .optional()?
+        {
+            current = next.id;
+            latest = Some(next);
+        }
+        Ok(latest)
+    }
+
+    /// Corrige un evento de campo añadiendo uno nuevo que lo referencia; el original
+    /// queda intacto en la cadena. Los eventos del sistema y los de lotes cerrados
+    /// (SOLD, CANCELLED) no admiten correcciones.
+    pub fn correct(
+        conn: &mut PgConnection,
+        owned: &OwnedEvent,
+        request: UpdateEventRequest,
+        actor_id: Uuid,
+    ) -> Result<Event, AppError> {
+        ensure_editable(owned)?;
+
+        let original = &owned.event;
+        let event_type = request.event_type.unwrap_or(original.event_type);
+        let description = request.description.or_else(|| original.description.clone());
+        validate_event_fields(event_type, description.as_deref())?;
+
+        let correction = serde_json::json!({ "action": AMEND, "actor_id": actor_id });
+        append_correction(
+            conn,
+            owned,
+            event_type,
+            description,
+            request
+                .event_location
+                .or_else(|| original.event_location.clone()),
+            request.coordinates.or(original.coordinates),
+            with_correction(
+                request.metadata.or_else(|| original.metadata.clone()),
+                correction,
+            ),
+        )
+    }
+
+    /// Anula un evento de campo añadiendo una corrección que lo marca como retirado.
+    pub fn retract(
+        conn: &mut PgConnection,
+        owned: &OwnedEvent,
+        actor_id: Uuid,
+    ) -> Result<Event, AppError> {
+        ensure_editable(owned)?;
+
+        let original = &owned.event;
+        let correction = serde_json::json!({ "action": RETRACT, "actor_id": actor_id });
+        append_correction(
+            conn,
+            owned,
+            original.event_type,
+            original.description.clone(),
+            original.event_location.clone(),
+            original.coordinates,
+            with_correction(None, correction),
+        )
+    }
+}
+
+fn with_correction(
+    metadata: Option<serde_json::Value>,
+    correction: serde_json::Value,
+) -> serde_json::Value {
+    let mut metadata = match metadata {
+        Some(serde_json::Value::Object(map)) => map,
+        Some(other) => serde_json::Map::from_iter([("value".to_string(), other)]),
+        None => serde_json::Map::new(),
+    };
+    metadata.insert("correction".into(), correction);
+    serde_json::Value::Object(metadata)
+}
+
+fn append_correction(
+    conn: &mut PgConnection,
+    owned: &OwnedEvent,
+    event_type: EventType,
+    description: Option<String>,
+    event_location: Option<String>,
+    coordinates: Option<Point>,
+    metadata: serde_json::Value,
+) -> Result<Event, AppError> {
+    let original_id = owned.event.id;
+
+    conn.transaction::<_, AppError, _>(|conn| {
+        let corrected_by: Option<Uuid> = events::table
+            .filter(events::corrects_event_id.eq(original_id))
+            .select(events::id)
+            .first(conn)
+            .optional()?;
+        if let Some(corrected_by) = corrected_by {
+            return Err(already_corrected(original_id, Some(corrected_by)));
+        }
+
+        let event = diesel::insert_into(events::table)
+            .values((
+                events::lot_id.eq(owned.event.lot_id),
+                events::event_type.eq(DbEventType(event_type)),
+                events::description.eq(description),
+                events::event_location.eq(event_location),
+                events::coordinates.eq(coordinates.map(DbPoint)),
+                events::metadata.eq(metadata),
+                events::corrects_event_id.eq(original_id),
+            ))
+            .returning(Event::as_returning())
+            .get_result(conn)
+            .map_err(|err| match err {
+                // Otra corrección concurrente ganó la carrera por idx_events_corrects_event_id
+                DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    already_corrected(original_id, None)
+                }
+                other => other.into(),
+            })?;
+
+        seal_pending(conn, owned.event.lot_id)?;
+        Ok(event)
+    })
+}
+
+fn already_corrected(event_id: Uuid, corrected_by: Option<Uuid>) -> AppError {
+    AppError::Conflict(ApiError {
+        code: "EVENT_ALREADY_CORRECTED".into(),
+        message: "Event already has a correction, correct the latest version instead".into(),
+        details: Some(serde_json::json!({ "event_id": event_id, "corrected_by": corrected_by })),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_event_types_cannot_be_set_manually() {
+        assert!(validate_event_fields(EventType::HarvestCompleted, None).is_err());
+        assert!(validate_event_fields(EventType::Irrigation, None).is_ok());
+    }
+
+    #[test]
+    fn description_rules_match_the_database_check() {
+        assert!(validate_event_fields(EventType::PestControl, None).is_err());
+        assert!(validate_event_fields(EventType::PestControl, Some("ok")).is_err());
+        assert!(validate_event_fields(EventType::PestControl, Some("Aplicación de neem")).is_ok());
+    }
+}
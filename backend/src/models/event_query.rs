@@ -0,0 +1,83 @@
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use kairos_common::EventQuery;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        event::Event,
+        pagination::{is_descending, Page},
+        sql_enums::DbEventType,
+    },
+    schema::{events, lots},
+};
+
+/// Columnas por las que se puede ordenar; cualquier otro valor de `sort_by` es un 400.
+pub const SORTABLE_COLUMNS: &[&str] = &["created_at", "event_type"];
+
+type EventsOfProducer<'a> =
+    diesel::dsl::IntoBoxed<'a, diesel::dsl::InnerJoin<events::table, lots::table>, Pg>;
+
+/// Eventos de los lotes del productor con los filtros de `EventQuery`.
+pub(crate) fn filtered<'a>(producer_id: Uuid, query: &EventQuery) -> EventsOfProducer<'a> {
+    let mut q = events::table
+        .inner_join(lots::table)
+        .filter(lots::producer_id.eq(producer_id))
+        .into_boxed();
+
+    if !query.include_deleted {
+        q = q.filter(events::deleted_at.is_null());
+    }
+    // Quien pide los eventos de un lote concreto los ve aunque esté archivado
+    match query.lot_id {
+        Some(lot_id) => q = q.filter(events::lot_id.eq(lot_id)),
+        None if !query.include_archived => q = q.filter(lots::archived_at.is_null()),
+        None => {}
+    }
+    if let Some(event_type) = query.event_type {
+        q = q.filter(events::event_type.eq(DbEventType(event_type)));
+    }
+
+    q
+}
+
+impl Event {
+    /// Lista los eventos de los lotes de un productor; `EventQuery.lot_id` acota a un lote.
+    /// Devuelve la página pedida y el total de coincidencias.
+    pub fn search(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        query: &EventQuery,
+    ) -> Result<(Vec<Event>, i64), AppError> {
+        let sort_by = query.sort_by.as_deref().unwrap_or("created_at");
+        if !SORTABLE_COLUMNS.contains(&sort_by) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid sort_by '{}', expected one of: {}",
+                sort_by,
+                SORTABLE_COLUMNS.join(", ")
+            )));
+        }
+        let descending = is_descending(query.sort_order.as_deref())?;
+        let page = Page::new(query.page, query.per_page);
+
+        let total = filtered(producer_id, query).count().get_result(conn)?;
+
+        let q = filtered(producer_id, query);
+        let q = match (sort_by, descending) {
+            ("event_type", true) => q.order((events::event_type.desc(), events::created_at.desc())),
+            ("event_type", false) => q.order((events::event_type.asc(), events::created_at.asc())),
+            (_, true) => q.order(events::created_at.desc()),
+            (_, false) => q.order(events::created_at.asc()),
+        };
+
+        let events = q
+            .then_order_by(events::id)
+            .offset(page.offset())
+            .limit(page.per_page)
+            .select(Event::as_select())
+            .load(conn)?;
+
+        Ok((events, total))
+    }
+}
@@ -0,0 +1,287 @@
+//! Borrado lógico y archivo de lotes. Un lote borrado desaparece de la API salvo para
+//! los administradores, que lo pueden restaurar; uno archivado solo se oculta de los
+//! listados. En ambos casos el lote y su cadena de eventos se conservan.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::prelude::*;
+use kairos_common::{ApiError, EventType, LotStatus};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        lot::Lot,
+        lot_genealogy::{lock_lots, record_event},
+        lot_query::coalesce,
+        sql_enums::DbLotStatus,
+    },
+    schema::{events, lots},
+};
+
+/// Solo se archivan lotes cerrados.
+fn ensure_archivable(status: LotStatus) -> Result<(), AppError> {
+    if !status.is_terminal() {
+        return Err(AppError::Conflict(ApiError {
+            code: "LOT_NOT_ARCHIVABLE".into(),
+            message: format!("Only SOLD or CANCELLED lots can be archived, lot is {}", status),
+            details: Some(serde_json::json!({ "lot_status": status })),
+        }));
+    }
+    Ok(())
+}
+
+impl Lot {
+    /// Como `find_by_id`, pero los lotes borrados no se encuentran.
+    pub fn find_live(conn: &mut PgConnection, id: Uuid) -> QueryResult<Lot> {
+        lots::table
+            .filter(lots::id.eq(id))
+            .filter(lots::deleted_at.is_null())
+            .select(Lot::as_select())
+            .first(conn)
+    }
+
+    /// Marca el lote y sus eventos como borrados con el mismo instante, después de
+    /// dejar constancia del borrado en la cadena.
+    pub fn soft_delete(
+        conn: &mut PgConnection,
+        lot_id: Uuid,
+        actor_id: Uuid,
+    ) -> Result<DateTime<Utc>, AppError> {
+        conn.transaction::<_, AppError, _>(|conn| {
+            let lot = lock_lots(conn, &[lot_id])?
+                .pop()
+                .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+            let before = Lot::snapshot(conn, lot.id)?;
+
+            record_event(
+                conn,
+                lot.id,
+                EventType::LotUpdated,
+                "Lot deleted".into(),
+                serde_json::json!({ "deletion": { "action": "delete" }, "actor_id": actor_id }),
+            )?;
+
+            let deleted_at: Option<DateTime<Utc>> = diesel::update(
+                lots::table
+                    .filter(lots::id.eq(lot.id))
+                    .filter(lots::deleted_at.is_null()),
+            )
+            .set(lots::deleted_at.eq(diesel::dsl::now))
+            .returning(lots::deleted_at)
+            .get_result(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+
+            diesel::update(
+                events::table
+                    .filter(events::lot_id.eq(lot.id))
+                    .filter(events::deleted_at.is_null()),
+            )
+            .set(events::deleted_at.eq(deleted_at))
+            .execute(conn)?;
+            Lot::record_version(conn, before, actor_id)?;
+
+            deleted_at.ok_or_else(|| AppError::InternalServerError("deleted_at was not set".into()))
+        })
+    }
+
+    /// Devuelve el lote borrado y los eventos que se borraron con él. Los eventos
+    /// borrados antes por otro motivo siguen borrados.
+    pub fn restore(conn: &mut PgConnection, lot_id: Uuid, actor_id: Uuid) -> Result<Lot, AppError> {
+        conn.transaction::<_, AppError, _>(|conn| {
+            let deleted_at: Option<DateTime<Utc>> = lots::table
+                .filter(lots::id.eq(lot_id))
+                .for_update()
+                .select(lots::deleted_at)
+                .first(conn)
+                .optional()?
+                .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+            let Some(deleted_at) = deleted_at else {
+                return Err(AppError::Conflict(ApiError {
+                    code: "LOT_NOT_DELETED".into(),
+                    message: "Lot is not deleted".into(),
+                    details: None,
+                }));
+            };
+            let before = Lot::snapshot(conn, lot_id)?;
+
+            diesel::update(lots::table.find(lot_id))
+                .set(lots::deleted_at.eq(None::<DateTime<Utc>>))
+                .execute(conn)?;
+            diesel::update(
+                events::table
+                    .filter(events::lot_id.eq(lot_id))
+                    .filter(events::deleted_at.eq(deleted_at)),
+            )
+            .set(events::deleted_at.eq(None::<DateTime<Utc>>))
+            .execute(conn)?;
+            Lot::record_version(conn, before, actor_id)?;
+
+            record_event(
+                conn,
+                lot_id,
+                EventType::LotUpdated,
+                "Lot restored".into(),
+                serde_json::json!({
+                    "deletion": { "action": "restore", "deleted_at": deleted_at },
+                    "actor_id": actor_id,
+                }),
+            )?;
+
+            Ok(Lot::find_by_id(conn, lot_id)?)
+        })
+    }
+
+    /// Archiva un lote cerrado; si ya lo estaba, devuelve cuándo se archivó.
+    pub fn archive(conn: &mut PgConnection, lot_id: Uuid, actor_id: Uuid) -> Result<DateTime<Utc>, AppError> {
+        conn.transaction::<_, AppError, _>(|conn| {
+            let lot = lock_lots(conn, &[lot_id])?
+                .pop()
+                .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+            ensure_archivable(lot.current_status)?;
+            let before = Lot::snapshot(conn, lot.id)?;
+
+            let archived_at: Option<DateTime<Utc>> = diesel::update(
+                lots::table
+                    .filter(lots::id.eq(lot.id))
+                    .filter(lots::archived_at.is_null()),
+            )
+            .set(lots::archived_at.eq(diesel::dsl::now))
+            .returning(lots::archived_at)
+            .get_result(conn)
+            .optional()?
+            .flatten();
+
+            match archived_at {
+                Some(archived_at) => {
+                    Lot::record_version(conn, before, actor_id)?;
+                    Ok(archived_at)
+                }
+                None => lots::table
+                    .find(lot.id)
+                    .select(lots::archived_at)
+                    .first::<Option<DateTime<Utc>>>(conn)?
+                    .ok_or_else(|| AppError::InternalServerError("archived_at was not set".into())),
+            }
+        })
+    }
+
+    pub fn unarchive(conn: &mut PgConnection, lot_id: Uuid, actor_id: Uuid) -> Result<(), AppError> {
+        conn.transaction::<_, AppError, _>(|conn| {
+            lock_lots(conn, &[lot_id])?;
+            let before = Lot::snapshot(conn, lot_id)?;
+            let updated = diesel::update(
+                lots::table
+                    .filter(lots::id.eq(lot_id))
+                    .filter(lots::archived_at.is_not_null()),
+            )
+            .set(lots::archived_at.eq(None::<DateTime<Utc>>))
+            .execute(conn)?;
+            if updated > 0 {
+                Lot::record_version(conn, before, actor_id)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Archiva los lotes cerrados del productor cosechados (o con cosecha estimada)
+    /// hasta `season_end`, incluido. Devuelve los lotes archivados.
+    pub fn archive_season(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        season_end: NaiveDate,
+        actor_id: Uuid,
+    ) -> Result<Vec<Uuid>, AppError> {
+        let closed = vec![DbLotStatus(LotStatus::Sold), DbLotStatus(LotStatus::Cancelled)];
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let ids: Vec<Uuid> = lots::table
+                .filter(lots::producer_id.eq(producer_id))
+                .filter(lots::deleted_at.is_null())
+                .filter(lots::archived_at.is_null())
+                .filter(lots::current_status.eq_any(closed))
+                .filter(coalesce(lots::actual_harvest_date, lots::estimated_harvest_date).le(season_end))
+                .order(lots::id)
+                .for_update()
+                .select(lots::id)
+                .load(conn)?;
+
+            for &lot_id in &ids {
+                let before = Lot::snapshot(conn, lot_id)?;
+                diesel::update(lots::table.find(lot_id))
+                    .set(lots::archived_at.eq(diesel::dsl::now))
+                    .execute(conn)?;
+                Lot::record_version(conn, before, actor_id)?;
+            }
+            Ok(ids)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_connection;
+    use diesel::sql_types::{Date, Uuid as SqlUuid};
+
+    /// Lote vendido cosechado hace dos años. Se inserta con el trigger desactivado,
+    /// como si la fecha se hubiera fijado entonces.
+    fn sold_lot_harvested_long_ago(conn: &mut PgConnection) -> (Uuid, Uuid) {
+        let producer_id = Uuid::new_v4();
+        let lot_id = Uuid::new_v4();
+        let harvested = Utc::now().date_naive() - chrono::Duration::days(730);
+
+        diesel::sql_query(
+            "INSERT INTO producers (id, full_name, email, password_hash) \
+             VALUES ($1, 'Old Farm', 'old-farm-' || $1 || '@example.com', repeat('x', 60))",
+        )
+        .bind::<SqlUuid, _>(producer_id)
+        .execute(conn)
+        .unwrap();
+        diesel::sql_query("ALTER TABLE lots DISABLE TRIGGER check_lots_actual_harvest_date")
+            .execute(conn)
+            .unwrap();
+        diesel::sql_query(
+            "INSERT INTO lots (id, producer_id, lot_code, product_name, crop_type, \
+             estimated_quantity, unit_of_measure, estimated_harvest_date, actual_harvest_date, current_status) \
+             VALUES ($1, $2, generate_lot_code(), 'Tomate', 'CONVENTIONAL', 500, 'kg', $3, $3, 'SOLD')",
+        )
+        .bind::<SqlUuid, _>(lot_id)
+        .bind::<SqlUuid, _>(producer_id)
+        .bind::<Date, _>(harvested)
+        .execute(conn)
+        .unwrap();
+        diesel::sql_query("ALTER TABLE lots ENABLE TRIGGER check_lots_actual_harvest_date")
+            .execute(conn)
+            .unwrap();
+        (producer_id, lot_id)
+    }
+
+    #[test]
+    fn lots_harvested_over_a_year_ago_can_be_archived_and_deleted() {
+        let Some(mut conn) = test_connection() else {
+            return;
+        };
+        let (actor, lot_id) = sold_lot_harvested_long_ago(&mut conn);
+        assert_eq!(Lot::find_by_id(&mut conn, lot_id).unwrap().current_status, LotStatus::Sold);
+
+        Lot::archive(&mut conn, lot_id, actor).unwrap();
+        Lot::unarchive(&mut conn, lot_id, actor).unwrap();
+        Lot::soft_delete(&mut conn, lot_id, actor).unwrap();
+        Lot::restore(&mut conn, lot_id, actor).unwrap();
+    }
+
+    #[test]
+    fn only_closed_lots_can_be_archived() {
+        assert!(ensure_archivable(LotStatus::Sold).is_ok());
+        assert!(ensure_archivable(LotStatus::Cancelled).is_ok());
+
+        match ensure_archivable(LotStatus::Harvested) {
+            Err(AppError::Conflict(error)) => {
+                assert_eq!(error.code, "LOT_NOT_ARCHIVABLE");
+                assert_eq!(error.details, Some(serde_json::json!({ "lot_status": "HARVESTED" })));
+            }
+            other => panic!("expected 409, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Exportación de los lotes de un productor, o de sus eventos, con los filtros de
+//! `LotQuery` y `EventQuery` (ver `crate::exports` para los formatos).
+//!
+//! Se recorre el resultado por tandas ordenadas por `(created_at, id)`, así que la
+//! memoria no depende del número de filas. No es una instantánea: una fila creada
+//! durante la exportación puede aparecer o no.
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use kairos_common::{EventQuery, EventType, LotQuery};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    exports::{self, Cell, Column, ExportFormat, Kind, Output, Row, BATCH_SIZE},
+    models::{
+        event_query,
+        lot::Lot,
+        lot_query,
+        lot_query::validate_harvest_window,
+        sql_enums::{DbEventType, DbPoint},
+    },
+    schema::{events, lots},
+};
+
+const fn column(name: &'static str, kind: Kind) -> Column {
+    Column { name, kind }
+}
+
+pub const LOT_COLUMNS: &[Column] = &[
+    column("lot_id", Kind::Text),
+    column("lot_code", Kind::Text),
+    column("product_name", Kind::Text),
+    column("crop_type", Kind::Text),
+    column("estimated_quantity", Kind::Decimal),
+    column("unit_of_measure", Kind::Text),
+    column("estimated_harvest_date", Kind::Date),
+    column("actual_harvest_date", Kind::Date),
+    column("current_status", Kind::Text),
+    column("additional_description", Kind::Text),
+    column("latitude", Kind::Float),
+    column("longitude", Kind::Float),
+    column("created_at", Kind::Timestamp),
+    column("updated_at", Kind::Timestamp),
+];
+
+pub const EVENT_COLUMNS: &[Column] = &[
+    column("event_id", Kind::Text),
+    column("lot_id", Kind::Text),
+    column("lot_code", Kind::Text),
+    column("product_name", Kind::Text),
+    column("event_type", Kind::Text),
+    column("description", Kind::Text),
+    column("event_location", Kind::Text),
+    column("latitude", Kind::Float),
+    column("longitude", Kind::Float),
+    column("metadata", Kind::Text),
+    column("corrects_event_id", Kind::Text),
+    column("hash", Kind::Text),
+    column("created_at", Kind::Timestamp),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportDataset {
+    Lots,
+    Events,
+}
+
+impl ExportDataset {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "lots" => Ok(ExportDataset::Lots),
+            "events" => Ok(ExportDataset::Events),
+            _ => Err(format!(
+                "Unsupported dataset '{}', expected lots or events",
+                value
+            )),
+        }
+    }
+}
+
+type ExportedEvent = (
+    Uuid,
+    Uuid,
+    String,
+    String,
+    DbEventType,
+    Option<String>,
+    Option<String>,
+    Option<DbPoint>,
+    Option<serde_json::Value>,
+    Option<Uuid>,
+    Option<String>,
+    DateTime<Utc>,
+);
+
+fn coordinates(point: Option<kairos_common::Point>) -> [Cell; 2] {
+    [point.map(|p| p.y).into(), point.map(|p| p.x).into()]
+}
+
+fn lot_row(lot: &Lot) -> Row {
+    let [latitude, longitude] = coordinates(lot.location_coordinates);
+    vec![
+        Cell::Text(lot.id.to_string()),
+        Cell::Text(lot.lot_code.clone()),
+        Cell::Text(lot.product_name.clone()),
+        Cell::Text(lot.crop_type.to_str().into()),
+        Cell::Decimal(lot.estimated_quantity),
+        Cell::Text(lot.unit_of_measure.clone()),
+        Cell::Date(lot.estimated_harvest_date),
+        lot.actual_harvest_date.into(),
+        Cell::Text(lot.current_status.to_str().into()),
+        lot.additional_description.clone().into(),
+        latitude,
+        longitude,
+        Cell::Timestamp(lot.created_at),
+        Cell::Timestamp(lot.updated_at),
+    ]
+}
+
+fn event_row(event: ExportedEvent) -> Row {
+    let (
+        id,
+        lot_id,
+        lot_code,
+        product_name,
+        event_type,
+        description,
+        event_location,
+        point,
+        metadata,
+        corrects_event_id,
+        hash,
+        created_at,
+    ) = event;
+    let event_type: EventType = event_type.0;
+    let [latitude, longitude] = coordinates(point.map(|p| p.0));
+    vec![
+        Cell::Text(id.to_string()),
+        Cell::Text(lot_id.to_string()),
+        Cell::Text(lot_code),
+        Cell::Text(product_name),
+        Cell::Text(event_type.to_str().into()),
+        description.into(),
+        event_location.into(),
+        latitude,
+        longitude,
+        metadata.map(|m| m.to_string()).into(),
+        corrects_event_id.map(|id| id.to_string()).into(),
+        hash.into(),
+        Cell::Timestamp(created_at),
+    ]
+}
+
+/// Se exportan todas las coincidencias en orden de creación, así que pedir orden o
+/// página es un error y no algo que se ignora en silencio.
+fn validate_unpaginated(query: &LotQuery) -> Result<(), AppError> {
+    if query.sort_by.is_some() || query.sort_order.is_some() || query.page.is_some() || query.per_page.is_some() {
+        return Err(AppError::BadRequest(
+            "Exports are not sorted or paginated: remove sort_by, sort_order, page and per_page".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Exportación ya validada; se ejecuta fuera del hilo de la petición con `run`.
+#[derive(Debug, Clone)]
+pub struct LotExport {
+    pub producer_id: Uuid,
+    pub format: ExportFormat,
+    pub dataset: ExportDataset,
+    pub lots: LotQuery,
+    pub events: EventQuery,
+}
+
+impl LotExport {
+    pub fn new(
+        producer_id: Uuid,
+        format: &str,
+        dataset: Option<&str>,
+        lots: LotQuery,
+        events: EventQuery,
+    ) -> Result<Self, AppError> {
+        validate_harvest_window(&lots)?;
+        validate_unpaginated(&lots)?;
+        Ok(LotExport {
+            producer_id,
+            format: ExportFormat::parse(format).map_err(AppError::BadRequest)?,
+            dataset: dataset
+                .map(ExportDataset::parse)
+                .transpose()
+                .map_err(AppError::BadRequest)?
+                .unwrap_or(ExportDataset::Lots),
+            lots,
+            events,
+        })
+    }
+
+    pub fn file_name(&self) -> String {
+        let dataset = match self.dataset {
+            ExportDataset::Lots => "lots",
+            ExportDataset::Events => "events",
+        };
+        format!(
+            "{}-{}.{}",
+            dataset,
+            Utc::now().format("%Y%m%d"),
+            self.format.extension()
+        )
+    }
+
+    pub fn run(&self, conn: &mut PgConnection, out: Output<'_>) -> Result<(), String> {
+        let db_error = |e: diesel::result::Error| format!("export query failed: {}", e);
+
+        match self.dataset {
+            ExportDataset::Lots => {
+                let mut encoder = exports::encoder(self.format, LOT_COLUMNS, out)?;
+                let mut after = None;
+                loop {
+                    let batch = self.lots_after(conn, after).map_err(db_error)?;
+                    let Some(last) = batch.last() else { break };
+                    after = Some((last.created_at, last.id));
+                    encoder.write_rows(&batch.iter().map(lot_row).collect::<Vec<_>>())?;
+                }
+                encoder.finish()
+            }
+            ExportDataset::Events => {
+                let mut encoder = exports::encoder(self.format, EVENT_COLUMNS, out)?;
+                let mut after = None;
+                loop {
+                    let batch = self.events_after(conn, after).map_err(db_error)?;
+                    let Some(last) = batch.last() else { break };
+                    after = Some((last.11, last.0));
+                    encoder.write_rows(&batch.into_iter().map(event_row).collect::<Vec<_>>())?;
+                }
+                encoder.finish()
+            }
+        }
+    }
+
+    fn lots_after(
+        &self,
+        conn: &mut PgConnection,
+        after: Option<(DateTime<Utc>, Uuid)>,
+    ) -> QueryResult<Vec<Lot>> {
+        let mut q = lot_query::filtered(Some(self.producer_id), &self.lots);
+        if let Some((created_at, id)) = after {
+            q = q.filter(
+                lots::created_at
+                    .gt(created_at)
+                    .or(lots::created_at.eq(created_at).and(lots::id.gt(id))),
+            );
+        }
+        q.order((lots::created_at, lots::id))
+            .limit(BATCH_SIZE)
+            .select(Lot::as_select())
+            .load(conn)
+    }
+
+    fn events_after(
+        &self,
+        conn: &mut PgConnection,
+        after: Option<(DateTime<Utc>, Uuid)>,
+    ) -> QueryResult<Vec<ExportedEvent>> {
+        let lot_ids = lot_query::filtered(Some(self.producer_id), &self.lots).select(lots::id);
+        let mut q = event_query::filtered(self.producer_id, &self.events)
+            .filter(events::lot_id.eq_any(lot_ids));
+        if let Some((created_at, id)) = after {
+            q = q.filter(
+                events::created_at
+                    .gt(created_at)
+                    .or(events::created_at.eq(created_at).and(events::id.gt(id))),
+            );
+        }
+        q.order((events::created_at, events::id))
+            .limit(BATCH_SIZE)
+            .select((
+                events::id,
+                events::lot_id,
+                lots::lot_code,
+                lots::product_name,
+                events::event_type,
+                events::description,
+                events::event_location,
+                events::coordinates,
+                events::metadata,
+                events::corrects_event_id,
+                events::hash,
+                events::created_at,
+            ))
+            .load(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_reject_sorting_and_pagination() {
+        let export = |lots: LotQuery| LotExport::new(Uuid::new_v4(), "csv", None, lots, EventQuery::default());
+        assert!(export(LotQuery::default()).is_ok());
+
+        for lots in [
+            LotQuery { sort_by: Some("created_at".into()), ..LotQuery::default() },
+            LotQuery { page: Some(2), ..LotQuery::default() },
+            LotQuery { per_page: Some(50), ..LotQuery::default() },
+        ] {
+            assert!(matches!(export(lots), Err(AppError::BadRequest(_))));
+        }
+    }
+}
@@ -0,0 +1,543 @@
+//! División y fusión de lotes, con la genealogía padre → hijo en `lot_genealogy`.
+//!
+//! La cantidad disponible de un lote es su cantidad menos lo ya traspasado a lotes
+//! hijos y lo ya enviado a compradores; ninguna operación puede sacar más de lo disponible.
+
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Integer, Numeric, Text, Uuid as SqlUuid};
+use kairos_common::{
+    ApiError, EventType, GenealogyLink, GenealogyOperation, LotGenealogy, LotStatus,
+    MergeLotsRequest, SplitLotRequest,
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        event_chain::seal_pending,
+        lot::Lot,
+        sql_enums::{DbCropType, DbEventType, DbLotStatus, DbPoint},
+    },
+    schema::{events, lot_genealogy, lots, shipments},
+};
+
+/// Límite de pasos al recorrer la genealogía.
+pub const MAX_GENEALOGY_DEPTH: i32 = 50;
+
+diesel::define_sql_function! {
+    fn generate_lot_code() -> Text;
+}
+
+/// Datos del lote nuevo que no se heredan sin más de los lotes de origen.
+struct ChildLot<'a> {
+    template: &'a Lot,
+    product_name: String,
+    quantity: Decimal,
+    estimated_harvest_date: chrono::NaiveDate,
+    actual_harvest_date: Option<chrono::NaiveDate>,
+    additional_description: Option<String>,
+}
+
+pub(crate) fn validate_quantity(quantity: Decimal) -> Result<(), AppError> {
+    if quantity <= Decimal::ZERO {
+        return Err(AppError::BadRequest(
+            "quantity must be greater than zero".into(),
+        ));
+    }
+    if quantity.round_dp(3) != quantity {
+        return Err(AppError::BadRequest(
+            "quantity supports at most 3 decimal places".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_product_name(name: Option<&str>) -> Result<(), AppError> {
+    if matches!(name, Some(n) if n.trim().chars().count() < 2) {
+        return Err(AppError::BadRequest(
+            "product_name must be at least 2 characters long".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Solo se divide o fusiona producto ya cosechado.
+fn ensure_divisible(lot: &Lot) -> Result<(), AppError> {
+    if lot.current_status != LotStatus::Harvested {
+        return Err(AppError::Conflict(ApiError {
+            code: "LOT_NOT_DIVISIBLE".into(),
+            message: format!(
+                "Only HARVESTED lots can be split or merged, {} is {}",
+                lot.lot_code, lot.current_status
+            ),
+            details: Some(serde_json::json!({
+                "lot_id": lot.id,
+                "current_status": lot.current_status,
+            })),
+        }));
+    }
+    Ok(())
+}
+
+pub(crate) fn insufficient_quantity(lot: &Lot, available: Decimal, requested: Decimal) -> AppError {
+    AppError::Conflict(ApiError {
+        code: "INSUFFICIENT_QUANTITY".into(),
+        message: format!(
+            "Lot {} has {} {} available, {} requested",
+            lot.lot_code, available, lot.unit_of_measure, requested
+        ),
+        details: Some(serde_json::json!({
+            "lot_id": lot.id,
+            "available": available,
+            "requested": requested,
+        })),
+    })
+}
+
+/// Bloquea los lotes en orden de id para que dos operaciones cruzadas no se bloqueen entre sí.
+pub(crate) fn lock_lots(conn: &mut PgConnection, ids: &[Uuid]) -> QueryResult<Vec<Lot>> {
+    lots::table
+        .filter(lots::id.eq_any(ids))
+        .order(lots::id)
+        .for_update()
+        .select(Lot::as_select())
+        .load(conn)
+}
+
+fn insert_child(conn: &mut PgConnection, child: ChildLot<'_>) -> QueryResult<Lot> {
+    let lot_code: String = diesel::select(generate_lot_code()).get_result(conn)?;
+    let template = child.template;
+
+    diesel::insert_into(lots::table)
+        .values((
+            lots::producer_id.eq(template.producer_id),
+            lots::lot_code.eq(lot_code),
+            lots::product_name.eq(child.product_name),
+            lots::crop_type.eq(DbCropType(template.crop_type)),
+            lots::estimated_quantity.eq(child.quantity),
+            lots::unit_of_measure.eq(&template.unit_of_measure),
+            lots::estimated_harvest_date.eq(child.estimated_harvest_date),
+            lots::actual_harvest_date.eq(child.actual_harvest_date),
+            lots::current_status.eq(DbLotStatus(LotStatus::Harvested)),
+            lots::additional_description.eq(child.additional_description),
+            lots::location_coordinates.eq(template.location_coordinates.map(DbPoint)),
+        ))
+        .returning(Lot::as_returning())
+        .get_result(conn)
+}
+
+fn insert_link(
+    conn: &mut PgConnection,
+    parent: &Lot,
+    child: &Lot,
+    operation: GenealogyOperation,
+    quantity: Decimal,
+    actor_id: Uuid,
+) -> QueryResult<usize> {
+    diesel::insert_into(lot_genealogy::table)
+        .values((
+            lot_genealogy::parent_lot_id.eq(parent.id),
+            lot_genealogy::child_lot_id.eq(child.id),
+            lot_genealogy::operation.eq(operation.to_str()),
+            lot_genealogy::quantity.eq(quantity),
+            lot_genealogy::created_by.eq(actor_id),
+        ))
+        .execute(conn)
+}
+
+/// Deja constancia de la operación en la cadena de eventos del lote.
+pub(crate) fn record_event(
+    conn: &mut PgConnection,
+    lot_id: Uuid,
+    event_type: EventType,
+    description: String,
+    metadata: serde_json::Value,
+) -> QueryResult<usize> {
+    diesel::insert_into(events::table)
+        .values((
+            events::lot_id.eq(lot_id),
+            events::event_type.eq(DbEventType(event_type)),
+            events::description.eq(description),
+            events::metadata.eq(metadata),
+        ))
+        .execute(conn)?;
+    seal_pending(conn, lot_id)
+}
+
+fn link_json(lot: &Lot, quantity: Decimal) -> serde_json::Value {
+    serde_json::json!({ "lot_id": lot.id, "lot_code": lot.lot_code, "quantity": quantity })
+}
+
+impl Lot {
+    /// Lo que queda por traspasar o enviar: lo que tiene el lote (lo cosechado, si ya
+    /// se registró, menos sus pérdidas) menos lo ya traspasado y enviado.
+    pub fn available_quantity(conn: &mut PgConnection, lot: &Lot) -> QueryResult<Decimal> {
+        let transferred: Option<Decimal> = lot_genealogy::table
+            .filter(lot_genealogy::parent_lot_id.eq(lot.id))
+            .select(diesel::dsl::sum(lot_genealogy::quantity))
+            .get_result(conn)?;
+        let shipped: Option<Decimal> = shipments::table
+            .filter(shipments::lot_id.eq(lot.id))
+            .select(diesel::dsl::sum(shipments::quantity))
+            .get_result(conn)?;
+        Ok(Lot::on_hand(conn, lot)? - transferred.unwrap_or_default() - shipped.unwrap_or_default())
+    }
+
+    /// Divide un lote cosechado en lotes hijos. Lo que no se reparte queda en el original.
+    pub fn split(
+        conn: &mut PgConnection,
+        lot_id: Uuid,
+        request: &SplitLotRequest,
+        actor_id: Uuid,
+    ) -> Result<Vec<Lot>, AppError> {
+        if request.portions.is_empty() {
+            return Err(AppError::BadRequest("portions must not be empty".into()));
+        }
+        for portion in &request.portions {
+            validate_quantity(portion.quantity)?;
+            validate_product_name(portion.product_name.as_deref())?;
+        }
+        let requested: Decimal = request.portions.iter().map(|p| p.quantity).sum();
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let parent = lock_lots(conn, &[lot_id])?
+                .pop()
+                .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+            ensure_divisible(&parent)?;
+
+            let available = Lot::available_quantity(conn, &parent)?;
+            if requested > available {
+                return Err(insufficient_quantity(&parent, available, requested));
+            }
+
+            let mut children = Vec::with_capacity(request.portions.len());
+            for portion in &request.portions {
+                let child = insert_child(
+                    conn,
+                    ChildLot {
+                        template: &parent,
+                        product_name: portion
+                            .product_name
+                            .clone()
+                            .unwrap_or_else(|| parent.product_name.clone()),
+                        quantity: portion.quantity,
+                        estimated_harvest_date: parent.estimated_harvest_date,
+                        actual_harvest_date: parent.actual_harvest_date,
+                        additional_description: portion.additional_description.clone(),
+                    },
+                )?;
+                insert_link(
+                    conn,
+                    &parent,
+                    &child,
+                    GenealogyOperation::Split,
+                    portion.quantity,
+                    actor_id,
+                )?;
+                record_event(
+                    conn,
+                    child.id,
+                    EventType::LotRegistered,
+                    format!("Split from lot {}", parent.lot_code),
+                    serde_json::json!({
+                        "genealogy": {
+                            "operation": GenealogyOperation::Split,
+                            "parents": [link_json(&parent, portion.quantity)],
+                        },
+                        "reason": request.reason,
+                        "actor_id": actor_id,
+                    }),
+                )?;
+                children.push(child);
+            }
+
+            let links: Vec<_> = children
+                .iter()
+                .map(|child| link_json(child, child.estimated_quantity))
+                .collect();
+            record_event(
+                conn,
+                parent.id,
+                EventType::LotUpdated,
+                format!("Split into {} lots", children.len()),
+                serde_json::json!({
+                    "genealogy": { "operation": GenealogyOperation::Split, "children": links },
+                    "remaining_quantity": available - requested,
+                    "reason": request.reason,
+                    "actor_id": actor_id,
+                }),
+            )?;
+
+            Ok(children)
+        })
+    }
+
+    /// Fusiona lotes cosechados del mismo productor, tipo de cultivo y unidad en un lote nuevo.
+    pub fn merge(
+        conn: &mut PgConnection,
+        request: &MergeLotsRequest,
+        actor_id: Uuid,
+    ) -> Result<Lot, AppError> {
+        if request.sources.len() < 2 {
+            return Err(AppError::BadRequest(
+                "merge needs at least two source lots".into(),
+            ));
+        }
+        let mut ids: Vec<Uuid> = request.sources.iter().map(|s| s.lot_id).collect();
+        ids.sort();
+        ids.dedup();
+        if ids.len() != request.sources.len() {
+            return Err(AppError::BadRequest("source lots must be distinct".into()));
+        }
+        for quantity in request.sources.iter().filter_map(|s| s.quantity) {
+            validate_quantity(quantity)?;
+        }
+        validate_product_name(request.product_name.as_deref())?;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let locked = lock_lots(conn, &ids)?;
+            if locked.len() != ids.len() {
+                return Err(AppError::NotFound("Lot not found".into()));
+            }
+
+            // Se conserva el orden de la petición: el primer lote hace de plantilla
+            let mut sources = Vec::with_capacity(request.sources.len());
+            for source in &request.sources {
+                let lot = locked
+                    .iter()
+                    .find(|l| l.id == source.lot_id)
+                    .expect("every requested lot was locked");
+                ensure_divisible(lot)?;
+
+                let available = Lot::available_quantity(conn, lot)?;
+                let quantity = source.quantity.unwrap_or(available);
+                if quantity <= Decimal::ZERO || quantity > available {
+                    return Err(insufficient_quantity(lot, available, quantity));
+                }
+                sources.push((lot, quantity));
+            }
+
+            let template = sources[0].0;
+            for (lot, _) in &sources[1..] {
+                if lot.producer_id != template.producer_id {
+                    return Err(AppError::BadRequest(
+                        "Only lots of the same producer can be merged".into(),
+                    ));
+                }
+                if lot.crop_type != template.crop_type {
+                    return Err(AppError::BadRequest(
+                        "Lots with different crop types cannot be merged".into(),
+                    ));
+                }
+                if lot.unit_of_measure != template.unit_of_measure {
+                    return Err(AppError::BadRequest(
+                        "Lots with different units of measure cannot be merged".into(),
+                    ));
+                }
+            }
+
+            let total: Decimal = sources.iter().map(|(_, quantity)| *quantity).sum();
+            let child = insert_child(
+                conn,
+                ChildLot {
+                    template,
+                    product_name: request
+                        .product_name
+                        .clone()
+                        .unwrap_or_else(|| template.product_name.clone()),
+                    quantity: total,
+                    // Fechas que respetan check_harvest_dates para todos los orígenes
+                    estimated_harvest_date: sources
+                        .iter()
+                        .map(|(lot, _)| lot.estimated_harvest_date)
+                        .min()
+                        .expect("at least two sources"),
+                    actual_harvest_date: sources
+                        .iter()
+                        .filter_map(|(lot, _)| lot.actual_harvest_date)
+                        .max(),
+                    additional_description: request.additional_description.clone(),
+                },
+            )?;
+
+            for (lot, quantity) in &sources {
+                insert_link(
+                    conn,
+                    lot,
+                    &child,
+                    GenealogyOperation::Merge,
+                    *quantity,
+                    actor_id,
+                )?;
+                record_event(
+                    conn,
+                    lot.id,
+                    EventType::LotUpdated,
+                    format!("Merged into lot {}", child.lot_code),
+                    serde_json::json!({
+                        "genealogy": {
+                            "operation": GenealogyOperation::Merge,
+                            "children": [link_json(&child, *quantity)],
+                        },
+                        "reason": request.reason,
+                        "actor_id": actor_id,
+                    }),
+                )?;
+            }
+
+            let parents: Vec<_> = sources
+                .iter()
+                .map(|(lot, quantity)| link_json(lot, *quantity))
+                .collect();
+            record_event(
+                conn,
+                child.id,
+                EventType::LotRegistered,
+                format!("Merged from {} lots", parents.len()),
+                serde_json::json!({
+                    "genealogy": { "operation": GenealogyOperation::Merge, "parents": parents },
+                    "reason": request.reason,
+                    "actor_id": actor_id,
+                }),
+            )?;
+
+            Ok(child)
+        })
+    }
+
+    /// Recorre la genealogía hacia los orígenes y hacia los destinos del lote.
+    /// Con `max_depth = 1` es la trazabilidad "un paso atrás, un paso adelante".
+    pub fn genealogy(
+        conn: &mut PgConnection,
+        lot: &Lot,
+        max_depth: i32,
+    ) -> Result<LotGenealogy, AppError> {
+        let max_depth = max_depth.clamp(1, MAX_GENEALOGY_DEPTH);
+
+        Ok(LotGenealogy {
+            lot_id: lot.id,
+            lot_code: lot.lot_code.clone(),
+            available_quantity: Lot::available_quantity(conn, lot)?,
+            ancestors: walk(conn, &[lot.id], Direction::Ancestors, max_depth)?,
+            descendants: walk(conn, &[lot.id], Direction::Descendants, max_depth)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Direction {
+    Ancestors,
+    Descendants,
+}
+
+#[derive(QueryableByName)]
+struct LinkRow {
+    #[diesel(sql_type = SqlUuid)]
+    lot_id: Uuid,
+    #[diesel(sql_type = Text)]
+    lot_code: String,
+    #[diesel(sql_type = Text)]
+    product_name: String,
+    #[diesel(sql_type = Text)]
+    unit_of_measure: String,
+    #[diesel(sql_type = SqlUuid)]
+    via_lot_id: Uuid,
+    #[diesel(sql_type = Numeric)]
+    quantity: Decimal,
+    #[diesel(sql_type = Text)]
+    operation: String,
+    #[diesel(sql_type = Integer)]
+    depth: i32,
+}
+
+pub(crate) fn parse_operation(value: &str) -> GenealogyOperation {
+    match value {
+        "merge" => GenealogyOperation::Merge,
+        _ => GenealogyOperation::Split,
+    }
+}
+
+/// Recorre la genealogía desde varios lotes a la vez; un lote puede aparecer una vez
+/// por cada camino que llegue a él.
+pub(crate) fn walk(
+    conn: &mut PgConnection,
+    lot_ids: &[Uuid],
+    direction: Direction,
+    max_depth: i32,
+) -> QueryResult<Vec<GenealogyLink>> {
+    // `near` es el lado ya visitado del enlace y `far` el lote al que se llega
+    let (near, far) = match direction {
+        Direction::Ancestors => ("child_lot_id", "parent_lot_id"),
+        Direction::Descendants => ("parent_lot_id", "child_lot_id"),
+    };
+    let query = format!(
+        "WITH RECURSIVE walk AS (
+            SELECT g.{far} AS lot_id, g.{near} AS via_lot_id, g.quantity, g.operation,
+                   1 AS depth, ARRAY[g.{near}, g.{far}] AS path
+            FROM lot_genealogy g
+            WHERE g.{near} = ANY($1)
+            UNION ALL
+            SELECT g.{far}, g.{near}, g.quantity, g.operation,
+                   walk.depth + 1, walk.path || g.{far}
+            FROM lot_genealogy g
+            JOIN walk ON g.{near} = walk.lot_id
+            WHERE walk.depth < $2 AND NOT g.{far} = ANY(walk.path)
+        )
+        SELECT walk.lot_id, l.lot_code, l.product_name, l.unit_of_measure,
+               walk.via_lot_id, walk.quantity, walk.operation, walk.depth
+        FROM walk
+        JOIN lots l ON l.id = walk.lot_id
+        ORDER BY walk.depth, l.lot_code",
+        near = near,
+        far = far,
+    );
+
+    let rows: Vec<LinkRow> = diesel::sql_query(query)
+        .bind::<Array<SqlUuid>, _>(lot_ids)
+        .bind::<Integer, _>(max_depth)
+        .load(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| GenealogyLink {
+            lot_id: row.lot_id,
+            lot_code: row.lot_code,
+            product_name: row.product_name,
+            unit_of_measure: row.unit_of_measure,
+            via_lot_id: row.via_lot_id,
+            quantity: row.quantity,
+            operation: parse_operation(&row.operation),
+            depth: row.depth,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn quantities_must_be_positive_with_three_decimals() {
+        assert!(validate_quantity(Decimal::from_str("12.5").unwrap()).is_ok());
+        assert!(validate_quantity(Decimal::from_str("0.001").unwrap()).is_ok());
+        assert!(validate_quantity(Decimal::ZERO).is_err());
+        assert!(validate_quantity(Decimal::from_str("-1").unwrap()).is_err());
+        assert!(validate_quantity(Decimal::from_str("1.0005").unwrap()).is_err());
+    }
+
+    #[test]
+    fn product_name_override_is_optional() {
+        assert!(validate_product_name(None).is_ok());
+        assert!(validate_product_name(Some("Tomates")).is_ok());
+        assert!(validate_product_name(Some(" a ")).is_err());
+    }
+
+    #[test]
+    fn operations_round_trip_through_the_column() {
+        for op in [GenealogyOperation::Split, GenealogyOperation::Merge] {
+            assert_eq!(parse_operation(op.to_str()), op);
+        }
+    }
+}
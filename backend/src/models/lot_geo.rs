@@ -0,0 +1,233 @@
+//! Búsqueda de lotes por ubicación: radio, recuadro y vecinos más cercanos sobre
+//! `location_coordinates` (ver `kairos_common::geo` y la migración `lot_geo_search`).
+
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Double, Nullable};
+use kairos_common::{
+    geo::{BoundingBox, LotGeoQuery, MAX_NEAREST},
+    LotQuery, Point,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        lot::Lot,
+        lot_query::{filtered, validate_harvest_window},
+        pagination::Page,
+        sql_enums::DbPoint,
+    },
+    schema::{lots, sql_types::Point as PointType},
+};
+
+diesel::define_sql_function! {
+    fn haversine_km(a: Nullable<PointType>, b: PointType) -> Nullable<Double>;
+}
+
+diesel::define_sql_function! {
+    fn point_in_bbox(
+        p: Nullable<PointType>,
+        min_lon: Double,
+        min_lat: Double,
+        max_lon: Double,
+        max_lat: Double,
+    ) -> Nullable<Bool>;
+}
+
+/// Lote con su distancia en km al centro de la búsqueda, si se dio uno.
+#[derive(Debug, Serialize)]
+pub struct GeoLot {
+    #[serde(flatten)]
+    pub lot: Lot,
+    pub distance_km: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GeoSearch {
+    center: Option<Point>,
+    radius_km: Option<f64>,
+    bbox: Option<BoundingBox>,
+    nearest: Option<i64>,
+}
+
+fn parse(geo: &LotGeoQuery) -> Result<GeoSearch, String> {
+    let center = geo.center()?;
+    let bbox = geo.bbox.as_deref().map(BoundingBox::parse).transpose()?;
+
+    if matches!(geo.radius_km, Some(radius) if !(radius > 0.0 && radius <= 20_000.0)) {
+        return Err("radius_km must be greater than 0 and at most 20000".into());
+    }
+    if matches!(geo.nearest, Some(k) if !(1..=MAX_NEAREST).contains(&k)) {
+        return Err(format!("nearest must be between 1 and {}", MAX_NEAREST));
+    }
+    if geo.radius_km.is_none() && bbox.is_none() && geo.nearest.is_none() {
+        return Err("one of radius_km, bbox or nearest is required".into());
+    }
+
+    Ok(GeoSearch {
+        center,
+        radius_km: geo.radius_km,
+        bbox,
+        nearest: geo.nearest,
+    })
+}
+
+/// Un recuadro que cruza el antimeridiano se consulta como dos.
+fn within<'a>(q: lots::BoxedQuery<'a, Pg>, bbox: BoundingBox) -> lots::BoxedQuery<'a, Pg> {
+    let part = |b: BoundingBox| {
+        point_in_bbox(
+            lots::location_coordinates,
+            b.min_lon,
+            b.min_lat,
+            b.max_lon,
+            b.max_lat,
+        )
+    };
+    match bbox.split()[..] {
+        [east, west] => q.filter(part(east).or(part(west))),
+        _ => q.filter(part(bbox)),
+    }
+}
+
+fn search_query<'a>(
+    producer_id: Option<Uuid>,
+    query: &LotQuery,
+    search: GeoSearch,
+) -> lots::BoxedQuery<'a, Pg> {
+    let mut q = filtered(producer_id, query).filter(lots::location_coordinates.is_not_null());
+
+    if let (Some(center), Some(radius)) = (search.center, search.radius_km) {
+        // El recuadro usa el índice GiST; la distancia exacta descarta las esquinas
+        q = within(q, BoundingBox::around(center, radius));
+        q = q.filter(haversine_km(lots::location_coordinates, DbPoint(center)).le(radius));
+    }
+    if let Some(bbox) = search.bbox {
+        q = within(q, bbox);
+    }
+    q
+}
+
+impl Lot {
+    /// Lotes con ubicación que cumplen la búsqueda espacial y los filtros de `LotQuery`.
+    /// Con centro se ordenan por distancia; si no, por fecha estimada de cosecha.
+    /// `nearest` limita a los k más cercanos e ignora la paginación.
+    pub fn geo_search(
+        conn: &mut PgConnection,
+        producer_id: Option<Uuid>,
+        query: &LotQuery,
+        geo: &LotGeoQuery,
+    ) -> Result<(Vec<GeoLot>, i64), AppError> {
+        validate_harvest_window(query)?;
+        let search = parse(geo).map_err(AppError::BadRequest)?;
+        let page = Page::new(query.page, query.per_page);
+        let (limit, offset) = match search.nearest {
+            Some(k) => (k, 0),
+            None => (page.per_page, page.offset()),
+        };
+
+        let rows: Vec<(Lot, Option<f64>)> = match search.center {
+            Some(center) => {
+                let distance = || haversine_km(lots::location_coordinates, DbPoint(center));
+                search_query(producer_id, query, search)
+                    .order(distance().asc())
+                    .then_order_by(lots::id)
+                    .offset(offset)
+                    .limit(limit)
+                    .select((Lot::as_select(), distance()))
+                    .load(conn)?
+            }
+            None => search_query(producer_id, query, search)
+                .order((lots::estimated_harvest_date.asc(), lots::id))
+                .offset(offset)
+                .limit(limit)
+                .select((Lot::as_select(), None::<f64>.into_sql::<Nullable<Double>>()))
+                .load(conn)?,
+        };
+
+        let total = match search.nearest {
+            Some(_) => rows.len() as i64,
+            None => search_query(producer_id, query, search)
+                .count()
+                .get_result(conn)?,
+        };
+
+        let lots = rows
+            .into_iter()
+            .map(|(lot, distance_km)| GeoLot { lot, distance_km })
+            .collect();
+        Ok((lots, total))
+    }
+}
+
+/// Resultados como `FeatureCollection` de puntos, para pintarlos en un mapa.
+pub fn feature_collection(lots: &[GeoLot]) -> Result<Value, AppError> {
+    let features = lots
+        .iter()
+        .filter_map(|item| {
+            let point = item.lot.location_coordinates?;
+            Some(serde_json::to_value(item).map(|mut properties| {
+                if let Some(properties) = properties.as_object_mut() {
+                    properties.remove("location_coordinates");
+                }
+                json!({
+                    "type": "Feature",
+                    "id": item.lot.id,
+                    "geometry": { "type": "Point", "coordinates": [point.x, point.y] },
+                    "properties": properties,
+                })
+            }))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AppError::InternalServerError(format!("Invalid lot: {}", e)))?;
+
+    Ok(json!({ "type": "FeatureCollection", "features": features }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geo(lat: Option<f64>, lon: Option<f64>) -> LotGeoQuery {
+        LotGeoQuery {
+            lat,
+            lon,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_spatial_criterion_is_required() {
+        assert!(parse(&geo(Some(40.4), Some(-3.7))).is_err());
+        assert!(parse(&LotGeoQuery {
+            bbox: Some("-3.8,40.3,-3.6,40.5".into()),
+            ..Default::default()
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn radius_and_nearest_are_bounded() {
+        let radius = |r| LotGeoQuery {
+            radius_km: Some(r),
+            ..geo(Some(40.4), Some(-3.7))
+        };
+        assert!(parse(&radius(50.0)).is_ok());
+        assert!(parse(&radius(0.0)).is_err());
+        assert!(parse(&radius(f64::NAN)).is_err());
+
+        let nearest = |k| LotGeoQuery {
+            nearest: Some(k),
+            ..geo(Some(40.4), Some(-3.7))
+        };
+        assert!(parse(&nearest(MAX_NEAREST)).is_ok());
+        assert!(parse(&nearest(MAX_NEAREST + 1)).is_err());
+        assert!(parse(&LotGeoQuery {
+            nearest: Some(5),
+            ..geo(None, None)
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,553 @@
+//! Cantidad cosechada, pérdidas y conciliación con lo estimado (ver
+//! `kairos_common::harvest`).
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::prelude::*;
+use kairos_common::{
+    harvest::{
+        LossReason, LotHarvest, LotLoss, ReconciliationGroup, ReconciliationLine,
+        ReconciliationReport, RecordHarvestRequest, RecordLossRequest, YieldTotals,
+    },
+    units::{PackagingWeights, Quantity, Unit},
+    CropType, EventType, LotStatus,
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        lot::Lot,
+        lot_genealogy::{lock_lots, record_event, validate_quantity},
+        packaging_weight::PackagingWeight,
+        sql_enums::DbCropType,
+    },
+    schema::{lot_losses, lots, producers},
+};
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = lot_losses)]
+struct LotLossRow {
+    id: Uuid,
+    lot_id: Uuid,
+    reason: String,
+    quantity: Decimal,
+    notes: Option<String>,
+    occurred_on: NaiveDate,
+    created_by: Uuid,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<LotLossRow> for LotLoss {
+    type Error = AppError;
+
+    fn try_from(row: LotLossRow) -> Result<Self, Self::Error> {
+        Ok(LotLoss {
+            id: row.id,
+            lot_id: row.lot_id,
+            reason: row.reason.parse().map_err(AppError::InternalServerError)?,
+            quantity: row.quantity,
+            notes: row.notes,
+            occurred_on: row.occurred_on,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// Cero es válido: la cosecha se perdió entera.
+pub(crate) fn validate_actual_quantity(quantity: Decimal) -> Result<(), AppError> {
+    if quantity.is_zero() {
+        return Ok(());
+    }
+    validate_quantity(quantity)
+        .map_err(|_| AppError::BadRequest("actual_quantity must be zero or positive with at most 3 decimal places".into()))
+}
+
+fn validate_loss(request: &RecordLossRequest, today: NaiveDate) -> Result<NaiveDate, AppError> {
+    validate_quantity(request.quantity)?;
+    let occurred_on = request.occurred_on.unwrap_or(today);
+    if occurred_on > today {
+        return Err(AppError::BadRequest("occurred_on cannot be in the future".into()));
+    }
+    Ok(occurred_on)
+}
+
+/// Pérdidas que se descuentan de lo que tiene el lote. Antes de registrar la cosecha,
+/// todas; después, solo las del día de la cosecha en adelante, porque las anteriores
+/// ya quedaron fuera de lo cosechado.
+fn counted_losses(
+    actual: Option<Decimal>,
+    harvested_on: Option<NaiveDate>,
+    losses: &[(NaiveDate, Decimal)],
+) -> Decimal {
+    losses
+        .iter()
+        .filter(|(occurred_on, _)| actual.is_none() || harvested_on.map_or(true, |day| *occurred_on >= day))
+        .map(|(_, quantity)| *quantity)
+        .sum()
+}
+
+/// Lo que tiene el lote: lo cosechado si ya se registró o, si no, lo estimado, menos
+/// las pérdidas que cuentan. Los traspasos y envíos se descuentan aparte.
+fn on_hand(
+    estimated: Decimal,
+    actual: Option<Decimal>,
+    harvested_on: Option<NaiveDate>,
+    losses: &[(NaiveDate, Decimal)],
+) -> Decimal {
+    actual.unwrap_or(estimated) - counted_losses(actual, harvested_on, losses)
+}
+
+/// Las pérdidas que cuentan, incluida la nueva, no pueden superar lo cosechado (o lo
+/// estimado, mientras no haya cosecha).
+fn validate_loss_total(
+    estimated: Decimal,
+    actual: Option<Decimal>,
+    harvested_on: Option<NaiveDate>,
+    losses: &[(NaiveDate, Decimal)],
+) -> Result<(), AppError> {
+    let base = actual.unwrap_or(estimated);
+    let total = counted_losses(actual, harvested_on, losses);
+    if total > base {
+        return Err(AppError::BadRequest(format!(
+            "Losses would total {} but the lot has {}",
+            total, base
+        )));
+    }
+    Ok(())
+}
+
+/// Lote cosechado tal como entra en la conciliación.
+#[derive(Debug, Clone)]
+struct HarvestedLot {
+    id: Uuid,
+    producer_id: Uuid,
+    producer_name: String,
+    crop_type: CropType,
+    product_name: String,
+    unit_of_measure: String,
+    estimated_quantity: Decimal,
+    actual_quantity: Decimal,
+}
+
+/// Agrupa los lotes en kg con los pesos de envase de su productor. Los lotes cuya
+/// unidad no se puede pasar a masa no suman, ni tampoco sus pérdidas.
+fn reconcile(
+    harvested: &[HarvestedLot],
+    losses: &[(Uuid, LossReason, Decimal)],
+    weights: &HashMap<Uuid, PackagingWeights>,
+    group_by: ReconciliationGroup,
+    unit: Unit,
+) -> ReconciliationReport {
+    let no_weights = PackagingWeights::default();
+    let mut losses_by_lot: HashMap<Uuid, Vec<(LossReason, Decimal)>> = HashMap::new();
+    for (lot_id, reason, quantity) in losses {
+        losses_by_lot.entry(*lot_id).or_default().push((*reason, *quantity));
+    }
+
+    // Ordenadas por cultivo o por nombre del productor
+    let mut groups: BTreeMap<(String, Option<Uuid>), (&HarvestedLot, YieldTotals)> = BTreeMap::new();
+    let mut unconverted_lots = 0;
+
+    for lot in harvested {
+        let weights = weights.get(&lot.producer_id).unwrap_or(&no_weights);
+        let to_kg = |amount: Decimal| -> Option<Decimal> {
+            let unit = lot.unit_of_measure.parse::<Unit>().ok()?;
+            Quantity::new(amount, unit).to_kg(&lot.product_name, weights).ok()
+        };
+        let (Some(estimated_kg), Some(actual_kg)) =
+            (to_kg(lot.estimated_quantity), to_kg(lot.actual_quantity))
+        else {
+            unconverted_lots += 1;
+            continue;
+        };
+
+        let key = match group_by {
+            ReconciliationGroup::Crop => (lot.crop_type.to_str().to_string(), None),
+            ReconciliationGroup::Producer => (lot.producer_name.clone(), Some(lot.producer_id)),
+        };
+        let (_, totals) = groups.entry(key).or_insert_with(|| (lot, YieldTotals::default()));
+        totals.add_lots(1, estimated_kg, actual_kg);
+        for (reason, quantity) in losses_by_lot.get(&lot.id).into_iter().flatten() {
+            // Misma unidad que lo estimado, así que también se puede convertir
+            totals.add_loss(*reason, to_kg(*quantity).unwrap_or_default());
+        }
+    }
+
+    let mut total = YieldTotals::default();
+    let lines = groups
+        .into_values()
+        .map(|(lot, totals)| {
+            total.merge(&totals);
+            let by_producer = group_by == ReconciliationGroup::Producer;
+            ReconciliationLine {
+                crop_type: (!by_producer).then_some(lot.crop_type),
+                producer_id: by_producer.then_some(lot.producer_id),
+                producer_name: by_producer.then(|| lot.producer_name.clone()),
+                report: totals.report(unit),
+            }
+        })
+        .collect();
+
+    ReconciliationReport {
+        group_by,
+        lines,
+        total: total.report(unit),
+        unconverted_lots,
+    }
+}
+
+impl Lot {
+    pub fn find_losses(conn: &mut PgConnection, lot_id: Uuid) -> Result<Vec<LotLoss>, AppError> {
+        lot_losses::table
+            .filter(lot_losses::lot_id.eq(lot_id))
+            .order((lot_losses::occurred_on, lot_losses::created_at))
+            .select(LotLossRow::as_select())
+            .load(conn)?
+            .into_iter()
+            .map(LotLoss::try_from)
+            .collect()
+    }
+
+    /// Estimado, cosechado y pérdidas del lote, en su unidad.
+    fn actual_quantity(conn: &mut PgConnection, lot_id: Uuid) -> QueryResult<Option<Decimal>> {
+        lots::table.find(lot_id).select(lots::actual_quantity).first(conn)
+    }
+
+    fn loss_amounts(conn: &mut PgConnection, lot_id: Uuid) -> QueryResult<Vec<(NaiveDate, Decimal)>> {
+        lot_losses::table
+            .filter(lot_losses::lot_id.eq(lot_id))
+            .select((lot_losses::occurred_on, lot_losses::quantity))
+            .load(conn)
+    }
+
+    /// Lo que tiene el lote antes de traspasos y envíos (ver `on_hand`).
+    pub(crate) fn on_hand(conn: &mut PgConnection, lot: &Lot) -> QueryResult<Decimal> {
+        let actual = Lot::actual_quantity(conn, lot.id)?;
+        let losses = Lot::loss_amounts(conn, lot.id)?;
+        Ok(on_hand(lot.estimated_quantity, actual, lot.actual_harvest_date, &losses))
+    }
+
+    pub fn harvest(conn: &mut PgConnection, lot: &Lot) -> Result<LotHarvest, AppError> {
+        let actual_quantity = Lot::actual_quantity(conn, lot.id)?;
+        let losses = Lot::find_losses(conn, lot.id)?;
+
+        Ok(LotHarvest {
+            lot_id: lot.id,
+            unit_of_measure: lot.unit_of_measure.clone(),
+            estimated_quantity: lot.estimated_quantity,
+            actual_quantity,
+            total_losses: losses.iter().map(|loss| loss.quantity).sum(),
+            variance: actual_quantity.map(|actual| actual - lot.estimated_quantity),
+            losses,
+        })
+    }
+
+    /// Registra o corrige la cantidad cosechada de un lote ya cosechado.
+    pub fn record_harvest(
+        conn: &mut PgConnection,
+        lot_id: Uuid,
+        request: &RecordHarvestRequest,
+        actor_id: Uuid,
+    ) -> Result<LotHarvest, AppError> {
+        validate_actual_quantity(request.actual_quantity)?;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let lot = lock_lots(conn, &[lot_id])?
+                .pop()
+                .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+            if !matches!(lot.current_status, LotStatus::Harvested | LotStatus::Sold) {
+                return Err(AppError::BadRequest(format!(
+                    "Only HARVESTED or SOLD lots have an actual quantity, lot is {}",
+                    lot.current_status
+                )));
+            }
+
+            let previous: Option<Decimal> = lots::table
+                .find(lot.id)
+                .select(lots::actual_quantity)
+                .first(conn)?;
+            let before = Lot::snapshot(conn, lot.id)?;
+            diesel::update(lots::table.find(lot.id))
+                .set(lots::actual_quantity.eq(request.actual_quantity))
+                .execute(conn)?;
+            Lot::record_version(conn, before, actor_id)?;
+
+            record_event(
+                conn,
+                lot.id,
+                EventType::LotUpdated,
+                format!(
+                    "Actual harvest recorded: {} {} (estimated {})",
+                    request.actual_quantity, lot.unit_of_measure, lot.estimated_quantity
+                ),
+                serde_json::json!({
+                    "harvest": {
+                        "actual_quantity": request.actual_quantity,
+                        "previous_actual_quantity": previous,
+                        "estimated_quantity": lot.estimated_quantity,
+                    },
+                    "actor_id": actor_id,
+                }),
+            )?;
+
+            Lot::harvest(conn, &lot)
+        })
+    }
+
+    /// Registra una pérdida del lote y la deja como evento en su cadena. Se admite
+    /// en cualquier estado salvo CANCELLED: hay pérdidas en campo antes de cosechar y
+    /// rechazos después de vender.
+    pub fn record_loss(
+        conn: &mut PgConnection,
+        lot_id: Uuid,
+        request: &RecordLossRequest,
+        actor_id: Uuid,
+    ) -> Result<LotLoss, AppError> {
+        let occurred_on = validate_loss(request, Utc::now().date_naive())?;
+        let notes = request.notes.as_deref().map(str::trim).filter(|n| !n.is_empty());
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let lot = lock_lots(conn, &[lot_id])?
+                .pop()
+                .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+            if lot.current_status == LotStatus::Cancelled {
+                return Err(AppError::BadRequest("Cannot record losses on a CANCELLED lot".into()));
+            }
+
+            let actual = Lot::actual_quantity(conn, lot.id)?;
+            let mut losses = Lot::loss_amounts(conn, lot.id)?;
+            losses.push((occurred_on, request.quantity));
+            validate_loss_total(lot.estimated_quantity, actual, lot.actual_harvest_date, &losses)?;
+
+            let loss: LotLoss = diesel::insert_into(lot_losses::table)
+                .values((
+                    lot_losses::lot_id.eq(lot.id),
+                    lot_losses::reason.eq(request.reason.to_str()),
+                    lot_losses::quantity.eq(request.quantity),
+                    lot_losses::notes.eq(notes),
+                    lot_losses::occurred_on.eq(occurred_on),
+                    lot_losses::created_by.eq(actor_id),
+                ))
+                .returning(LotLossRow::as_returning())
+                .get_result(conn)?
+                .try_into()?;
+
+            record_event(
+                conn,
+                lot.id,
+                EventType::LotUpdated,
+                format!(
+                    "Loss recorded: {} {} ({})",
+                    loss.quantity, lot.unit_of_measure, loss.reason
+                ),
+                serde_json::json!({
+                    "loss": {
+                        "id": loss.id,
+                        "reason": loss.reason,
+                        "quantity": loss.quantity,
+                        "occurred_on": loss.occurred_on,
+                    },
+                    "actor_id": actor_id,
+                }),
+            )?;
+
+            Ok(loss)
+        })
+    }
+
+    /// Conciliación de los lotes con cosecha registrada, de un productor o de todos.
+    pub fn reconciliation(
+        conn: &mut PgConnection,
+        producer_id: Option<Uuid>,
+        group_by: ReconciliationGroup,
+        unit: Unit,
+    ) -> Result<ReconciliationReport, AppError> {
+        let mut query = lots::table
+            .inner_join(producers::table)
+            .filter(lots::actual_quantity.is_not_null())
+            .filter(lots::deleted_at.is_null())
+            .into_boxed();
+        if let Some(producer_id) = producer_id {
+            query = query.filter(lots::producer_id.eq(producer_id));
+        }
+
+        let rows: Vec<(Uuid, Uuid, String, Option<String>, DbCropType, String, String, Decimal, Option<Decimal>)> =
+            query
+                .select((
+                    lots::id,
+                    lots::producer_id,
+                    producers::full_name,
+                    producers::farm_name,
+                    lots::crop_type,
+                    lots::product_name,
+                    lots::unit_of_measure,
+                    lots::estimated_quantity,
+                    lots::actual_quantity,
+                ))
+                .load(conn)?;
+
+        let harvested: Vec<HarvestedLot> = rows
+            .into_iter()
+            .map(|(id, producer_id, full_name, farm_name, crop_type, product_name, unit_of_measure, estimated, actual)| {
+                HarvestedLot {
+                    id,
+                    producer_id,
+                    producer_name: farm_name.unwrap_or(full_name),
+                    crop_type: crop_type.0,
+                    product_name,
+                    unit_of_measure,
+                    estimated_quantity: estimated,
+                    actual_quantity: actual.unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let ids: Vec<Uuid> = harvested.iter().map(|lot| lot.id).collect();
+        let losses = lot_losses::table
+            .filter(lot_losses::lot_id.eq_any(&ids))
+            .select((lot_losses::lot_id, lot_losses::reason, lot_losses::quantity))
+            .load::<(Uuid, String, Decimal)>(conn)?
+            .into_iter()
+            .map(|(lot_id, reason, quantity)| {
+                let reason = reason.parse().map_err(AppError::InternalServerError)?;
+                Ok((lot_id, reason, quantity))
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        let mut weights = HashMap::new();
+        for lot in &harvested {
+            if !weights.contains_key(&lot.producer_id) {
+                weights.insert(lot.producer_id, PackagingWeight::weights(conn, lot.producer_id)?);
+            }
+        }
+
+        Ok(reconcile(&harvested, &losses, &weights, group_by, unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lot(producer_id: Uuid, crop_type: CropType, unit: &str, estimated: i64, actual: i64) -> HarvestedLot {
+        HarvestedLot {
+            id: Uuid::new_v4(),
+            producer_id,
+            producer_name: format!("Finca {}", producer_id.simple()),
+            crop_type,
+            product_name: "Papa".into(),
+            unit_of_measure: unit.into(),
+            estimated_quantity: Decimal::new(estimated, 0),
+            actual_quantity: Decimal::new(actual, 0),
+        }
+    }
+
+    #[test]
+    fn lots_are_grouped_by_crop_in_kg() {
+        let producer = Uuid::new_v4();
+        let mut producer_weights = PackagingWeights::default();
+        producer_weights.insert("papa", Unit::Sack, Decimal::new(50, 0));
+        let weights = HashMap::from([(producer, producer_weights)]);
+
+        let organic = lot(producer, CropType::OrganicCertified, "sack", 20, 18);
+        let harvested = vec![
+            lot(producer, CropType::Conventional, "kg", 1_000, 1_100),
+            organic.clone(),
+            lot(producer, CropType::Conventional, "unit", 500, 400),
+        ];
+        let losses = vec![(organic.id, LossReason::Pests, Decimal::new(2, 0))];
+
+        let report = reconcile(&harvested, &losses, &weights, ReconciliationGroup::Crop, Unit::Kg);
+
+        assert_eq!(report.unconverted_lots, 1);
+        assert_eq!(report.lines.len(), 2);
+        assert_eq!(report.lines[0].crop_type, Some(CropType::Conventional));
+        assert_eq!(report.lines[0].report.variance, Decimal::new(100, 0));
+        assert_eq!(report.lines[1].report.estimated, Decimal::new(1_000, 0));
+        assert_eq!(report.lines[1].report.losses, Decimal::new(100, 0));
+        assert_eq!(report.total.lots, 2);
+        assert_eq!(report.total.actual, Decimal::new(2_000, 0));
+    }
+
+    #[test]
+    fn producer_lines_carry_the_producer() {
+        let producer = Uuid::new_v4();
+        let harvested = vec![lot(producer, CropType::Hydroponic, "ton", 2, 1)];
+
+        let report = reconcile(&harvested, &[], &HashMap::new(), ReconciliationGroup::Producer, Unit::Ton);
+
+        assert_eq!(report.lines[0].producer_id, Some(producer));
+        assert!(report.lines[0].crop_type.is_none());
+        assert_eq!(report.lines[0].report.variance_pct, Some(Decimal::new(-50, 0)));
+    }
+
+    #[test]
+    fn a_total_loss_is_a_valid_harvest() {
+        assert!(validate_actual_quantity(Decimal::ZERO).is_ok());
+        assert!(validate_actual_quantity(Decimal::new(-1, 0)).is_err());
+        assert!(validate_actual_quantity(Decimal::new(1, 4)).is_err());
+    }
+
+    #[test]
+    fn losses_cannot_be_dated_in_the_future() {
+        let today = NaiveDate::from_ymd_opt(2025, 7, 25).unwrap();
+        let mut request = RecordLossRequest {
+            reason: LossReason::Weather,
+            quantity: Decimal::new(5, 0),
+            notes: None,
+            occurred_on: None,
+        };
+        assert_eq!(validate_loss(&request, today).unwrap(), today);
+
+        request.occurred_on = today.succ_opt();
+        assert!(validate_loss(&request, today).is_err());
+    }
+
+    #[test]
+    fn losses_cannot_exceed_the_estimate() {
+        let estimated = Decimal::new(100, 0);
+        let day = NaiveDate::from_ymd_opt(2025, 7, 20).unwrap();
+        let losses = |last: i64| [(day, Decimal::new(60, 0)), (day, Decimal::new(last, 0))];
+        assert!(validate_loss_total(estimated, None, None, &losses(40)).is_ok());
+        assert!(validate_loss_total(estimated, None, None, &losses(41)).is_err());
+    }
+
+    #[test]
+    fn losses_after_a_short_harvest_cannot_exceed_what_was_harvested() {
+        let estimated = Decimal::new(100, 0);
+        let actual = Some(Decimal::new(70, 0));
+        let harvested_on = NaiveDate::from_ymd_opt(2025, 7, 20);
+        let before = NaiveDate::from_ymd_opt(2025, 7, 10).unwrap();
+        let after = NaiveDate::from_ymd_opt(2025, 7, 22).unwrap();
+
+        // La pérdida en campo ya está fuera de los 70 cosechados
+        let losses = [(before, Decimal::new(30, 0)), (after, Decimal::new(70, 0))];
+        assert!(validate_loss_total(estimated, actual, harvested_on, &losses).is_ok());
+        let losses = [(before, Decimal::new(30, 0)), (after, Decimal::new(71, 0))];
+        assert!(validate_loss_total(estimated, actual, harvested_on, &losses).is_err());
+    }
+
+    #[test]
+    fn what_is_on_hand_starts_from_the_harvest_once_recorded() {
+        let estimated = Decimal::new(100, 0);
+        let harvested_on = NaiveDate::from_ymd_opt(2025, 7, 20);
+        let before = NaiveDate::from_ymd_opt(2025, 7, 10).unwrap();
+        let after = NaiveDate::from_ymd_opt(2025, 7, 22).unwrap();
+        let losses = [(before, Decimal::new(30, 0)), (after, Decimal::new(10, 0))];
+
+        assert_eq!(on_hand(estimated, None, None, &losses), Decimal::new(60, 0));
+        assert_eq!(
+            on_hand(estimated, Some(Decimal::new(70, 0)), harvested_on, &losses),
+            Decimal::new(60, 0)
+        );
+        assert_eq!(
+            on_hand(estimated, Some(Decimal::new(50, 0)), harvested_on, &losses),
+            Decimal::new(40, 0)
+        );
+        assert_eq!(on_hand(estimated, Some(Decimal::ZERO), harvested_on, &[]), Decimal::ZERO);
+    }
+}
@@ -0,0 +1,481 @@
+//! Alta masiva de lotes desde un CSV exportado de una hoja de cálculo.
+//!
+//! Cada fila se valida con las mismas reglas que los CHECK de `lots`, de modo que
+//! un CSV que pasa el `dry_run` se importa sin sorpresas de la base de datos.
+
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, Utc};
+use diesel::prelude::*;
+use kairos_common::{
+    geo, CropType, EventType, ImportedLot, LotImportError, LotImportResult, LotStatus, Point,
+    UNITS_OF_MEASURE,
+};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        lot::Lot,
+        lot_genealogy::{generate_lot_code, record_event},
+        sql_enums::{DbCropType, DbLotStatus, DbPoint},
+    },
+    schema::lots,
+};
+
+/// Filas de datos admitidas por fichero.
+pub const MAX_IMPORT_ROWS: usize = 1000;
+/// Tamaño máximo del CSV subido.
+pub const MAX_IMPORT_BYTES: usize = 2 * 1024 * 1024;
+
+const REQUIRED_COLUMNS: [&str; 5] = [
+    "product_name",
+    "crop_type",
+    "estimated_quantity",
+    "unit_of_measure",
+    "estimated_harvest_date",
+];
+const OPTIONAL_COLUMNS: [&str; 3] = ["additional_description", "latitude", "longitude"];
+
+/// `NUMERIC(12,3)`: nueve dígitos enteros.
+const MAX_QUANTITY: Decimal = Decimal::from_parts(1_000_000_000, 0, 0, false, 0);
+
+/// Fila ya validada, lista para insertar.
+#[derive(Debug)]
+struct LotRow {
+    row: usize,
+    product_name: String,
+    crop_type: CropType,
+    estimated_quantity: Decimal,
+    unit_of_measure: String,
+    estimated_harvest_date: NaiveDate,
+    additional_description: Option<String>,
+    location_coordinates: Option<Point>,
+}
+
+/// Excel en español exporta con `;`; se elige el separador que aparezca en la cabecera.
+fn delimiter(data: &[u8]) -> u8 {
+    let header = data.split(|b| *b == b'\n').next().unwrap_or_default();
+    let count = |d: u8| header.iter().filter(|b| **b == d).count();
+    if count(b';') > count(b',') {
+        b';'
+    } else {
+        b','
+    }
+}
+
+fn columns(headers: &csv::StringRecord) -> Result<HashMap<&'static str, usize>, String> {
+    let mut columns = HashMap::new();
+    for (index, header) in headers.iter().enumerate() {
+        let name = header.trim().to_lowercase();
+        let known = REQUIRED_COLUMNS
+            .iter()
+            .chain(OPTIONAL_COLUMNS.iter())
+            .find(|column| **column == name)
+            .ok_or_else(|| format!("unknown column '{}'", header.trim()))?;
+        if columns.insert(*known, index).is_some() {
+            return Err(format!("duplicate column '{}'", known));
+        }
+    }
+
+    let missing: Vec<_> = REQUIRED_COLUMNS
+        .iter()
+        .filter(|column| !columns.contains_key(*column))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("missing required columns: {}", missing.join(", ")));
+    }
+    Ok(columns)
+}
+
+fn parse_crop_type(value: &str) -> Result<CropType, String> {
+    serde_json::from_value(Value::String(value.to_uppercase())).map_err(|_| {
+        "must be one of CONVENTIONAL, AGROECOLOGICAL_UNCERTIFIED, ORGANIC_CERTIFIED, HYDROPONIC"
+            .to_string()
+    })
+}
+
+/// Admite coma decimal (`12,5`) siempre que no haya también punto.
+fn parse_quantity(value: &str) -> Result<Decimal, String> {
+    let normalized = if value.contains('.') {
+        value.to_string()
+    } else {
+        value.replace(',', ".")
+    };
+    let quantity: Decimal = normalized
+        .parse()
+        .map_err(|_| "must be a number".to_string())?;
+
+    if quantity <= Decimal::ZERO {
+        return Err("must be greater than zero".into());
+    }
+    if quantity.round_dp(3) != quantity {
+        return Err("supports at most 3 decimal places".into());
+    }
+    if quantity >= MAX_QUANTITY {
+        return Err("must be less than 1000000000".into());
+    }
+    Ok(quantity)
+}
+
+fn parse_unit(value: &str) -> Result<String, String> {
+    let unit = value.to_lowercase();
+    if !UNITS_OF_MEASURE.contains(&unit.as_str()) {
+        return Err(format!("must be one of {}", UNITS_OF_MEASURE.join(", ")));
+    }
+    Ok(unit)
+}
+
+fn parse_harvest_date(value: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| "must be a date in YYYY-MM-DD format".to_string())?;
+    if date < today {
+        return Err("must not be in the past".into());
+    }
+    Ok(date)
+}
+
+fn parse_coordinate(value: &str) -> Result<f64, String> {
+    value
+        .replace(',', ".")
+        .parse()
+        .map_err(|_| "must be a number".to_string())
+}
+
+/// Acumula el problema de una columna y sigue validando el resto de la fila.
+fn check<T>(
+    errors: &mut Vec<LotImportError>,
+    row: usize,
+    column: &str,
+    result: Result<T, String>,
+) -> Option<T> {
+    result
+        .map_err(|message| {
+            errors.push(LotImportError {
+                row,
+                column: Some(column.to_string()),
+                message,
+            })
+        })
+        .ok()
+}
+
+/// Valida una fila y devuelve todos sus problemas, no solo el primero.
+fn parse_row(
+    row: usize,
+    record: &csv::StringRecord,
+    columns: &HashMap<&'static str, usize>,
+    today: NaiveDate,
+) -> Result<LotRow, Vec<LotImportError>> {
+    let mut errors = Vec::new();
+    let field = |name: &str| {
+        columns
+            .get(name)
+            .and_then(|index| record.get(*index))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    let required = |name: &str| field(name).ok_or_else(|| "is required".to_string());
+
+    let product_name = check(
+        &mut errors,
+        row,
+        "product_name",
+        required("product_name").and_then(|name| match name.chars().count() {
+            0..=1 => Err("must be at least 2 characters long".to_string()),
+            _ => Ok(name.to_string()),
+        }),
+    );
+    let crop_type = check(
+        &mut errors,
+        row,
+        "crop_type",
+        required("crop_type").and_then(parse_crop_type),
+    );
+    let estimated_quantity = check(
+        &mut errors,
+        row,
+        "estimated_quantity",
+        required("estimated_quantity").and_then(parse_quantity),
+    );
+    let unit_of_measure = check(
+        &mut errors,
+        row,
+        "unit_of_measure",
+        required("unit_of_measure").and_then(parse_unit),
+    );
+    let estimated_harvest_date = check(
+        &mut errors,
+        row,
+        "estimated_harvest_date",
+        required("estimated_harvest_date").and_then(|value| parse_harvest_date(value, today)),
+    );
+    let latitude = check(
+        &mut errors,
+        row,
+        "latitude",
+        field("latitude").map(parse_coordinate).transpose(),
+    );
+    let longitude = check(
+        &mut errors,
+        row,
+        "longitude",
+        field("longitude").map(parse_coordinate).transpose(),
+    );
+    let location_coordinates = match (latitude, longitude) {
+        (Some(Some(y)), Some(Some(x))) => check(
+            &mut errors,
+            row,
+            "latitude",
+            Some(Point { x, y })
+                .filter(|p| geo::is_valid(*p))
+                .map(Some)
+                .ok_or_else(|| "latitude/longitude are not a valid WGS84 position".to_string()),
+        ),
+        (Some(None), Some(None)) => Some(None),
+        (Some(_), Some(_)) => check(
+            &mut errors,
+            row,
+            "latitude",
+            Err("latitude and longitude must be given together".to_string()),
+        ),
+        _ => None,
+    };
+
+    match (
+        product_name,
+        crop_type,
+        estimated_quantity,
+        unit_of_measure,
+        estimated_harvest_date,
+        location_coordinates,
+    ) {
+        (
+            Some(product_name),
+            Some(crop_type),
+            Some(estimated_quantity),
+            Some(unit_of_measure),
+            Some(estimated_harvest_date),
+            Some(location_coordinates),
+        ) if errors.is_empty() => Ok(LotRow {
+            row,
+            product_name,
+            crop_type,
+            estimated_quantity,
+            unit_of_measure,
+            estimated_harvest_date,
+            additional_description: field("additional_description").map(str::to_string),
+            location_coordinates,
+        }),
+        _ => Err(errors),
+    }
+}
+
+/// Filas del CSV, una entrada por fila de datos. Los problemas del fichero en sí
+/// (cabecera, tamaño, codificación) se devuelven como `Err`.
+fn parse(
+    data: &[u8],
+    today: NaiveDate,
+) -> Result<Vec<Result<LotRow, Vec<LotImportError>>>, String> {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter(data))
+        .flexible(true)
+        .from_reader(data);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("invalid CSV header: {}", e))?
+        .clone();
+    let columns = columns(&headers)?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("invalid CSV: {}", e))?;
+        // Las hojas de cálculo suelen dejar filas vacías al final
+        if record.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        if rows.len() == MAX_IMPORT_ROWS {
+            return Err(format!(
+                "at most {} rows can be imported at once",
+                MAX_IMPORT_ROWS
+            ));
+        }
+
+        let row = record
+            .position()
+            .map_or(rows.len() + 2, |p| p.line() as usize);
+        if record.len() != headers.len() {
+            rows.push(Err(vec![LotImportError {
+                row,
+                column: None,
+                message: format!("expected {} fields, found {}", headers.len(), record.len()),
+            }]));
+            continue;
+        }
+        rows.push(parse_row(row, &record, &columns, today));
+    }
+
+    if rows.is_empty() {
+        return Err("the CSV has no rows".into());
+    }
+    Ok(rows)
+}
+
+fn insert(conn: &mut PgConnection, producer_id: Uuid, row: &LotRow) -> QueryResult<Lot> {
+    let lot_code: String = diesel::select(generate_lot_code()).get_result(conn)?;
+
+    diesel::insert_into(lots::table)
+        .values((
+            lots::producer_id.eq(producer_id),
+            lots::lot_code.eq(lot_code),
+            lots::product_name.eq(&row.product_name),
+            lots::crop_type.eq(DbCropType(row.crop_type)),
+            lots::estimated_quantity.eq(row.estimated_quantity),
+            lots::unit_of_measure.eq(&row.unit_of_measure),
+            lots::estimated_harvest_date.eq(row.estimated_harvest_date),
+            lots::current_status.eq(DbLotStatus(LotStatus::Registered)),
+            lots::additional_description.eq(&row.additional_description),
+            lots::location_coordinates.eq(row.location_coordinates.map(DbPoint)),
+        ))
+        .returning(Lot::as_returning())
+        .get_result(conn)
+}
+
+impl Lot {
+    /// Valida el CSV y, si no es `dry_run` y todas las filas son válidas, crea los
+    /// lotes en una sola transacción. Con alguna fila inválida no se crea ninguno.
+    pub fn import_csv(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        data: &[u8],
+        dry_run: bool,
+        actor_id: Uuid,
+    ) -> Result<LotImportResult, AppError> {
+        let rows = parse(data, Utc::now().date_naive()).map_err(AppError::BadRequest)?;
+
+        let mut result = LotImportResult {
+            dry_run,
+            total_rows: rows.len(),
+            ..Default::default()
+        };
+        let mut valid = Vec::new();
+        for row in rows {
+            match row {
+                Ok(row) => valid.push(row),
+                Err(errors) => result.errors.extend(errors),
+            }
+        }
+        result.valid_rows = valid.len();
+        if dry_run || !result.errors.is_empty() {
+            return Ok(result);
+        }
+
+        result.created = conn.transaction::<_, AppError, _>(|conn| {
+            valid
+                .iter()
+                .map(|row| {
+                    let lot = insert(conn, producer_id, row)?;
+                    record_event(
+                        conn,
+                        lot.id,
+                        EventType::LotRegistered,
+                        format!("Lot registered from CSV import (row {})", row.row),
+                        json!({ "import": { "row": row.row }, "actor_id": actor_id }),
+                    )?;
+                    Ok(ImportedLot {
+                        row: row.row,
+                        lot_id: lot.id,
+                        lot_code: lot.lot_code,
+                    })
+                })
+                .collect()
+        })?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 7, 24).unwrap()
+    }
+
+    fn errors(rows: &[Result<LotRow, Vec<LotImportError>>]) -> Vec<(usize, Option<&str>)> {
+        rows.iter()
+            .filter_map(|row| row.as_ref().err())
+            .flatten()
+            .map(|e| (e.row, e.column.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_rows_follow_the_lot_constraints() {
+        let csv = "\u{feff}Product_Name;crop_type;estimated_quantity;unit_of_measure;estimated_harvest_date;latitude;longitude\n\
+                   Tomate pera;organic_certified;1250,5;KG;2025-09-01;40.4168;-3.7038\n\
+                   Lechuga;HYDROPONIC;300;unit;2025-07-24;;\n\
+                   ;;;;;;\n";
+        let rows = parse(csv.as_bytes(), today()).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        let tomato = rows[0].as_ref().unwrap();
+        assert_eq!(tomato.row, 2);
+        assert_eq!(tomato.crop_type, CropType::OrganicCertified);
+        assert_eq!(tomato.estimated_quantity, Decimal::new(12505, 1));
+        assert_eq!(tomato.unit_of_measure, "kg");
+        assert_eq!(
+            tomato.location_coordinates,
+            Some(Point {
+                x: -3.7038,
+                y: 40.4168
+            })
+        );
+        assert_eq!(rows[1].as_ref().unwrap().location_coordinates, None);
+    }
+
+    #[test]
+    fn every_problem_is_reported_with_its_row_and_column() {
+        let csv = "product_name,crop_type,estimated_quantity,unit_of_measure,estimated_harvest_date,latitude,longitude\n\
+                   X,ORGANIC,0,litre,2025-07-23,40.4,\n\
+                   Maíz,CONVENTIONAL,1.2345,kg,24/09/2025,95,10\n\
+                   Trigo,CONVENTIONAL,10,kg\n";
+        let rows = parse(csv.as_bytes(), today()).unwrap();
+
+        assert_eq!(
+            errors(&rows),
+            vec![
+                (2, Some("product_name")),
+                (2, Some("crop_type")),
+                (2, Some("estimated_quantity")),
+                (2, Some("unit_of_measure")),
+                (2, Some("estimated_harvest_date")),
+                (2, Some("latitude")),
+                (3, Some("estimated_quantity")),
+                (3, Some("estimated_harvest_date")),
+                (3, Some("latitude")),
+                (4, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn file_level_problems_reject_the_whole_upload() {
+        assert!(parse(b"product_name,crop_type\nTomate,CONVENTIONAL\n", today()).is_err());
+        assert!(parse(
+            b"product_name,crop_type,estimated_quantity,unit_of_measure,estimated_harvest_date,color\n",
+            today()
+        )
+        .is_err());
+        assert!(parse(
+            b"product_name,crop_type,estimated_quantity,unit_of_measure,estimated_harvest_date\n",
+            today()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use kairos_common::{ApiError, EventType, LotStatus, LotTransitionRequest};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        event_chain::seal_pending,
+        lot::Lot,
+        lot_genealogy::lock_lots,
+        lot_harvest::validate_actual_quantity,
+        sql_enums::{DbEventType, DbLotStatus},
+    },
+    schema::{events, lots},
+};
+
+impl Lot {
+    /// Aplica un cambio de estado validado contra la tabla de transiciones de
+    /// `LotStatus` y deja constancia en `events`, todo en una transacción.
+    pub fn transition(
+        conn: &mut PgConnection,
+        lot: &Lot,
+        request: &LotTransitionRequest,
+        actor_id: Uuid,
+    ) -> Result<Lot, AppError> {
+        let from = lot.current_status;
+        let to = request.to;
+
+        if !from.can_transition_to(to) {
+            return Err(AppError::Conflict(ApiError {
+                code: "INVALID_LOT_TRANSITION".into(),
+                message: format!("Lot cannot move from {} to {}", from, to),
+                details: Some(serde_json::json!({
+                    "from": from,
+                    "to": to,
+                    "allowed": from.allowed_transitions(),
+                })),
+            }));
+        }
+
+        let harvest_date = if to == LotStatus::Harvested {
+            let date = request
+                .actual_harvest_date
+                .unwrap_or_else(|| Utc::now().date_naive());
+            if date > Utc::now().date_naive() {
+                return Err(AppError::BadRequest("actual_harvest_date cannot be in the future".into()));
+            }
+            if date < lot.estimated_harvest_date {
+                return Err(AppError::BadRequest(
+                    "actual_harvest_date cannot be before estimated_harvest_date".into(),
+                ));
+            }
+            Some(date)
+        } else {
+            None
+        };
+
+        match request.actual_quantity {
+            Some(quantity) if to == LotStatus::Harvested => validate_actual_quantity(quantity)?,
+            Some(_) => {
+                return Err(AppError::BadRequest(
+                    "actual_quantity can only be set when moving to HARVESTED".into(),
+                ))
+            }
+            None => {}
+        }
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            // Bloqueada para que la versión guarde solo este cambio; el filtro por
+            // estado detecta los que llegaron antes que el bloqueo
+            lock_lots(conn, &[lot.id])?;
+            let before = Lot::snapshot(conn, lot.id)?;
+            let target = lots::table
+                .filter(lots::id.eq(lot.id))
+                .filter(lots::current_status.eq(DbLotStatus(from)));
+
+            let updated = match harvest_date {
+                Some(date) => diesel::update(target)
+                    .set((
+                        lots::current_status.eq(DbLotStatus(to)),
+                        lots::actual_harvest_date.eq(date),
+                        lots::actual_quantity.eq(request.actual_quantity),
+                    ))
+                    .execute(conn)?,
+                None => diesel::update(target)
+                    .set(lots::current_status.eq(DbLotStatus(to)))
+                    .execute(conn)?,
+            };
+
+            if updated == 0 {
+                return Err(AppError::Conflict(ApiError {
+                    code: "LOT_STATUS_CHANGED".into(),
+                    message: "Lot status was changed by another request, reload and retry".into(),
+                    details: None,
+                }));
+            }
+
+            let event_type = if to == LotStatus::Harvested {
+                EventType::HarvestCompleted
+            } else {
+                EventType::LotUpdated
+            };
+
+            diesel::insert_into(events::table)
+                .values((
+                    events::lot_id.eq(lot.id),
+                    events::event_type.eq(DbEventType(event_type)),
+                    events::description.eq(format!("Status changed from {} to {}", from, to)),
+                    events::metadata.eq(serde_json::json!({
+                        "transition": { "from": from, "to": to },
+                        "reason": request.reason,
+                        "actual_harvest_date": harvest_date,
+                        "actual_quantity": request.actual_quantity,
+                        "actor_id": actor_id,
+                    })),
+                ))
+                .execute(conn)?;
+            seal_pending(conn, lot.id)?;
+            Lot::record_version(conn, before, actor_id)?;
+
+            Lot::find_by_id(conn, lot.id)
+        })
+    }
+}
@@ -0,0 +1,140 @@
+//! Totales de cantidad de los lotes normalizados a una unidad de masa, con los pesos
+//! de envase del productor (ver `kairos_common::units`).
+
+use std::collections::BTreeMap;
+
+use diesel::dsl::{count_star, sum};
+use diesel::prelude::*;
+use kairos_common::{
+    units::{
+        LotQuantitySummary, PackagingWeights, ProductQuantitySummary, Quantity, QuantityAggregator,
+        Unit,
+    },
+    LotQuery,
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        lot::Lot,
+        lot_query::{filtered, validate_harvest_window},
+        packaging_weight::PackagingWeight,
+    },
+    schema::lots,
+};
+
+/// Suma de un producto en una unidad: `(product_name, unit, cantidad, lotes)`.
+type QuantityRow = (String, Unit, Decimal, i64);
+
+/// Agrupa por producto sin distinguir mayúsculas, como los pesos de envase; el
+/// nombre mostrado es el primero en orden alfabético.
+fn summarize(rows: &[QuantityRow], target: Unit, weights: &PackagingWeights) -> LotQuantitySummary {
+    let mut overall = QuantityAggregator::new(target, weights);
+    let mut products: BTreeMap<String, (&str, i64, QuantityAggregator)> = BTreeMap::new();
+    let mut lots = 0;
+
+    for (product_name, unit, amount, count) in rows {
+        let quantity = Quantity::new(*amount, *unit);
+        overall.add(product_name, quantity);
+        lots += count;
+
+        let product = products
+            .entry(product_name.trim().to_lowercase())
+            .or_insert_with(|| {
+                (
+                    product_name.trim(),
+                    0,
+                    QuantityAggregator::new(target, weights),
+                )
+            });
+        product.1 += count;
+        product.2.add(product_name, quantity);
+    }
+
+    LotQuantitySummary {
+        lots,
+        totals: overall.finish(),
+        products: products
+            .into_values()
+            .map(|(product_name, lots, totals)| ProductQuantitySummary {
+                product_name: product_name.to_string(),
+                lots,
+                totals: totals.finish(),
+            })
+            .collect(),
+    }
+}
+
+impl Lot {
+    /// Totales de los lotes que cumplen los filtros de `LotQuery` (sin paginar),
+    /// en `target`, por producto y en conjunto.
+    pub fn quantity_summary(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        query: &LotQuery,
+        target: Unit,
+    ) -> Result<LotQuantitySummary, AppError> {
+        validate_harvest_window(query)?;
+
+        // Las consultas boxed no admiten GROUP BY; los filtros van en una subconsulta
+        let ids = filtered(Some(producer_id), query).select(lots::id);
+        let rows: Vec<(String, String, Option<Decimal>, i64)> = lots::table
+            .filter(lots::id.eq_any(ids))
+            .group_by((lots::product_name, lots::unit_of_measure))
+            .order((lots::product_name, lots::unit_of_measure))
+            .select((
+                lots::product_name,
+                lots::unit_of_measure,
+                sum(lots::estimated_quantity),
+                count_star(),
+            ))
+            .load(conn)?;
+
+        let rows = rows
+            .into_iter()
+            .map(|(product_name, unit, amount, count)| {
+                let unit = unit.parse().map_err(AppError::InternalServerError)?;
+                Ok((product_name, unit, amount.unwrap_or_default(), count))
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        let weights = PackagingWeight::weights(conn, producer_id)?;
+        Ok(summarize(&rows, target, &weights))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn products_are_grouped_ignoring_case() {
+        let mut weights = PackagingWeights::default();
+        weights.insert("papa", Unit::Sack, Decimal::new(50, 0));
+        let rows = vec![
+            ("Papa".to_string(), Unit::Kg, Decimal::new(300, 0), 2),
+            ("Papa".to_string(), Unit::Sack, Decimal::new(10, 0), 1),
+            ("Tomate".to_string(), Unit::Box, Decimal::new(4, 0), 1),
+            ("papa ".to_string(), Unit::Ton, Decimal::new(1, 0), 1),
+        ];
+
+        let summary = summarize(&rows, Unit::Ton, &weights);
+
+        assert_eq!(summary.lots, 5);
+        assert_eq!(
+            summary.totals.total,
+            Quantity::new(Decimal::new(18, 1), Unit::Ton)
+        );
+        assert_eq!(summary.totals.unconverted.len(), 1);
+        assert_eq!(summary.products.len(), 2);
+        assert_eq!(summary.products[0].product_name, "Papa");
+        assert_eq!(summary.products[0].lots, 4);
+        assert!(summary.products[0].totals.unconverted.is_empty());
+        assert_eq!(
+            summary.products[1].totals.unconverted[0].product_name,
+            "Tomate"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Date, Float4, Nullable, Text};
+use kairos_common::LotQuery;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        lot::Lot,
+        pagination::{is_descending, Page},
+        sql_enums::{DbCropType, DbLotStatus},
+    },
+    schema::lots,
+};
+
+/// Columnas por las que se puede ordenar; cualquier otro valor de `sort_by` es un 400.
+pub const SORTABLE_COLUMNS: &[&str] = &[
+    "created_at",
+    "updated_at",
+    "product_name",
+    "lot_code",
+    "estimated_quantity",
+    "estimated_harvest_date",
+    "current_status",
+    "relevance",
+];
+
+// Operador de similitud de pg_trgm; junto con ILIKE lo resuelve idx_lots_product_name_gin
+diesel::infix_operator!(TrigramSimilar, " % ", backend: Pg);
+
+diesel::define_sql_function! {
+    fn similarity(a: Text, b: Text) -> Float4;
+}
+
+diesel::define_sql_function! {
+    pub(crate) fn coalesce(actual: Nullable<Date>, estimated: Date) -> Date;
+}
+
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Aplica los filtros de `LotQuery`; sin productor, abarca los lotes de todos.
+pub(crate) fn filtered<'a>(
+    producer_id: Option<Uuid>,
+    query: &LotQuery,
+) -> lots::BoxedQuery<'a, Pg> {
+    let mut q = lots::table.into_boxed();
+
+    if let Some(producer_id) = producer_id {
+        q = q.filter(lots::producer_id.eq(producer_id));
+    }
+    if !query.include_deleted {
+        q = q.filter(lots::deleted_at.is_null());
+    }
+    if !query.include_archived {
+        q = q.filter(lots::archived_at.is_null());
+    }
+
+    if let Some(term) = query
+        .product_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        let pattern = format!("%{}%", escape_like(term));
+        q = q.filter(lots::product_name.ilike(pattern).or(TrigramSimilar::new(
+            lots::product_name,
+            term.to_string().into_sql::<Text>(),
+        )));
+    }
+    if let Some(status) = query.status {
+        q = q.filter(lots::current_status.eq(DbLotStatus(status)));
+    }
+    if let Some(crop_type) = query.crop_type {
+        q = q.filter(lots::crop_type.eq(DbCropType(crop_type)));
+    }
+    // La fecha real manda; la estimada solo cuenta mientras no se haya cosechado
+    if let Some(from) = query.harvest_from {
+        q = q.filter(coalesce(lots::actual_harvest_date, lots::estimated_harvest_date).ge(from));
+    }
+    if let Some(to) = query.harvest_to {
+        q = q.filter(coalesce(lots::actual_harvest_date, lots::estimated_harvest_date).le(to));
+    }
+
+    q
+}
+
+pub(crate) fn validate_harvest_window(query: &LotQuery) -> Result<(), AppError> {
+    match (query.harvest_from, query.harvest_to) {
+        (Some(from), Some(to)) if from > to => Err(AppError::BadRequest(
+            "harvest_from must not be after harvest_to".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl Lot {
+    /// Lista los lotes de un productor aplicando todos los filtros de `LotQuery`.
+    /// Devuelve la página pedida y el total de coincidencias.
+    pub fn search(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        query: &LotQuery,
+    ) -> Result<(Vec<Lot>, i64), AppError> {
+        let sort_by = query.sort_by.as_deref().unwrap_or("created_at");
+        if !SORTABLE_COLUMNS.contains(&sort_by) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid sort_by '{}', expected one of: {}",
+                sort_by,
+                SORTABLE_COLUMNS.join(", ")
+            )));
+        }
+        let descending = is_descending(query.sort_order.as_deref())?;
+        validate_harvest_window(query)?;
+        let page = Page::new(query.page, query.per_page);
+
+        let total = filtered(Some(producer_id), query)
+            .count()
+            .get_result(conn)?;
+
+        let q = filtered(Some(producer_id), query);
+        let q = match (sort_by, descending) {
+            ("updated_at", true) => q.order(lots::updated_at.desc()),
+            ("updated_at", false) => q.order(lots::updated_at.asc()),
+            ("product_name", true) => q.order(lots::product_name.desc()),
+            ("product_name", false) => q.order(lots::product_name.asc()),
+            ("lot_code", true) => q.order(lots::lot_code.desc()),
+            ("lot_code", false) => q.order(lots::lot_code.asc()),
+            ("estimated_quantity", true) => q.order(lots::estimated_quantity.desc()),
+            ("estimated_quantity", false) => q.order(lots::estimated_quantity.asc()),
+            ("estimated_harvest_date", true) => q.order(lots::estimated_harvest_date.desc()),
+            ("estimated_harvest_date", false) => q.order(lots::estimated_harvest_date.asc()),
+            ("current_status", true) => q.order(lots::current_status.desc()),
+            ("current_status", false) => q.order(lots::current_status.asc()),
+            ("relevance", desc) => match query.product_name.as_deref() {
+                Some(term) if desc => {
+                    q.order(similarity(lots::product_name, term.to_string()).desc())
+                }
+                Some(term) => q.order(similarity(lots::product_name, term.to_string()).asc()),
+                None => {
+                    return Err(AppError::BadRequest(
+                        "sort_by=relevance requires product_name".into(),
+                    ))
+                }
+            },
+            (_, true) => q.order(lots::created_at.desc()),
+            (_, false) => q.order(lots::created_at.asc()),
+        };
+
+        let lots = q
+            .then_order_by(lots::id)
+            .offset(page.offset())
+            .limit(page.per_page)
+            .select(Lot::as_select())
+            .load(conn)?;
+
+        Ok((lots, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    }
+}
@@ -0,0 +1,276 @@
+//! Versiones de un lote guardadas en cada cambio (ver `kairos_common::history`).
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::max;
+use diesel::prelude::*;
+use kairos_common::history::{self, LotAsOf, LotVersion, LotVersionDiff, VersionDiffQuery};
+use rust_decimal::Decimal;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::lot::Lot,
+    schema::{lot_versions, lots},
+};
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = lot_versions)]
+struct LotVersionRow {
+    lot_id: Uuid,
+    version: i32,
+    snapshot: serde_json::Value,
+    changed_fields: Vec<String>,
+    actor_id: Option<Uuid>,
+    recorded_at: DateTime<Utc>,
+}
+
+impl From<LotVersionRow> for LotVersion {
+    fn from(row: LotVersionRow) -> Self {
+        LotVersion {
+            lot_id: row.lot_id,
+            version: row.version,
+            snapshot: row.snapshot,
+            changed_fields: row.changed_fields,
+            actor_id: row.actor_id,
+            recorded_at: row.recorded_at,
+        }
+    }
+}
+
+/// Foto del lote tomada antes de un cambio, para compararla con la de después.
+pub(crate) struct LotSnapshot {
+    lot_id: Uuid,
+    value: serde_json::Value,
+    updated_at: DateTime<Utc>,
+}
+
+/// El lote tal como lo devuelve la API más las columnas que no están en `Lot`, para
+/// que cosecha, parcela, archivo y borrado también queden en el historial.
+fn snapshot(
+    lot: &Lot,
+    plot_id: Option<Uuid>,
+    actual_quantity: Option<Decimal>,
+    archived_at: Option<DateTime<Utc>>,
+    deleted_at: Option<DateTime<Utc>>,
+) -> Result<serde_json::Value, AppError> {
+    let mut value = serde_json::to_value(lot).map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert("plot_id".into(), json!(plot_id));
+        fields.insert("actual_quantity".into(), json!(actual_quantity));
+        fields.insert("archived_at".into(), json!(archived_at));
+        fields.insert("deleted_at".into(), json!(deleted_at));
+    }
+    Ok(value)
+}
+
+/// Versiones a guardar por un cambio de `before` a `after`: ninguna si no cambió
+/// nada; si el lote nunca se había editado, antes la versión 1 con `before`.
+fn next_versions(
+    last: Option<i32>,
+    before: LotSnapshot,
+    after: LotSnapshot,
+    actor_id: Uuid,
+) -> Vec<LotVersion> {
+    let changed_fields: Vec<String> = history::diff(&before.value, &after.value)
+        .into_iter()
+        .map(|change| change.field)
+        .collect();
+    if changed_fields.is_empty() {
+        return Vec::new();
+    }
+
+    let mut versions = Vec::new();
+    let next = match last {
+        Some(last) => last + 1,
+        None => {
+            versions.push(LotVersion {
+                lot_id: before.lot_id,
+                version: 1,
+                snapshot: before.value,
+                changed_fields: Vec::new(),
+                actor_id: None,
+                recorded_at: before.updated_at,
+            });
+            2
+        }
+    };
+    versions.push(LotVersion {
+        lot_id: after.lot_id,
+        version: next,
+        snapshot: after.value,
+        changed_fields,
+        actor_id: Some(actor_id),
+        recorded_at: after.updated_at,
+    });
+    versions
+}
+
+fn insert(conn: &mut PgConnection, version: LotVersion) -> QueryResult<LotVersion> {
+    diesel::insert_into(lot_versions::table)
+        .values((
+            lot_versions::lot_id.eq(version.lot_id),
+            lot_versions::version.eq(version.version),
+            lot_versions::snapshot.eq(version.snapshot),
+            lot_versions::changed_fields.eq(version.changed_fields),
+            lot_versions::actor_id.eq(version.actor_id),
+            lot_versions::recorded_at.eq(version.recorded_at),
+        ))
+        .returning(LotVersionRow::as_returning())
+        .get_result(conn)
+        .map(LotVersion::from)
+}
+
+impl Lot {
+    /// Foto actual del lote. Quien escribe en `lots` la toma antes del cambio y se
+    /// la pasa después a `record_version`.
+    pub(crate) fn snapshot(conn: &mut PgConnection, lot_id: Uuid) -> Result<LotSnapshot, AppError> {
+        let (lot, plot_id, actual_quantity, archived_at, deleted_at) = lots::table
+            .find(lot_id)
+            .select((
+                Lot::as_select(),
+                lots::plot_id,
+                lots::actual_quantity,
+                lots::archived_at,
+                lots::deleted_at,
+            ))
+            .first::<(Lot, Option<Uuid>, Option<Decimal>, Option<DateTime<Utc>>, Option<DateTime<Utc>>)>(conn)?;
+
+        Ok(LotSnapshot {
+            lot_id,
+            value: snapshot(&lot, plot_id, actual_quantity, archived_at, deleted_at)?,
+            updated_at: lot.updated_at,
+        })
+    }
+
+    /// Guarda la versión que deja un cambio en el lote respecto a `before`. Se llama
+    /// en la misma transacción que el cambio y con la fila bloqueada, desde todo lo
+    /// que escribe en `lots`: edición, estado, cosecha, parcela, archivo y borrado.
+    pub(crate) fn record_version(
+        conn: &mut PgConnection,
+        before: LotSnapshot,
+        actor_id: Uuid,
+    ) -> Result<Option<LotVersion>, AppError> {
+        let after = Lot::snapshot(conn, before.lot_id)?;
+        let last: Option<i32> = lot_versions::table
+            .filter(lot_versions::lot_id.eq(before.lot_id))
+            .select(max(lot_versions::version))
+            .get_result(conn)?;
+
+        let mut recorded = None;
+        for version in next_versions(last, before, after, actor_id) {
+            recorded = Some(insert(conn, version)?);
+        }
+        Ok(recorded)
+    }
+
+    pub fn versions(conn: &mut PgConnection, lot_id: Uuid) -> QueryResult<Vec<LotVersion>> {
+        lot_versions::table
+            .filter(lot_versions::lot_id.eq(lot_id))
+            .order(lot_versions::version)
+            .select(LotVersionRow::as_select())
+            .load(conn)
+            .map(|rows| rows.into_iter().map(LotVersion::from).collect())
+    }
+
+    pub fn version_diff(
+        conn: &mut PgConnection,
+        lot_id: Uuid,
+        query: VersionDiffQuery,
+    ) -> Result<LotVersionDiff, AppError> {
+        let versions = Lot::versions(conn, lot_id)?;
+        let find = |number: i32| {
+            versions
+                .iter()
+                .find(|v| v.version == number)
+                .ok_or_else(|| AppError::NotFound(format!("Lot version {} not found", number)))
+        };
+        let from = find(query.from)?;
+        let to = find(query.to)?;
+
+        Ok(LotVersionDiff {
+            lot_id,
+            from: from.version,
+            to: to.version,
+            changes: history::diff(&from.snapshot, &to.snapshot),
+        })
+    }
+
+    /// El lote tal como estaba en `at`. Un lote sin versiones no ha cambiado desde
+    /// que se creó.
+    pub fn as_of(conn: &mut PgConnection, lot: &Lot, at: DateTime<Utc>) -> Result<LotAsOf, AppError> {
+        if at < lot.created_at {
+            return Err(AppError::NotFound(format!("Lot did not exist at {}", at)));
+        }
+
+        let versions = Lot::versions(conn, lot.id)?;
+        let (version, snapshot) = match history::version_at(&versions, at) {
+            Some(version) => (Some(version.version), version.snapshot.clone()),
+            None => (None, Lot::snapshot(conn, lot.id)?.value),
+        };
+
+        Ok(LotAsOf {
+            lot_id: lot.id,
+            at,
+            version,
+            lot: snapshot,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn snapshot_at(lot_id: Uuid, status: &str, actual_quantity: Option<&str>, updated_at: DateTime<Utc>) -> LotSnapshot {
+        LotSnapshot {
+            lot_id,
+            value: json!({
+                "id": lot_id,
+                "current_status": status,
+                "actual_quantity": actual_quantity,
+                "updated_at": updated_at,
+            }),
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn a_transition_creates_a_version_visible_as_of_its_time() {
+        let lot_id = Uuid::new_v4();
+        let actor = Uuid::new_v4();
+        let created = Utc::now() - Duration::days(30);
+        let harvested = Utc::now() - Duration::days(1);
+
+        let versions = next_versions(
+            None,
+            snapshot_at(lot_id, "READY_FOR_HARVEST", None, created),
+            snapshot_at(lot_id, "HARVESTED", Some("480.000"), harvested),
+            actor,
+        );
+
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[1].changed_fields, ["actual_quantity", "current_status"]);
+        assert_eq!(versions[1].actor_id, Some(actor));
+
+        let before = history::version_at(&versions, harvested - Duration::hours(1)).unwrap();
+        assert_eq!(before.snapshot["current_status"], "READY_FOR_HARVEST");
+        let after = history::version_at(&versions, Utc::now()).unwrap();
+        assert_eq!(after.version, 2);
+        assert_eq!(after.snapshot["current_status"], "HARVESTED");
+    }
+
+    #[test]
+    fn a_write_that_changes_nothing_creates_no_version() {
+        let lot_id = Uuid::new_v4();
+        let now = Utc::now();
+        let versions = next_versions(
+            Some(3),
+            snapshot_at(lot_id, "GROWING", None, now - Duration::hours(1)),
+            snapshot_at(lot_id, "GROWING", None, now),
+            Uuid::new_v4(),
+        );
+        assert!(versions.is_empty());
+    }
+}
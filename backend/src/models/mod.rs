@@ -0,0 +1,31 @@
+pub mod account_token;
+pub mod certificate;
+pub mod epcis_exchange;
+pub mod event;
+pub mod event_chain;
+pub mod event_edit;
+pub mod event_query;
+pub mod lot;
+pub mod lot_archive;
+pub mod lot_export;
+pub mod lot_genealogy;
+pub mod lot_geo;
+pub mod lot_harvest;
+pub mod lot_import;
+pub mod lot_lifecycle;
+pub mod lot_query;
+pub mod lot_quantity;
+pub mod lot_version;
+pub mod notification;
+pub mod packaging_weight;
+pub mod pagination;
+pub mod plot;
+pub mod producer;
+pub mod producer_review;
+pub mod public_lot;
+pub mod recall;
+pub mod refresh_token;
+pub mod revoked_session;
+pub mod role;
+pub mod shipment;
+pub mod sql_enums;
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::schema::notifications;
+
+pub const PRODUCER_APPROVED: &str = "PRODUCER_APPROVED";
+pub const PRODUCER_REJECTED: &str = "PRODUCER_REJECTED";
+pub const RECALL_ISSUED: &str = "RECALL_ISSUED";
+
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = notifications)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub title: String,
+    pub message: String,
+    pub notification_type: String,
+    pub is_read: bool,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = notifications)]
+pub struct NewNotification {
+    pub user_id: Uuid,
+    pub title: String,
+    pub message: String,
+    pub notification_type: String,
+    pub is_read: bool,
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl Notification {
+    pub fn create(conn: &mut PgConnection, new_notification: NewNotification) -> QueryResult<Self> {
+        diesel::insert_into(notifications::table)
+            .values(&new_notification)
+            .returning(Notification::as_returning())
+            .get_result(conn)
+    }
+
+    pub fn find_by_user(conn: &mut PgConnection, user_id: Uuid) -> QueryResult<Vec<Self>> {
+        notifications::table
+            .filter(notifications::user_id.eq(user_id))
+            .order(notifications::created_at.desc())
+            .select(Notification::as_select())
+            .load(conn)
+    }
+
+    pub fn mark_all_as_read(conn: &mut PgConnection, user_id: Uuid) -> QueryResult<usize> {
+        diesel::update(
+            notifications::table
+                .filter(notifications::user_id.eq(user_id))
+                .filter(notifications::is_read.eq(false)),
+        )
+        .set(notifications::is_read.eq(true))
+        .execute(conn)
+    }
+}
@@ -0,0 +1,163 @@
+//! Kilos por caja o saco de cada producto, por productor (ver `kairos_common::units`).
+
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use kairos_common::units::{self, PackagingWeights, SetPackagingWeightRequest, Unit, QUANTITY_SCALE};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{errors::AppError, schema::packaging_weights};
+
+diesel::define_sql_function! {
+    fn lower(value: Text) -> Text;
+}
+
+/// Fila de `packaging_weights`; la API devuelve `units::PackagingWeight`.
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = packaging_weights)]
+pub struct PackagingWeight {
+    pub id: Uuid,
+    pub producer_id: Uuid,
+    pub product_name: String,
+    pub unit: String,
+    pub kg_per_unit: Decimal,
+}
+
+impl TryFrom<PackagingWeight> for units::PackagingWeight {
+    type Error = AppError;
+
+    fn try_from(row: PackagingWeight) -> Result<Self, Self::Error> {
+        Ok(units::PackagingWeight {
+            id: row.id,
+            producer_id: row.producer_id,
+            product_name: row.product_name,
+            unit: row.unit.parse().map_err(AppError::InternalServerError)?,
+            kg_per_unit: row.kg_per_unit,
+        })
+    }
+}
+
+/// Devuelve el nombre del producto recortado.
+fn validate(request: &SetPackagingWeightRequest) -> Result<&str, String> {
+    let product_name = request.product_name.trim();
+    if product_name.chars().count() < 2 {
+        return Err("product_name must be at least 2 characters long".into());
+    }
+    if !request.unit.is_packaging() {
+        return Err(format!(
+            "unit must be box or sack, {} needs no weight",
+            request.unit
+        ));
+    }
+    if request.kg_per_unit <= Decimal::ZERO {
+        return Err("kg_per_unit must be greater than zero".into());
+    }
+    if request.kg_per_unit.round_dp(QUANTITY_SCALE) != request.kg_per_unit {
+        return Err("kg_per_unit supports at most 3 decimal places".into());
+    }
+    Ok(product_name)
+}
+
+impl PackagingWeight {
+    pub fn find_by_producer(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+    ) -> Result<Vec<units::PackagingWeight>, AppError> {
+        packaging_weights::table
+            .filter(packaging_weights::producer_id.eq(producer_id))
+            .order((packaging_weights::product_name, packaging_weights::unit))
+            .select(PackagingWeight::as_select())
+            .load(conn)?
+            .into_iter()
+            .map(units::PackagingWeight::try_from)
+            .collect()
+    }
+
+    pub fn find_by_id(conn: &mut PgConnection, id: Uuid) -> Result<units::PackagingWeight, AppError> {
+        packaging_weights::table
+            .find(id)
+            .select(PackagingWeight::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound("Packaging weight not found".into()))?
+            .try_into()
+    }
+
+    /// Pesos del productor listos para convertir cantidades.
+    pub fn weights(conn: &mut PgConnection, producer_id: Uuid) -> Result<PackagingWeights, AppError> {
+        let mut weights = PackagingWeights::default();
+        for weight in PackagingWeight::find_by_producer(conn, producer_id)? {
+            weights.insert(&weight.product_name, weight.unit, weight.kg_per_unit);
+        }
+        Ok(weights)
+    }
+
+    /// Crea el peso del envase del producto o reemplaza el que ya hubiera.
+    pub fn set(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        request: &SetPackagingWeightRequest,
+    ) -> Result<units::PackagingWeight, AppError> {
+        let product_name = validate(request).map_err(AppError::BadRequest)?;
+        let unit = request.unit.to_str();
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let existing = packaging_weights::table
+                .filter(packaging_weights::producer_id.eq(producer_id))
+                .filter(lower(packaging_weights::product_name).eq(product_name.to_lowercase()))
+                .filter(packaging_weights::unit.eq(unit))
+                .select(packaging_weights::id)
+                .first::<Uuid>(conn)
+                .optional()?;
+
+            let row = match existing {
+                Some(id) => diesel::update(packaging_weights::table.find(id))
+                    .set((
+                        packaging_weights::product_name.eq(product_name),
+                        packaging_weights::kg_per_unit.eq(request.kg_per_unit),
+                    ))
+                    .returning(PackagingWeight::as_returning())
+                    .get_result(conn)?,
+                None => diesel::insert_into(packaging_weights::table)
+                    .values((
+                        packaging_weights::producer_id.eq(producer_id),
+                        packaging_weights::product_name.eq(product_name),
+                        packaging_weights::unit.eq(unit),
+                        packaging_weights::kg_per_unit.eq(request.kg_per_unit),
+                    ))
+                    .returning(PackagingWeight::as_returning())
+                    .get_result(conn)?,
+            };
+            row.try_into()
+        })
+    }
+
+    pub fn delete(conn: &mut PgConnection, id: Uuid) -> QueryResult<usize> {
+        diesel::delete(packaging_weights::table.find(id)).execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(product_name: &str, unit: Unit, kg_per_unit: Decimal) -> SetPackagingWeightRequest {
+        SetPackagingWeightRequest {
+            product_name: product_name.into(),
+            unit,
+            kg_per_unit,
+        }
+    }
+
+    #[test]
+    fn only_packaging_units_take_a_weight() {
+        let box_weight = request("  Tomate pera ", Unit::Box, Decimal::new(125, 1));
+        assert_eq!(validate(&box_weight), Ok("Tomate pera"));
+
+        assert!(validate(&request("Papa", Unit::Kg, Decimal::ONE)).is_err());
+        assert!(validate(&request("Papa", Unit::Unit, Decimal::ONE)).is_err());
+        assert!(validate(&request("Papa", Unit::Sack, Decimal::ZERO)).is_err());
+        assert!(validate(&request("Papa", Unit::Sack, Decimal::new(1, 4))).is_err());
+        assert!(validate(&request(" P ", Unit::Sack, Decimal::ONE)).is_err());
+    }
+}
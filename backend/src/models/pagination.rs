@@ -0,0 +1,73 @@
+use crate::errors::AppError;
+
+pub const DEFAULT_PER_PAGE: i64 = 10;
+pub const MAX_PER_PAGE: i64 = 100;
+
+/// Página y tamaño de página normalizados.
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl Page {
+    pub fn new(page: Option<i64>, per_page: Option<i64>) -> Self {
+        Self {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+
+    pub fn meta(&self, total: i64) -> serde_json::Value {
+        serde_json::json!({
+            "page": self.page,
+            "per_page": self.per_page,
+            "total": total,
+            "total_pages": (total + self.per_page - 1) / self.per_page,
+        })
+    }
+}
+
+/// `sort_order` admite `asc` o `desc` (por defecto, lo más reciente primero).
+pub fn is_descending(sort_order: Option<&str>) -> Result<bool, AppError> {
+    match sort_order.map(str::to_lowercase).as_deref() {
+        None | Some("desc") => Ok(true),
+        Some("asc") => Ok(false),
+        Some(other) => Err(AppError::BadRequest(format!(
+            "Invalid sort_order '{}', expected 'asc' or 'desc'",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_is_clamped_to_valid_bounds() {
+        let page = Page::new(Some(0), Some(1000));
+        assert_eq!((page.page, page.per_page), (1, MAX_PER_PAGE));
+        assert_eq!(Page::new(None, None).per_page, DEFAULT_PER_PAGE);
+        assert_eq!(Page::new(Some(3), Some(20)).offset(), 40);
+    }
+
+    #[test]
+    fn total_pages_rounds_up() {
+        let page = Page::new(Some(1), Some(10));
+        assert_eq!(page.meta(0)["total_pages"], 0);
+        assert_eq!(page.meta(10)["total_pages"], 1);
+        assert_eq!(page.meta(11)["total_pages"], 2);
+    }
+
+    #[test]
+    fn sort_order_defaults_to_descending() {
+        assert!(is_descending(None).unwrap());
+        assert!(!is_descending(Some("ASC")).unwrap());
+        assert!(is_descending(Some("sideways")).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! Parcelas con contorno poligonal (ver `kairos_common::plot`).
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use kairos_common::{
+    plot::{
+        CreatePlotRequest, PlotImportResult, PlotSummary, Polygon, SkippedPlotFeature,
+        UpdatePlotRequest,
+    },
+    EventType,
+};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        lot::Lot,
+        lot_genealogy::{lock_lots, record_event},
+        sql_enums::DbPoint,
+    },
+    schema::{lots, plots},
+};
+
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = plots)]
+pub struct Plot {
+    pub id: Uuid,
+    pub producer_id: Uuid,
+    pub name: String,
+    /// `Polygon` GeoJSON normalizado.
+    pub boundary: Value,
+    pub area_hectares: Decimal,
+    pub centroid: DbPoint,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Contorno ya validado con los valores derivados que se guardan junto a él.
+struct Boundary {
+    geojson: Value,
+    area_hectares: Decimal,
+    centroid: DbPoint,
+}
+
+fn parse_boundary(value: &Value) -> Result<Boundary, String> {
+    let polygon = Polygon::from_geojson(value).map_err(|e| e.to_string())?;
+    let area_hectares = Decimal::from_f64(polygon.area_hectares())
+        .map(|area| area.round_dp(4))
+        .filter(|area| !area.is_zero())
+        .ok_or_else(|| "polygon is smaller than 1 m²".to_string())?;
+
+    Ok(Boundary {
+        geojson: polygon.to_geojson(),
+        area_hectares,
+        centroid: DbPoint(polygon.centroid()),
+    })
+}
+
+fn validate_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.chars().count() < 2 {
+        return Err("name must be at least 2 characters long".into());
+    }
+    Ok(name)
+}
+
+impl Plot {
+    pub fn create(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        request: &CreatePlotRequest,
+    ) -> Result<Plot, AppError> {
+        let name = validate_name(&request.name).map_err(AppError::BadRequest)?;
+        let boundary = parse_boundary(&request.boundary).map_err(AppError::BadRequest)?;
+
+        Ok(insert(conn, producer_id, name, &boundary)?)
+    }
+
+    pub fn find_by_id(conn: &mut PgConnection, id: Uuid) -> Result<Plot, AppError> {
+        plots::table
+            .find(id)
+            .select(Plot::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound("Plot not found".into()))
+    }
+
+    pub fn find_by_producer(conn: &mut PgConnection, producer_id: Uuid) -> QueryResult<Vec<Plot>> {
+        plots::table
+            .filter(plots::producer_id.eq(producer_id))
+            .order((plots::name, plots::id))
+            .select(Plot::as_select())
+            .load(conn)
+    }
+
+    /// Parcela asignada al lote, si tiene.
+    pub fn find_by_lot(conn: &mut PgConnection, lot_id: Uuid) -> QueryResult<Option<Plot>> {
+        lots::table
+            .inner_join(plots::table)
+            .filter(lots::id.eq(lot_id))
+            .select(Plot::as_select())
+            .first(conn)
+            .optional()
+    }
+
+    /// Cambiar el contorno mueve también el punto de los lotes de la parcela a su
+    /// nuevo centroide, con una versión nueva de cada lote.
+    pub fn update(
+        conn: &mut PgConnection,
+        plot: &Plot,
+        request: &UpdatePlotRequest,
+        actor_id: Uuid,
+    ) -> Result<Plot, AppError> {
+        let name = match &request.name {
+            Some(name) => validate_name(name).map_err(AppError::BadRequest)?,
+            None => plot.name.as_str(),
+        };
+        let boundary = request
+            .boundary
+            .as_ref()
+            .map(parse_boundary)
+            .transpose()
+            .map_err(AppError::BadRequest)?;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let target = diesel::update(plots::table.find(plot.id));
+            let updated = match &boundary {
+                Some(boundary) => {
+                    let updated = target
+                        .set((
+                            plots::name.eq(name),
+                            plots::boundary.eq(&boundary.geojson),
+                            plots::area_hectares.eq(boundary.area_hectares),
+                            plots::centroid.eq(boundary.centroid),
+                        ))
+                        .returning(Plot::as_returning())
+                        .get_result(conn)?;
+                    let lot_ids: Vec<Uuid> = lots::table
+                        .filter(lots::plot_id.eq(plot.id))
+                        .select(lots::id)
+                        .load(conn)?;
+                    for lot in lock_lots(conn, &lot_ids)? {
+                        let before = Lot::snapshot(conn, lot.id)?;
+                        diesel::update(lots::table.find(lot.id))
+                            .set(lots::location_coordinates.eq(Some(boundary.centroid)))
+                            .execute(conn)?;
+                        Lot::record_version(conn, before, actor_id)?;
+                    }
+                    updated
+                }
+                None => target
+                    .set(plots::name.eq(name))
+                    .returning(Plot::as_returning())
+                    .get_result(conn)?,
+            };
+            Ok(updated)
+        })
+    }
+
+    /// Los lotes de la parcela quedan sin parcela pero conservan su último centroide.
+    pub fn delete(conn: &mut PgConnection, id: Uuid) -> QueryResult<usize> {
+        diesel::delete(plots::table.find(id)).execute(conn)
+    }
+
+    /// Crea una parcela por cada `Feature` de una `FeatureCollection`, con el nombre
+    /// en `properties.name`. Los que no son válidos se omiten indicando el motivo.
+    pub fn import(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        document: &Value,
+    ) -> Result<PlotImportResult, AppError> {
+        let features = match document.get("type").and_then(Value::as_str) {
+            Some("FeatureCollection") => document
+                .get("features")
+                .and_then(Value::as_array)
+                .ok_or_else(|| AppError::BadRequest("features must be an array".into()))?,
+            _ => {
+                return Err(AppError::BadRequest(
+                    "expected a GeoJSON FeatureCollection".into(),
+                ))
+            }
+        };
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let mut result = PlotImportResult::default();
+
+            for (index, feature) in features.iter().enumerate() {
+                let parsed = feature["properties"]["name"]
+                    .as_str()
+                    .ok_or_else(|| "feature has no properties.name".to_string())
+                    .and_then(validate_name)
+                    .and_then(|name| Ok((name, parse_boundary(feature)?)));
+
+                match parsed {
+                    Ok((name, boundary)) => {
+                        let plot = insert(conn, producer_id, name, &boundary)?;
+                        result.created.push(plot.summary());
+                    }
+                    Err(reason) => result.skipped.push(SkippedPlotFeature { index, reason }),
+                }
+            }
+            Ok(result)
+        })
+    }
+
+    /// Vincula el lote a la parcela (o lo desvincula con `None`) y lo deja en su
+    /// historia. El punto del lote pasa a ser el centroide de la parcela.
+    pub fn assign(
+        conn: &mut PgConnection,
+        lot_id: Uuid,
+        plot: Option<&Plot>,
+        actor_id: Uuid,
+    ) -> Result<(), AppError> {
+        conn.transaction::<_, AppError, _>(|conn| {
+            let lot: Lot = lock_lots(conn, &[lot_id])?
+                .pop()
+                .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+
+            let before = Lot::snapshot(conn, lot.id)?;
+            let (description, metadata) = match plot {
+                Some(plot) => {
+                    diesel::update(lots::table.find(lot.id))
+                        .set((
+                            lots::plot_id.eq(plot.id),
+                            lots::location_coordinates.eq(Some(plot.centroid)),
+                        ))
+                        .execute(conn)?;
+                    (
+                        format!("Lot assigned to plot {} ({} ha)", plot.name, plot.area_hectares),
+                        json!({
+                            "plot": {
+                                "id": plot.id,
+                                "name": plot.name,
+                                "area_hectares": plot.area_hectares,
+                            },
+                            "actor_id": actor_id,
+                        }),
+                    )
+                }
+                None => {
+                    diesel::update(lots::table.find(lot.id))
+                        .set(lots::plot_id.eq(None::<Uuid>))
+                        .execute(conn)?;
+                    (
+                        "Lot removed from its plot".to_string(),
+                        json!({ "plot": null, "actor_id": actor_id }),
+                    )
+                }
+            };
+
+            Lot::record_version(conn, before, actor_id)?;
+            record_event(conn, lot.id, EventType::LotUpdated, description, metadata)?;
+            Ok(())
+        })
+    }
+
+    pub fn summary(&self) -> PlotSummary {
+        PlotSummary {
+            id: self.id,
+            name: self.name.clone(),
+            area_hectares: self.area_hectares,
+            centroid: self.centroid.0,
+            created_at: self.created_at,
+        }
+    }
+
+    pub fn feature(&self) -> Value {
+        json!({
+            "type": "Feature",
+            "id": self.id,
+            "geometry": self.boundary,
+            "properties": {
+                "name": self.name,
+                "area_hectares": self.area_hectares,
+                "centroid": [self.centroid.0.x, self.centroid.0.y],
+            },
+        })
+    }
+}
+
+pub fn feature_collection(plots: &[Plot]) -> Value {
+    json!({
+        "type": "FeatureCollection",
+        "features": plots.iter().map(Plot::feature).collect::<Vec<_>>(),
+    })
+}
+
+fn insert(
+    conn: &mut PgConnection,
+    producer_id: Uuid,
+    name: &str,
+    boundary: &Boundary,
+) -> QueryResult<Plot> {
+    diesel::insert_into(plots::table)
+        .values((
+            plots::producer_id.eq(producer_id),
+            plots::name.eq(name),
+            plots::boundary.eq(&boundary.geojson),
+            plots::area_hectares.eq(boundary.area_hectares),
+            plots::centroid.eq(boundary.centroid),
+        ))
+        .returning(Plot::as_returning())
+        .get_result(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundary_is_normalized_with_area_and_centroid() {
+        // Sentido horario: se guarda invertido según la regla de la mano derecha
+        let boundary = parse_boundary(&json!({
+            "type": "Feature",
+            "properties": { "name": "Lote norte" },
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [[[-3.70, 40.40], [-3.70, 40.41], [-3.69, 40.41], [-3.69, 40.40], [-3.70, 40.40]]],
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(boundary.geojson["type"], "Polygon");
+        assert_eq!(boundary.geojson["coordinates"][0][1], json!([-3.69, 40.40]));
+        assert!(
+            boundary.area_hectares > Decimal::from(90)
+                && boundary.area_hectares < Decimal::from(95)
+        );
+        assert!((boundary.centroid.0.x + 3.695).abs() < 1e-9);
+        assert!((boundary.centroid.0.y - 40.405).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invalid_boundaries_and_names_are_reported() {
+        assert!(parse_boundary(&json!({ "type": "Polygon", "coordinates": [] })).is_err());
+        assert!(validate_name(" x ").is_err());
+        assert_eq!(validate_name(" Lote norte ").unwrap(), "Lote norte");
+    }
+}
@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use kairos_common::ProducerStatus;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::models::sql_enums::DbProducerStatus;
+use crate::schema::{producer_reviews, producers};
+
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = producer_reviews)]
+pub struct ProducerReview {
+    pub id: Uuid,
+    pub producer_id: Uuid,
+    pub reviewed_by: Uuid,
+    pub approved: bool,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = producer_reviews)]
+pub struct NewProducerReview {
+    pub producer_id: Uuid,
+    pub reviewed_by: Uuid,
+    pub approved: bool,
+    pub reason: Option<String>,
+}
+
+/// Vista reducida de un productor en la cola de aprobación.
+#[derive(Debug, Clone, Queryable, Serialize)]
+pub struct PendingProducer {
+    pub id: Uuid,
+    pub full_name: String,
+    pub email: String,
+    pub farm_name: Option<String>,
+    pub phone: Option<String>,
+    pub email_verified: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ProducerReview {
+    /// Registra la decisión y actualiza el estado del productor.
+    pub fn record(conn: &mut PgConnection, review: NewProducerReview) -> QueryResult<Self> {
+        let status = if review.approved {
+            ProducerStatus::Approved
+        } else {
+            ProducerStatus::Rejected
+        };
+
+        diesel::update(producers::table.find(review.producer_id))
+            .set(producers::status.eq(DbProducerStatus(status)))
+            .execute(conn)?;
+
+        diesel::insert_into(producer_reviews::table)
+            .values(&review)
+            .returning(ProducerReview::as_returning())
+            .get_result(conn)
+    }
+
+    pub fn find_by_producer(conn: &mut PgConnection, producer_id: Uuid) -> QueryResult<Vec<Self>> {
+        producer_reviews::table
+            .filter(producer_reviews::producer_id.eq(producer_id))
+            .order(producer_reviews::created_at.desc())
+            .select(ProducerReview::as_select())
+            .load(conn)
+    }
+
+    /// Productores pendientes, los más antiguos primero, junto con el total.
+    pub fn pending(
+        conn: &mut PgConnection,
+        offset: i64,
+        limit: i64,
+    ) -> QueryResult<(Vec<PendingProducer>, i64)> {
+        let pending = producers::status.eq(DbProducerStatus(ProducerStatus::Pending));
+
+        let total = producers::table.filter(pending.clone()).count().get_result(conn)?;
+
+        let producers = producers::table
+            .filter(pending)
+            .order(producers::created_at.asc())
+            .offset(offset)
+            .limit(limit)
+            .select((
+                producers::id,
+                producers::full_name,
+                producers::email,
+                producers::farm_name,
+                producers::phone,
+                producers::email_verified,
+                producers::created_at,
+            ))
+            .load(conn)?;
+
+        Ok((producers, total))
+    }
+}
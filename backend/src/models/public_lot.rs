@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+use diesel::prelude::*;
+use kairos_common::{ProducerStatus, PublicEvent, PublicLotView};
+
+use crate::{
+    errors::AppError,
+    models::{
+        event_chain::{verify_chain, ChainedEvent},
+        event_edit::is_retraction,
+        lot::Lot,
+        sql_enums::DbProducerStatus,
+    },
+    schema::{events, lots, producers},
+};
+
+/// Línea de tiempo pública: las correcciones sustituyen al evento que corrigen
+/// (conservando su fecha original) y los eventos anulados desaparecen.
+pub fn build_timeline(chain: &[ChainedEvent]) -> Vec<PublicEvent> {
+    let superseded: HashSet<_> = chain.iter().filter_map(|e| e.corrects_event_id).collect();
+    let mut occurred_at = HashMap::new();
+
+    let mut timeline = Vec::new();
+    for event in chain {
+        let original_time = event
+            .corrects_event_id
+            .and_then(|id| occurred_at.get(&id).copied())
+            .unwrap_or(event.created_at);
+        occurred_at.insert(event.id, original_time);
+
+        if superseded.contains(&event.id) || is_retraction(event.metadata.as_ref()) {
+            continue;
+        }
+        timeline.push(PublicEvent {
+            event_type: event.event_type.0,
+            description: event.description.clone(),
+            event_location: event.event_location.clone(),
+            occurred_at: original_time,
+            corrected: event.corrects_event_id.is_some(),
+            hash: event.hash.clone(),
+        });
+    }
+
+    timeline.sort_by_key(|e| e.occurred_at);
+    timeline
+}
+
+impl Lot {
+    /// Vista pública por código de lote. Los lotes borrados o de productores no
+    /// aprobados o desactivados no se publican.
+    pub fn find_public(conn: &mut PgConnection, lot_code: &str) -> Result<PublicLotView, AppError> {
+        let (lot, farm_name) = lots::table
+            .inner_join(producers::table)
+            .filter(lots::lot_code.eq(lot_code))
+            .filter(lots::deleted_at.is_null())
+            .filter(producers::status.eq(DbProducerStatus(ProducerStatus::Approved)))
+            .filter(producers::is_active.eq(true))
+            .select((Lot::as_select(), producers::farm_name))
+            .first::<(Lot, Option<String>)>(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+
+        // Primero los sellados en orden de cadena; los pendientes de sellar al final
+        let chain = events::table
+            .filter(events::lot_id.eq(lot.id))
+            .order((
+                events::sequence.asc().nulls_last(),
+                events::created_at.asc(),
+                events::id.asc(),
+            ))
+            .select(ChainedEvent::as_select())
+            .load(conn)?;
+
+        let sealed: Vec<_> = chain
+            .iter()
+            .filter(|e| e.sequence.is_some())
+            .cloned()
+            .collect();
+        let (chain_valid, head_hash) = match verify_chain(&sealed) {
+            Ok(head) => (true, head),
+            Err(_) => (false, None),
+        };
+
+        Ok(PublicLotView {
+            lot_code: lot.lot_code,
+            product_name: lot.product_name,
+            crop_type: lot.crop_type,
+            status: lot.current_status,
+            farm_name,
+            estimated_harvest_date: lot.estimated_harvest_date,
+            actual_harvest_date: lot.actual_harvest_date,
+            timeline: build_timeline(&chain),
+            chain_valid,
+            head_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::sql_enums::DbEventType;
+    use chrono::{Duration, Utc};
+    use kairos_common::EventType;
+    use uuid::Uuid;
+
+    fn event(description: &str, minutes: i64) -> ChainedEvent {
+        ChainedEvent {
+            id: Uuid::new_v4(),
+            lot_id: Uuid::nil(),
+            sequence: None,
+            event_type: DbEventType(EventType::Irrigation),
+            description: Some(description.into()),
+            event_location: None,
+            coordinates: None,
+            metadata: Some(serde_json::json!({ "actor_id": Uuid::new_v4() })),
+            created_at: Utc::now() + Duration::minutes(minutes),
+            corrects_event_id: None,
+            prev_hash: None,
+            hash: None,
+        }
+    }
+
+    #[test]
+    fn corrections_replace_the_original_and_keep_its_date() {
+        let original = event("Riego 100 l", 0);
+        let mut correction = event("Riego 120 l", 30);
+        correction.corrects_event_id = Some(original.id);
+
+        let timeline = build_timeline(&[original.clone(), correction]);
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline[0].description.as_deref(), Some("Riego 120 l"));
+        assert_eq!(timeline[0].occurred_at, original.created_at);
+        assert!(timeline[0].corrected);
+    }
+
+    #[test]
+    fn retracted_events_are_hidden() {
+        let original = event("Riego por error", 0);
+        let mut retraction = event("Riego por error", 5);
+        retraction.corrects_event_id = Some(original.id);
+        retraction.metadata = Some(serde_json::json!({ "correction": { "action": "retract" } }));
+        let kept = event("Riego 80 l", 10);
+
+        let timeline = build_timeline(&[original, retraction, kept]);
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline[0].description.as_deref(), Some("Riego 80 l"));
+    }
+}
@@ -0,0 +1,832 @@
+//! Retiradas de producto: a partir de un criterio se buscan los lotes de partida,
+//! se recorre su genealogía y sus envíos, y se avisa a cada parte afectada.
+//!
+//! Los lotes derivados (`downstream`) se tratan como afectados y se avisa a sus
+//! compradores; los de origen (`upstream`) solo se informan para investigar la
+//! causa, así que se avisa a su productor pero no a quienes compraron de ellos.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Date, Nullable, Text, Uuid as SqlUuid};
+use kairos_common::{
+    AffectedLot, AffectedParty, CreateRecallRequest, GenealogyLink, RecallCriteria,
+    RecallPartyType, RecallReport, TraceDirection,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    mail::{Mailer, OutgoingMail},
+    models::{
+        lot_genealogy::{walk, Direction, MAX_GENEALOGY_DEPTH},
+        notification::{self, NewNotification, Notification},
+        shipment::Shipment,
+    },
+    schema::{lots, producers, recall_notifications, recalls},
+};
+
+pub const PENDING: &str = "pending";
+/// Reclamado por un despachador y en envío.
+pub const SENDING: &str = "sending";
+pub const SENT: &str = "sent";
+pub const FAILED: &str = "failed";
+
+/// Intentos de envío antes de dar un aviso por fallido.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 5;
+/// Tras este tiempo en `sending` se entiende que el despachador murió y el aviso
+/// se puede volver a reclamar.
+pub const CLAIM_TIMEOUT_SECONDS: i64 = 600;
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = recalls)]
+pub struct Recall {
+    pub id: Uuid,
+    pub initiated_by: Uuid,
+    pub reason: String,
+    pub criteria: serde_json::Value,
+    pub report: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = recall_notifications)]
+pub struct RecallNotification {
+    pub id: Uuid,
+    pub recall_id: Uuid,
+    pub party_type: String,
+    pub recipient_name: String,
+    pub recipient_email: Option<String>,
+    pub producer_id: Option<Uuid>,
+    pub subject: String,
+    #[serde(skip)]
+    pub body: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub claimed_at: Option<DateTime<Utc>>,
+}
+
+/// Resultado de una pasada del despachador de avisos.
+#[derive(Debug, Default, Serialize)]
+pub struct DispatchSummary {
+    pub sent: usize,
+    pub retrying: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Queryable)]
+struct TracedLot {
+    id: Uuid,
+    lot_code: String,
+    product_name: String,
+    producer_id: Uuid,
+    producer_name: String,
+    producer_email: String,
+    farm_name: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct IdRow {
+    #[diesel(sql_type = SqlUuid)]
+    id: Uuid,
+}
+
+fn json_error(e: serde_json::Error) -> AppError {
+    AppError::InternalServerError(format!("Invalid recall report: {}", e))
+}
+
+pub(crate) fn validate_request(request: &CreateRecallRequest) -> Result<(), AppError> {
+    if request.reason.trim().chars().count() < 3 {
+        return Err(AppError::BadRequest(
+            "reason must be at least 3 characters long".into(),
+        ));
+    }
+
+    let criteria = &request.criteria;
+    if matches!(&criteria.input_product, Some(p) if p.trim().is_empty()) {
+        return Err(AppError::BadRequest(
+            "input_product must not be empty".into(),
+        ));
+    }
+    let has_range = criteria.from.is_some() || criteria.to.is_some();
+    if criteria.lot_id.is_some() && (criteria.input_product.is_some() || has_range) {
+        return Err(AppError::BadRequest(
+            "lot_id cannot be combined with other recall criteria".into(),
+        ));
+    }
+    match (criteria.from, criteria.to) {
+        (Some(from), Some(to)) if from > to => {
+            return Err(AppError::BadRequest("from must not be after to".into()))
+        }
+        (Some(_), None) | (None, Some(_)) => {
+            return Err(AppError::BadRequest(
+                "from and to must be given together".into(),
+            ))
+        }
+        _ => {}
+    }
+    if criteria.lot_id.is_none() && criteria.input_product.is_none() && !has_range {
+        return Err(AppError::BadRequest(
+            "A recall needs lot_id, input_product or a from/to date range".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Lotes que cumplen el criterio. `producer_id` limita la búsqueda a un productor.
+fn seed_lots(
+    conn: &mut PgConnection,
+    criteria: &RecallCriteria,
+    producer_id: Option<Uuid>,
+) -> QueryResult<Vec<Uuid>> {
+    if let Some(lot_id) = criteria.lot_id {
+        let mut query = lots::table
+            .filter(lots::id.eq(lot_id))
+            .select(lots::id)
+            .into_boxed();
+        if let Some(producer_id) = producer_id {
+            query = query.filter(lots::producer_id.eq(producer_id));
+        }
+        return query.load(conn);
+    }
+
+    let rows: Vec<IdRow> = match &criteria.input_product {
+        Some(product) => diesel::sql_query(
+            "SELECT DISTINCT e.lot_id AS id
+             FROM events e
+             JOIN lots l ON l.id = e.lot_id
+             WHERE ($2::uuid IS NULL OR l.producer_id = $2)
+               AND ($3::date IS NULL OR e.created_at::date >= $3)
+               AND ($4::date IS NULL OR e.created_at::date <= $4)
+               AND (lower(e.metadata->>'input_product') = lower($1)
+                    OR EXISTS (
+                        SELECT 1
+                        FROM jsonb_array_elements_text(
+                            CASE WHEN jsonb_typeof(e.metadata->'inputs') = 'array'
+                                 THEN e.metadata->'inputs' ELSE '[]'::jsonb END
+                        ) AS input
+                        WHERE lower(input) = lower($1)
+                    ))",
+        )
+        .bind::<Text, _>(product.trim())
+        .bind::<Nullable<SqlUuid>, _>(producer_id)
+        .bind::<Nullable<Date>, _>(criteria.from)
+        .bind::<Nullable<Date>, _>(criteria.to)
+        .load(conn)?,
+        None => {
+            // validate_request garantiza que el rango está completo
+            let (Some(from), Some(to)) = (criteria.from, criteria.to) else {
+                return Ok(Vec::new());
+            };
+            diesel::sql_query(
+                "SELECT l.id
+                 FROM lots l
+                 WHERE ($1::uuid IS NULL OR l.producer_id = $1)
+                   AND COALESCE(l.actual_harvest_date, l.estimated_harvest_date) BETWEEN $2 AND $3",
+            )
+            .bind::<Nullable<SqlUuid>, _>(producer_id)
+            .bind::<Date, _>(from)
+            .bind::<Date, _>(to)
+            .load(conn)?
+        }
+    };
+
+    Ok(rows.into_iter().map(|row| row.id).collect())
+}
+
+/// Une lotes de partida, derivados y orígenes, cada uno una sola vez y a la menor
+/// distancia. Un lote que es a la vez derivado y origen cuenta como derivado.
+fn trace(
+    seeds: &[Uuid],
+    upstream: &[GenealogyLink],
+    downstream: &[GenealogyLink],
+) -> Vec<(Uuid, TraceDirection, i32)> {
+    let mut traced: Vec<(Uuid, TraceDirection, i32)> = Vec::new();
+    let mut seen: HashMap<Uuid, usize> = HashMap::new();
+
+    let mut add = |lot_id: Uuid, direction: TraceDirection, depth: i32| match seen.get(&lot_id) {
+        Some(&index) => {
+            let entry = &mut traced[index];
+            if entry.1 == direction && depth < entry.2 {
+                entry.2 = depth;
+            }
+        }
+        None => {
+            seen.insert(lot_id, traced.len());
+            traced.push((lot_id, direction, depth));
+        }
+    };
+
+    for &lot_id in seeds {
+        add(lot_id, TraceDirection::Seed, 0);
+    }
+    for link in downstream {
+        add(link.lot_id, TraceDirection::Downstream, link.depth);
+    }
+    for link in upstream {
+        add(link.lot_id, TraceDirection::Upstream, link.depth);
+    }
+    traced
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !values.iter().any(|v| v == value) {
+        values.push(value.to_string());
+    }
+}
+
+/// Productores de todos los lotes trazados y compradores de los lotes afectados.
+/// Los compradores se agrupan por email (o por nombre si no lo hay).
+fn collect_parties(
+    lots: &[AffectedLot],
+    producers: &HashMap<Uuid, (String, String)>,
+    shipments: &[Shipment],
+) -> Vec<AffectedParty> {
+    let mut parties: Vec<AffectedParty> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for lot in lots {
+        let key = format!("producer:{}", lot.producer_id);
+        let position = *index.entry(key).or_insert_with(|| {
+            let (name, email) = producers.get(&lot.producer_id).cloned().unwrap_or_default();
+            parties.push(AffectedParty {
+                party_type: RecallPartyType::Producer,
+                name,
+                email: Some(email),
+                producer_id: Some(lot.producer_id),
+                lot_codes: Vec::new(),
+            });
+            parties.len() - 1
+        });
+        push_unique(&mut parties[position].lot_codes, &lot.lot_code);
+    }
+
+    let affected: HashMap<Uuid, &AffectedLot> = lots
+        .iter()
+        .filter(|lot| lot.direction != TraceDirection::Upstream)
+        .map(|lot| (lot.lot_id, lot))
+        .collect();
+
+    for shipment in shipments {
+        let Some(lot) = affected.get(&shipment.lot_id) else {
+            continue;
+        };
+        let key = match &shipment.buyer_email {
+            Some(email) => format!("buyer:{}", email.trim().to_lowercase()),
+            None => format!("buyer-name:{}", shipment.buyer_name.trim().to_lowercase()),
+        };
+        let position = *index.entry(key).or_insert_with(|| {
+            parties.push(AffectedParty {
+                party_type: RecallPartyType::Buyer,
+                name: shipment.buyer_name.clone(),
+                email: shipment.buyer_email.clone(),
+                producer_id: None,
+                lot_codes: Vec::new(),
+            });
+            parties.len() - 1
+        });
+        push_unique(&mut parties[position].lot_codes, &lot.lot_code);
+    }
+
+    parties
+}
+
+fn mail_text(report: &RecallReport, party: &AffectedParty) -> (String, String) {
+    let action = match party.party_type {
+        RecallPartyType::Buyer => {
+            "Detén la venta y distribución del producto de estos lotes y contacta con tu proveedor."
+        }
+        RecallPartyType::Producer => {
+            "Revisa los lotes afectados en Kairos y conserva la documentación de su producción."
+        }
+    };
+    let subject = format!("Retirada de producto: {}", party.lot_codes.join(", "));
+    let body = format!(
+        "Hola {},\n\nSe ha iniciado una retirada de producto que afecta a los lotes:\n\n{}\n\nMotivo: {}\n\n{}\n\nReferencia de la retirada: {}",
+        party.name,
+        party.lot_codes.join("\n"),
+        report.reason,
+        action,
+        report.recall_id
+    );
+    (subject, body)
+}
+
+impl Recall {
+    /// Inicia la retirada: guarda el informe y encola un aviso por parte afectada.
+    pub fn initiate(
+        conn: &mut PgConnection,
+        request: &CreateRecallRequest,
+        producer_scope: Option<Uuid>,
+        actor_id: Uuid,
+    ) -> Result<RecallReport, AppError> {
+        validate_request(request)?;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let seeds = seed_lots(conn, &request.criteria, producer_scope)?;
+            if seeds.is_empty() {
+                return Err(AppError::NotFound(
+                    "No lots match the recall criteria".into(),
+                ));
+            }
+            let upstream = walk(conn, &seeds, Direction::Ancestors, MAX_GENEALOGY_DEPTH)?;
+            let downstream = walk(conn, &seeds, Direction::Descendants, MAX_GENEALOGY_DEPTH)?;
+            let traced = trace(&seeds, &upstream, &downstream);
+
+            let ids: Vec<Uuid> = traced.iter().map(|(id, _, _)| *id).collect();
+            let details: HashMap<Uuid, TracedLot> = lots::table
+                .inner_join(producers::table)
+                .filter(lots::id.eq_any(&ids))
+                .select((
+                    lots::id,
+                    lots::lot_code,
+                    lots::product_name,
+                    lots::producer_id,
+                    producers::full_name,
+                    producers::email,
+                    producers::farm_name,
+                ))
+                .load::<TracedLot>(conn)?
+                .into_iter()
+                .map(|lot| (lot.id, lot))
+                .collect();
+
+            let lots: Vec<AffectedLot> = traced
+                .iter()
+                .filter_map(|(id, direction, depth)| {
+                    details.get(id).map(|lot| AffectedLot {
+                        lot_id: lot.id,
+                        lot_code: lot.lot_code.clone(),
+                        product_name: lot.product_name.clone(),
+                        producer_id: lot.producer_id,
+                        direction: *direction,
+                        depth: *depth,
+                    })
+                })
+                .collect();
+            let producers: HashMap<Uuid, (String, String)> = details
+                .values()
+                .map(|lot| {
+                    let name = lot
+                        .farm_name
+                        .clone()
+                        .unwrap_or_else(|| lot.producer_name.clone());
+                    (lot.producer_id, (name, lot.producer_email.clone()))
+                })
+                .collect();
+            let shipments = Shipment::find_by_lots(conn, &ids)?;
+
+            let report = RecallReport {
+                recall_id: Uuid::new_v4(),
+                reason: request.reason.trim().to_string(),
+                criteria: request.criteria.clone(),
+                initiated_by: actor_id,
+                created_at: Utc::now(),
+                parties: collect_parties(&lots, &producers, &shipments),
+                lots,
+            };
+
+            diesel::insert_into(recalls::table)
+                .values((
+                    recalls::id.eq(report.recall_id),
+                    recalls::initiated_by.eq(actor_id),
+                    recalls::reason.eq(&report.reason),
+                    recalls::criteria
+                        .eq(serde_json::to_value(&report.criteria).map_err(json_error)?),
+                    recalls::report.eq(serde_json::to_value(&report).map_err(json_error)?),
+                    recalls::created_at.eq(report.created_at),
+                ))
+                .execute(conn)?;
+
+            for party in &report.parties {
+                queue_notification(conn, &report, party)?;
+            }
+
+            Ok(report)
+        })
+    }
+
+    pub fn find_by_id(conn: &mut PgConnection, id: Uuid) -> QueryResult<Recall> {
+        recalls::table
+            .find(id)
+            .select(Recall::as_select())
+            .first(conn)
+    }
+
+    pub fn find_by_initiator(
+        conn: &mut PgConnection,
+        initiated_by: Option<Uuid>,
+    ) -> QueryResult<Vec<Recall>> {
+        let mut query = recalls::table
+            .order(recalls::created_at.desc())
+            .select(Recall::as_select())
+            .into_boxed();
+        if let Some(initiated_by) = initiated_by {
+            query = query.filter(recalls::initiated_by.eq(initiated_by));
+        }
+        query.load(conn)
+    }
+
+    pub fn report(&self) -> Result<RecallReport, AppError> {
+        serde_json::from_value(self.report.clone()).map_err(json_error)
+    }
+}
+
+/// Los productores reciben además una notificación en la app. Los compradores sin
+/// email quedan registrados como fallidos para que se les contacte por otra vía.
+fn queue_notification(
+    conn: &mut PgConnection,
+    report: &RecallReport,
+    party: &AffectedParty,
+) -> Result<(), AppError> {
+    let (subject, body) = mail_text(report, party);
+    let (status, last_error) = match party.email {
+        Some(_) => (PENDING, None),
+        None => (FAILED, Some("No email address on record")),
+    };
+
+    diesel::insert_into(recall_notifications::table)
+        .values((
+            recall_notifications::recall_id.eq(report.recall_id),
+            recall_notifications::party_type.eq(party.party_type.to_str()),
+            recall_notifications::recipient_name.eq(&party.name),
+            recall_notifications::recipient_email.eq(&party.email),
+            recall_notifications::producer_id.eq(party.producer_id),
+            recall_notifications::subject.eq(&subject),
+            recall_notifications::body.eq(&body),
+            recall_notifications::status.eq(status),
+            recall_notifications::last_error.eq(last_error),
+        ))
+        .execute(conn)?;
+
+    if let Some(producer_id) = party.producer_id {
+        Notification::create(
+            conn,
+            NewNotification {
+                user_id: producer_id,
+                title: "A product recall affects your lots".to_string(),
+                message: format!("{} ({})", report.reason, party.lot_codes.join(", ")),
+                notification_type: notification::RECALL_ISSUED.to_string(),
+                is_read: false,
+                metadata: Some(serde_json::json!({
+                    "recall_id": report.recall_id,
+                    "lot_codes": party.lot_codes,
+                })),
+            },
+        )?;
+    }
+    Ok(())
+}
+
+impl RecallNotification {
+    pub fn find_by_recall(conn: &mut PgConnection, recall_id: Uuid) -> QueryResult<Vec<Self>> {
+        recall_notifications::table
+            .filter(recall_notifications::recall_id.eq(recall_id))
+            .order((recall_notifications::created_at, recall_notifications::id))
+            .select(RecallNotification::as_select())
+            .load(conn)
+    }
+
+    /// Reclama hasta `batch` avisos pendientes, o que otro despachador dejó en
+    /// `sending` hace más de `CLAIM_TIMEOUT_SECONDS`, y los confirma antes de enviar.
+    /// `SKIP LOCKED` permite varias instancias a la vez sin reclamar el mismo aviso.
+    fn claim_pending(conn: &mut PgConnection, batch: i64) -> QueryResult<Vec<RecallNotification>> {
+        conn.transaction(|conn| {
+            let stale = Utc::now() - Duration::seconds(CLAIM_TIMEOUT_SECONDS);
+            let ids: Vec<Uuid> = recall_notifications::table
+                .filter(
+                    recall_notifications::status.eq(PENDING).or(recall_notifications::status
+                        .eq(SENDING)
+                        .and(recall_notifications::claimed_at.lt(stale))),
+                )
+                .order(recall_notifications::created_at)
+                .limit(batch)
+                .for_update()
+                .skip_locked()
+                .select(recall_notifications::id)
+                .load(conn)?;
+
+            // El intento cuenta al reclamar, así un envío que tumba el proceso no se
+            // reintenta para siempre
+            diesel::update(recall_notifications::table.filter(recall_notifications::id.eq_any(&ids)))
+                .set((
+                    recall_notifications::status.eq(SENDING),
+                    recall_notifications::claimed_at.eq(Some(Utc::now())),
+                    recall_notifications::attempts.eq(recall_notifications::attempts + 1),
+                ))
+                .returning(RecallNotification::as_returning())
+                .get_results(conn)
+        })
+    }
+
+    /// Envía hasta `batch` avisos pendientes. Pensado para llamarse periódicamente.
+    /// Los correos salen fuera de cualquier transacción: un SMTP lento no retiene
+    /// bloqueos ni conexiones en espera, y cada resultado se guarda por separado.
+    pub fn dispatch_pending(
+        conn: &mut PgConnection,
+        mailer: &dyn Mailer,
+        batch: i64,
+    ) -> QueryResult<DispatchSummary> {
+        let claimed = RecallNotification::claim_pending(conn, batch)?;
+
+        let mut summary = DispatchSummary::default();
+        for notification in claimed {
+            let Some(to) = notification.recipient_email.clone() else {
+                continue;
+            };
+            // Si otro despachador lo reclamó por tardar demasiado, su resultado manda
+            let target = recall_notifications::table
+                .filter(recall_notifications::id.eq(notification.id))
+                .filter(recall_notifications::status.eq(SENDING))
+                .filter(recall_notifications::claimed_at.eq(notification.claimed_at));
+
+            match mailer.send(&OutgoingMail {
+                to,
+                subject: notification.subject,
+                body: notification.body,
+            }) {
+                Ok(()) => {
+                    diesel::update(target)
+                        .set((
+                            recall_notifications::status.eq(SENT),
+                            recall_notifications::last_error.eq(None::<String>),
+                            recall_notifications::sent_at.eq(Some(Utc::now())),
+                            recall_notifications::claimed_at.eq(None::<DateTime<Utc>>),
+                        ))
+                        .execute(conn)?;
+                    summary.sent += 1;
+                }
+                Err(e) => {
+                    let status = if notification.attempts >= MAX_DELIVERY_ATTEMPTS {
+                        summary.failed += 1;
+                        FAILED
+                    } else {
+                        summary.retrying += 1;
+                        PENDING
+                    };
+                    diesel::update(target)
+                        .set((
+                            recall_notifications::status.eq(status),
+                            recall_notifications::last_error.eq(Some(e.to_string())),
+                            recall_notifications::claimed_at.eq(None::<DateTime<Utc>>),
+                        ))
+                        .execute(conn)?;
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// Informe en CSV: una fila por parte afectada y lote.
+pub fn report_csv(
+    report: &RecallReport,
+    notifications: &[RecallNotification],
+) -> Result<Vec<u8>, AppError> {
+    let csv_error =
+        |e: csv::Error| AppError::InternalServerError(format!("CSV export failed: {}", e));
+    let status_of = |party: &AffectedParty| {
+        notifications
+            .iter()
+            .find(|n| {
+                n.party_type == party.party_type.to_str()
+                    && n.recipient_name == party.name
+                    && n.recipient_email == party.email
+            })
+            .map(|n| n.status.as_str())
+            .unwrap_or("")
+    };
+    let lots: HashMap<&str, &AffectedLot> = report
+        .lots
+        .iter()
+        .map(|lot| (lot.lot_code.as_str(), lot))
+        .collect();
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record([
+            "recall_id",
+            "party_type",
+            "name",
+            "email",
+            "lot_code",
+            "product_name",
+            "direction",
+            "depth",
+            "notification_status",
+        ])
+        .map_err(csv_error)?;
+
+    for party in &report.parties {
+        for lot_code in &party.lot_codes {
+            let lot = lots.get(lot_code.as_str());
+            writer
+                .write_record([
+                    report.recall_id.to_string(),
+                    party.party_type.to_str().to_string(),
+                    party.name.clone(),
+                    party.email.clone().unwrap_or_default(),
+                    lot_code.clone(),
+                    lot.map(|l| l.product_name.clone()).unwrap_or_default(),
+                    lot.map(|l| l.direction.to_str().to_string())
+                        .unwrap_or_default(),
+                    lot.map(|l| l.depth.to_string()).unwrap_or_default(),
+                    status_of(party).to_string(),
+                ])
+                .map_err(csv_error)?;
+        }
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| AppError::InternalServerError(format!("CSV export failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use kairos_common::GenealogyOperation;
+    use rust_decimal::Decimal;
+
+    fn link(lot_id: Uuid, depth: i32) -> GenealogyLink {
+        GenealogyLink {
+            lot_id,
+            lot_code: String::new(),
+            product_name: String::new(),
+            unit_of_measure: "kg".into(),
+            via_lot_id: Uuid::new_v4(),
+            quantity: Decimal::ONE,
+            operation: GenealogyOperation::Split,
+            depth,
+        }
+    }
+
+    fn lot(code: &str, producer_id: Uuid, direction: TraceDirection) -> AffectedLot {
+        AffectedLot {
+            lot_id: Uuid::new_v4(),
+            lot_code: code.into(),
+            product_name: "Tomates".into(),
+            producer_id,
+            direction,
+            depth: 0,
+        }
+    }
+
+    fn shipment(lot_id: Uuid, buyer: &str, email: Option<&str>) -> Shipment {
+        Shipment {
+            id: Uuid::new_v4(),
+            lot_id,
+            buyer_name: buyer.into(),
+            buyer_email: email.map(Into::into),
+            quantity: Decimal::TEN,
+            shipped_at: Utc::now(),
+            reference: None,
+            created_by: Uuid::new_v4(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn request(criteria: RecallCriteria) -> CreateRecallRequest {
+        CreateRecallRequest {
+            reason: "Listeria".into(),
+            criteria,
+        }
+    }
+
+    #[test]
+    fn criteria_need_exactly_one_starting_point() {
+        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+
+        assert!(validate_request(&request(RecallCriteria::default())).is_err());
+        assert!(validate_request(&request(RecallCriteria {
+            lot_id: Some(Uuid::new_v4()),
+            input_product: Some("Cobre".into()),
+            ..Default::default()
+        }))
+        .is_err());
+        assert!(validate_request(&request(RecallCriteria {
+            from: Some(date),
+            ..Default::default()
+        }))
+        .is_err());
+
+        assert!(validate_request(&request(RecallCriteria {
+            lot_id: Some(Uuid::new_v4()),
+            ..Default::default()
+        }))
+        .is_ok());
+        assert!(validate_request(&request(RecallCriteria {
+            input_product: Some("Cobre".into()),
+            from: Some(date),
+            to: Some(date),
+            ..Default::default()
+        }))
+        .is_ok());
+    }
+
+    #[test]
+    fn traced_lots_keep_the_closest_relation() {
+        let (seed, child, grandchild, parent) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+
+        let traced = trace(
+            &[seed],
+            &[link(parent, 1)],
+            &[
+                link(child, 1),
+                link(grandchild, 2),
+                link(grandchild, 1),
+                link(seed, 2),
+            ],
+        );
+
+        assert_eq!(
+            traced,
+            vec![
+                (seed, TraceDirection::Seed, 0),
+                (child, TraceDirection::Downstream, 1),
+                (grandchild, TraceDirection::Downstream, 1),
+                (parent, TraceDirection::Upstream, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn buyers_are_grouped_and_upstream_sales_are_ignored() {
+        let producer_id = Uuid::new_v4();
+        let lots = vec![
+            lot("LOT-A", producer_id, TraceDirection::Seed),
+            lot("LOT-B", producer_id, TraceDirection::Downstream),
+            lot("LOT-C", producer_id, TraceDirection::Upstream),
+        ];
+        let producers = HashMap::from([(
+            producer_id,
+            (
+                "Finca La Esperanza".to_string(),
+                "finca@example.com".to_string(),
+            ),
+        )]);
+        let shipments = vec![
+            shipment(
+                lots[0].lot_id,
+                "Mercado Central",
+                Some("compras@mercado.example"),
+            ),
+            shipment(
+                lots[1].lot_id,
+                "Mercado Central SA",
+                Some(" Compras@Mercado.example"),
+            ),
+            shipment(lots[1].lot_id, "Verdulería Pepe", None),
+            shipment(lots[2].lot_id, "Otro Comprador", Some("otro@example.com")),
+        ];
+
+        let parties = collect_parties(&lots, &producers, &shipments);
+
+        assert_eq!(parties.len(), 3);
+        assert_eq!(parties[0].party_type, RecallPartyType::Producer);
+        assert_eq!(parties[0].lot_codes, vec!["LOT-A", "LOT-B", "LOT-C"]);
+        assert_eq!(parties[1].name, "Mercado Central");
+        assert_eq!(parties[1].lot_codes, vec!["LOT-A", "LOT-B"]);
+        assert_eq!(parties[2].email, None);
+    }
+
+    #[test]
+    fn csv_report_has_one_row_per_party_and_lot() {
+        let producer_id = Uuid::new_v4();
+        let lots = vec![
+            lot("LOT-A", producer_id, TraceDirection::Seed),
+            lot("LOT-B", producer_id, TraceDirection::Downstream),
+        ];
+        let producers = HashMap::from([(
+            producer_id,
+            ("Finca".to_string(), "f@example.com".to_string()),
+        )]);
+        let report = RecallReport {
+            recall_id: Uuid::new_v4(),
+            reason: "Listeria".into(),
+            criteria: RecallCriteria::default(),
+            initiated_by: producer_id,
+            created_at: Utc::now(),
+            parties: collect_parties(&lots, &producers, &[]),
+            lots,
+        };
+
+        let csv = String::from_utf8(report_csv(&report, &[]).unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("recall_id,party_type,name"));
+        assert!(lines[2].contains(",producer,Finca,f@example.com,LOT-B,Tomates,downstream,0,"));
+    }
+}
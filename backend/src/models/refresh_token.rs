@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::schema::refresh_tokens;
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = refresh_tokens)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub producer_id: Uuid,
+    pub session_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = refresh_tokens)]
+pub struct NewRefreshToken {
+    pub producer_id: Uuid,
+    pub session_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl RefreshToken {
+    pub fn create(conn: &mut PgConnection, new_token: NewRefreshToken) -> QueryResult<Self> {
+        diesel::insert_into(refresh_tokens::table)
+            .values(&new_token)
+            .returning(RefreshToken::as_returning())
+            .get_result(conn)
+    }
+
+    pub fn find_by_hash(conn: &mut PgConnection, hash: &str) -> QueryResult<Self> {
+        refresh_tokens::table
+            .filter(refresh_tokens::token_hash.eq(hash))
+            .select(RefreshToken::as_select())
+            .first(conn)
+    }
+
+    /// Marca el token como usado. Devuelve 0 si ya estaba revocado, lo que
+    /// significa que alguien lo presentó antes (posible robo).
+    pub fn revoke(conn: &mut PgConnection, token_id: Uuid) -> QueryResult<usize> {
+        diesel::update(
+            refresh_tokens::table
+                .filter(refresh_tokens::id.eq(token_id))
+                .filter(refresh_tokens::revoked_at.is_null()),
+        )
+        .set(refresh_tokens::revoked_at.eq(Utc::now()))
+        .execute(conn)
+    }
+
+    pub fn revoke_session(conn: &mut PgConnection, session_id: Uuid) -> QueryResult<usize> {
+        diesel::update(
+            refresh_tokens::table
+                .filter(refresh_tokens::session_id.eq(session_id))
+                .filter(refresh_tokens::revoked_at.is_null()),
+        )
+        .set(refresh_tokens::revoked_at.eq(Utc::now()))
+        .execute(conn)
+    }
+
+    /// Revoca de una vez todos los refresh tokens pendientes del productor.
+    pub fn revoke_producer(conn: &mut PgConnection, producer_id: Uuid) -> QueryResult<usize> {
+        diesel::update(
+            refresh_tokens::table
+                .filter(refresh_tokens::producer_id.eq(producer_id))
+                .filter(refresh_tokens::revoked_at.is_null()),
+        )
+        .set(refresh_tokens::revoked_at.eq(Utc::now()))
+        .execute(conn)
+    }
+
+    /// Todas las sesiones del productor que siguen en la tabla, estén o no sus
+    /// refresh tokens usados o caducados: un access token de la sesión puede
+    /// seguir vivo aunque su refresh token ya no sirva.
+    pub fn sessions(conn: &mut PgConnection, producer_id: Uuid) -> QueryResult<Vec<Uuid>> {
+        refresh_tokens::table
+            .filter(refresh_tokens::producer_id.eq(producer_id))
+            .select(refresh_tokens::session_id)
+            .distinct()
+            .load(conn)
+    }
+
+    pub fn delete_expired(conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::delete(refresh_tokens::table.filter(refresh_tokens::expires_at.lt(Utc::now())))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::schema::revoked_sessions;
+
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = revoked_sessions)]
+pub struct RevokedSession {
+    pub session_id: Uuid,
+    pub producer_id: Uuid,
+    pub revoked_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl RevokedSession {
+    /// `expires_at` debe cubrir la vida del último access token emitido para la sesión;
+    /// pasado ese momento la entrada ya no es necesaria.
+    pub fn revoke(
+        conn: &mut PgConnection,
+        producer_id: Uuid,
+        session_id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> QueryResult<usize> {
+        diesel::insert_into(revoked_sessions::table)
+            .values(&RevokedSession {
+                session_id,
+                producer_id,
+                revoked_at: Utc::now(),
+                expires_at,
+            })
+            .on_conflict(revoked_sessions::session_id)
+            .do_nothing()
+            .execute(conn)
+    }
+
+    pub fn is_revoked(conn: &mut PgConnection, session_id: Uuid) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(
+            revoked_sessions::table.filter(revoked_sessions::session_id.eq(session_id)),
+        ))
+        .get_result(conn)
+    }
+
+    pub fn delete_expired(conn: &mut PgConnection) -> QueryResult<usize> {
+        diesel::delete(revoked_sessions::table.filter(revoked_sessions::expires_at.lt(Utc::now())))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::Write;
+
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::schema::{producers, sql_types::RoleEnum};
+
+/// Rol de la cuenta, mapeado a `role_enum` en Postgres.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = RoleEnum)]
+pub enum Role {
+    Admin,
+    Producer,
+    User,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "Admin",
+            Role::Producer => "Producer",
+            Role::User => "User",
+        }
+    }
+
+    pub fn for_producer(conn: &mut PgConnection, producer_id: Uuid) -> QueryResult<Self> {
+        producers::table
+            .find(producer_id)
+            .select(producers::role)
+            .first(conn)
+    }
+
+    /// Como `for_producer`, bloqueando la fila del productor hasta el final de la
+    /// transacción.
+    pub fn lock_for_producer(conn: &mut PgConnection, producer_id: Uuid) -> QueryResult<Self> {
+        producers::table
+            .find(producer_id)
+            .for_update()
+            .select(producers::role)
+            .first(conn)
+    }
+
+    pub fn assign(conn: &mut PgConnection, producer_id: Uuid, role: Role) -> QueryResult<usize> {
+        diesel::update(producers::table.find(producer_id))
+            .set(producers::role.eq(role))
+            .execute(conn)
+    }
+}
+
+impl ToSql<RoleEnum, Pg> for Role {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<RoleEnum, Pg> for Role {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"Admin" => Ok(Role::Admin),
+            b"Producer" => Ok(Role::Producer),
+            b"User" => Ok(Role::User),
+            _ => Err("Unrecognized role_enum variant".into()),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use kairos_common::{CreateShipmentRequest, EventType, LotStatus};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::{
+        lot::Lot,
+        lot_genealogy::{insufficient_quantity, lock_lots, record_event, validate_quantity},
+    },
+    schema::shipments,
+};
+
+/// Envío de parte de un lote a un comprador.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = shipments)]
+pub struct Shipment {
+    pub id: Uuid,
+    pub lot_id: Uuid,
+    pub buyer_name: String,
+    pub buyer_email: Option<String>,
+    pub quantity: Decimal,
+    pub shipped_at: DateTime<Utc>,
+    pub reference: Option<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+fn validate_buyer(request: &CreateShipmentRequest) -> Result<(), AppError> {
+    if request.buyer_name.trim().chars().count() < 2 {
+        return Err(AppError::BadRequest(
+            "buyer_name must be at least 2 characters long".into(),
+        ));
+    }
+    if matches!(&request.buyer_email, Some(email) if !email.contains('@')) {
+        return Err(AppError::BadRequest(
+            "buyer_email is not a valid email".into(),
+        ));
+    }
+    Ok(())
+}
+
+impl Shipment {
+    /// Registra el envío y lo deja como evento en la cadena del lote.
+    pub fn create(
+        conn: &mut PgConnection,
+        lot_id: Uuid,
+        request: &CreateShipmentRequest,
+        actor_id: Uuid,
+    ) -> Result<Shipment, AppError> {
+        validate_buyer(request)?;
+        validate_quantity(request.quantity)?;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            let lot = lock_lots(conn, &[lot_id])?
+                .pop()
+                .ok_or_else(|| AppError::NotFound("Lot not found".into()))?;
+            if !matches!(lot.current_status, LotStatus::Harvested | LotStatus::Sold) {
+                return Err(AppError::BadRequest(format!(
+                    "Only HARVESTED or SOLD lots can be shipped, lot is {}",
+                    lot.current_status
+                )));
+            }
+
+            let available = Lot::available_quantity(conn, &lot)?;
+            if request.quantity > available {
+                return Err(insufficient_quantity(&lot, available, request.quantity));
+            }
+
+            let shipment: Shipment = diesel::insert_into(shipments::table)
+                .values((
+                    shipments::lot_id.eq(lot.id),
+                    shipments::buyer_name.eq(request.buyer_name.trim()),
+                    shipments::buyer_email.eq(request.buyer_email.as_deref().map(str::trim)),
+                    shipments::quantity.eq(request.quantity),
+                    shipments::shipped_at.eq(request.shipped_at.unwrap_or_else(Utc::now)),
+                    shipments::reference.eq(&request.reference),
+                    shipments::created_by.eq(actor_id),
+                ))
+                .returning(Shipment::as_returning())
+                .get_result(conn)?;
+
+            record_event(
+                conn,
+                lot.id,
+                EventType::LotUpdated,
+                format!(
+                    "Shipped {} {} to {}",
+                    shipment.quantity, lot.unit_of_measure, shipment.buyer_name
+                ),
+                serde_json::json!({
+                    "shipment": {
+                        "id": shipment.id,
+                        "buyer_name": shipment.buyer_name,
+                        "quantity": shipment.quantity,
+                        "reference": shipment.reference,
+                    },
+                    "actor_id": actor_id,
+                }),
+            )?;
+
+            Ok(shipment)
+        })
+    }
+
+    pub fn find_by_lot(conn: &mut PgConnection, lot_id: Uuid) -> QueryResult<Vec<Shipment>> {
+        shipments::table
+            .filter(shipments::lot_id.eq(lot_id))
+            .order((shipments::shipped_at.desc(), shipments::id))
+            .select(Shipment::as_select())
+            .load(conn)
+    }
+
+    pub fn find_by_lots(conn: &mut PgConnection, lot_ids: &[Uuid]) -> QueryResult<Vec<Shipment>> {
+        shipments::table
+            .filter(shipments::lot_id.eq_any(lot_ids))
+            .order((shipments::shipped_at, shipments::id))
+            .select(Shipment::as_select())
+            .load(conn)
+    }
+}
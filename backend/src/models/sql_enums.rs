@@ -0,0 +1,112 @@
+//! Envoltorios para mapear los enums de `kairos_common` a los tipos ENUM de Postgres,
+//! y `Point` al tipo geométrico POINT.
+//!
+//! `kairos_common` se compila también a WASM y no puede depender de diesel, así que
+//! los tipos compartidos se envuelven aquí para poder usarlos en filtros y updates.
+
+use std::io::Write;
+
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use kairos_common::{CropType, EventType, LotStatus, Point, ProducerStatus};
+
+use crate::schema::sql_types::{
+    CropTypeEnum, EventTypeEnum, LotStatusEnum, Point as PointType, ProducerStatusEnum,
+};
+
+macro_rules! pg_enum {
+    ($wrapper:ident, $inner:ty, $sql_type:ty, $pg_name:literal, { $($variant:path => $label:literal),+ $(,)? }) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+        #[diesel(sql_type = $sql_type)]
+        pub struct $wrapper(pub $inner);
+
+        impl ToSql<$sql_type, Pg> for $wrapper {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+                let value: &[u8] = match self.0 {
+                    $($variant => $label.as_bytes(),)+
+                };
+                out.write_all(value)?;
+                Ok(IsNull::No)
+            }
+        }
+
+        impl FromSql<$sql_type, Pg> for $wrapper {
+            fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+                $(
+                    if bytes.as_bytes() == $label.as_bytes() {
+                        return Ok($wrapper($variant));
+                    }
+                )+
+                Err(concat!("Unrecognized ", $pg_name, " variant").into())
+            }
+        }
+    };
+}
+
+pg_enum!(DbProducerStatus, ProducerStatus, ProducerStatusEnum, "producer_status_enum", {
+    ProducerStatus::Pending => "pending",
+    ProducerStatus::Approved => "approved",
+    ProducerStatus::Rejected => "rejected",
+});
+
+pg_enum!(DbLotStatus, LotStatus, LotStatusEnum, "lot_status_enum", {
+    LotStatus::Registered => "REGISTERED",
+    LotStatus::Growing => "GROWING",
+    LotStatus::ReadyForHarvest => "READY_FOR_HARVEST",
+    LotStatus::Harvested => "HARVESTED",
+    LotStatus::Sold => "SOLD",
+    LotStatus::Cancelled => "CANCELLED",
+});
+
+pg_enum!(DbEventType, EventType, EventTypeEnum, "event_type_enum", {
+    EventType::LotRegistered => "LOT_REGISTERED",
+    EventType::FertilizerApplication => "FERTILIZER_APPLICATION",
+    EventType::Irrigation => "IRRIGATION",
+    EventType::PestControl => "PEST_CONTROL",
+    EventType::HarvestStarted => "HARVEST_STARTED",
+    EventType::HarvestCompleted => "HARVEST_COMPLETED",
+    EventType::LotUpdated => "LOT_UPDATED",
+    EventType::QualityInspection => "QUALITY_INSPECTION",
+});
+
+pg_enum!(DbCropType, CropType, CropTypeEnum, "crop_type_enum", {
+    CropType::Conventional => "CONVENTIONAL",
+    CropType::AgroecologicalUncertified => "AGROECOLOGICAL_UNCERTIFIED",
+    CropType::OrganicCertified => "ORGANIC_CERTIFIED",
+    CropType::Hydroponic => "HYDROPONIC",
+});
+
+/// POINT viaja en binario como dos float8 big-endian (x, y).
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = PointType)]
+pub struct DbPoint(pub Point);
+
+impl ToSql<PointType, Pg> for DbPoint {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(&self.0.x.to_be_bytes())?;
+        out.write_all(&self.0.y.to_be_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl serde::Serialize for DbPoint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl FromSql<PointType, Pg> for DbPoint {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let raw: [u8; 16] = bytes
+            .as_bytes()
+            .try_into()
+            .map_err(|_| "Invalid point length")?;
+        let (x, y) = raw.split_at(8);
+        Ok(DbPoint(Point {
+            x: f64::from_be_bytes(x.try_into()?),
+            y: f64::from_be_bytes(y.try_into()?),
+        }))
+    }
+}
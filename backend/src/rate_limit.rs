@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::HttpRequest;
+
+use crate::errors::AppError;
+
+/// Por encima de este número de IPs se purgan las ventanas ya vencidas.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Límite de peticiones por IP en ventanas fijas. Se registra una sola instancia
+/// como `web::Data` para que la cuenta sea común a todos los workers.
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    hits: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    pub fn check(&self, ip: IpAddr) -> Result<(), AppError> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), AppError> {
+        let mut hits = self.hits.lock().unwrap_or_else(|e| e.into_inner());
+        if hits.len() > PRUNE_THRESHOLD {
+            hits.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        }
+
+        let (started, count) = hits.entry(ip).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+        if *count >= self.limit {
+            let retry_in = self.window.saturating_sub(now.duration_since(*started));
+            return Err(AppError::TooManyRequests(format!(
+                "Rate limit exceeded, retry in {} seconds",
+                retry_in.as_secs().max(1)
+            )));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// IP del cliente. `X-Forwarded-For` solo se respeta detrás de un proxy de confianza;
+/// si no, cualquiera podría esquivar el límite cambiando la cabecera.
+pub fn client_ip(req: &HttpRequest, trust_forwarded_for: bool) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let info = req.connection_info();
+        if let Some(addr) = info.realip_remote_addr() {
+            if let Ok(ip) = addr.parse::<IpAddr>() {
+                return Some(ip);
+            }
+            if let Ok(socket) = addr.parse::<SocketAddr>() {
+                return Some(socket.ip());
+            }
+        }
+    }
+    req.peer_addr().map(|addr| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn requests_over_the_limit_are_rejected_until_the_window_resets() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+        let start = Instant::now();
+
+        assert!(limiter.check_at(ip, start).is_ok());
+        assert!(limiter.check_at(ip, start).is_ok());
+        assert!(matches!(
+            limiter.check_at(ip, start + Duration::from_secs(1)),
+            Err(AppError::TooManyRequests(_))
+        ));
+        assert!(limiter
+            .check_at(ip, start + Duration::from_secs(60))
+            .is_ok());
+    }
+
+    #[test]
+    fn limits_are_tracked_per_ip() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter
+            .check_at(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1)), now)
+            .is_ok());
+        assert!(limiter
+            .check_at(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 2)), now)
+            .is_ok());
+    }
+}
@@ -0,0 +1,321 @@
+//! Certificados de trazabilidad firmados con Ed25519 y su verificación sin conexión.
+//!
+//! La firma cubre el JSON canónico del campo `certificate` (claves ordenadas, sin
+//! espacios), así que el documento puede reformatearse sin invalidarla. Quien
+//! verifica debe tener de antemano las claves públicas del emisor
+//! (`GET /public/certificates/keys`); la clave incluida en la firma es informativa.
+
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, NaiveDate, Utc};
+use ed25519_dalek::{Signature, VerifyingKey};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{CropType, EventType, GenealogyLink, LotStatus};
+
+pub const ALGORITHM: &str = "Ed25519";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateLot {
+    pub lot_id: Uuid,
+    pub lot_code: String,
+    pub product_name: String,
+    pub crop_type: CropType,
+    pub status: LotStatus,
+    pub quantity: Decimal,
+    pub unit_of_measure: String,
+    pub estimated_harvest_date: NaiveDate,
+    pub actual_harvest_date: Option<NaiveDate>,
+    pub producer_name: String,
+    pub farm_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateEvent {
+    pub event_id: Uuid,
+    pub sequence: i64,
+    pub event_type: EventType,
+    pub description: Option<String>,
+    pub event_location: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub corrects_event_id: Option<Uuid>,
+    pub prev_hash: Option<String>,
+    pub hash: String,
+}
+
+/// Contenido firmado: el lote, su cadena completa de eventos y los lotes de origen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceabilityCertificate {
+    pub certificate_id: Uuid,
+    pub issuer: String,
+    pub issued_at: DateTime<Utc>,
+    pub lot: CertificateLot,
+    pub events: Vec<CertificateEvent>,
+    pub head_hash: Option<String>,
+    pub origins: Vec<GenealogyLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateSignature {
+    pub algorithm: String,
+    pub key_id: String,
+    /// Clave pública en base64, solo como referencia.
+    pub public_key: String,
+    /// Firma en base64 del JSON canónico de `certificate`.
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedCertificate {
+    pub certificate: TraceabilityCertificate,
+    pub signature: CertificateSignature,
+}
+
+/// Clave pública de confianza publicada por el emisor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub key_id: String,
+    pub algorithm: String,
+    /// Clave pública en base64.
+    pub public_key: String,
+}
+
+/// Respuesta de `POST /public/certificates/verify`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateVerification {
+    pub valid: bool,
+    pub certificate_id: Option<Uuid>,
+    pub lot_code: Option<String>,
+    pub key_id: Option<String>,
+    /// Motivo del rechazo si `valid` es `false`.
+    pub reason: Option<String>,
+    /// `true` si el lote tiene eventos posteriores a la emisión del certificado.
+    pub history_extended: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificateError {
+    Malformed(String),
+    UnsupportedAlgorithm(String),
+    UnknownKey(String),
+    InvalidSignature,
+    /// La cadena de eventos no enlaza en la posición indicada.
+    BrokenChain(i64),
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateError::Malformed(reason) => write!(f, "malformed certificate: {}", reason),
+            CertificateError::UnsupportedAlgorithm(alg) => {
+                write!(f, "unsupported signature algorithm {}", alg)
+            }
+            CertificateError::UnknownKey(key_id) => write!(f, "unknown signing key {}", key_id),
+            CertificateError::InvalidSignature => write!(f, "signature does not match"),
+            CertificateError::BrokenChain(sequence) => {
+                write!(f, "event chain is broken at sequence {}", sequence)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CertificateError {}
+
+/// JSON con las claves de los objetos ordenadas y sin espacios: los bytes que se firman.
+pub fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let fields: Vec<String> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    format!("{}:{}", Value::String(key.clone()), canonical_json(value))
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+fn decode_fixed<const N: usize>(value: &str, what: &str) -> Result<[u8; N], CertificateError> {
+    STANDARD
+        .decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| CertificateError::Malformed(format!("{} is not valid base64", what)))
+}
+
+/// Comprueba que los eventos forman una cadena continua que termina en `head_hash`.
+fn check_chain(certificate: &TraceabilityCertificate) -> Result<(), CertificateError> {
+    let mut previous: Option<&str> = None;
+    for (index, event) in certificate.events.iter().enumerate() {
+        if event.sequence != index as i64 + 1 || event.prev_hash.as_deref() != previous {
+            return Err(CertificateError::BrokenChain(event.sequence));
+        }
+        previous = Some(&event.hash);
+    }
+    if certificate.head_hash.as_deref() != previous {
+        return Err(CertificateError::BrokenChain(certificate.events.len() as i64));
+    }
+    Ok(())
+}
+
+/// Verifica sin conexión un certificado firmado contra las claves de confianza.
+pub fn verify_certificate(
+    document: &Value,
+    trusted_keys: &[TrustedKey],
+) -> Result<TraceabilityCertificate, CertificateError> {
+    let payload = document
+        .get("certificate")
+        .ok_or_else(|| CertificateError::Malformed("missing certificate".into()))?;
+    let signature: CertificateSignature = document
+        .get("signature")
+        .cloned()
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| CertificateError::Malformed(e.to_string()))?
+        .ok_or_else(|| CertificateError::Malformed("missing signature".into()))?;
+
+    if signature.algorithm != ALGORITHM {
+        return Err(CertificateError::UnsupportedAlgorithm(signature.algorithm));
+    }
+    let trusted = trusted_keys
+        .iter()
+        .find(|key| key.key_id == signature.key_id && key.algorithm == ALGORITHM)
+        .ok_or_else(|| CertificateError::UnknownKey(signature.key_id.clone()))?;
+
+    let key = VerifyingKey::from_bytes(&decode_fixed(&trusted.public_key, "public key")?)
+        .map_err(|_| CertificateError::Malformed("public key is not an Ed25519 key".into()))?;
+    let value = Signature::from_bytes(&decode_fixed(&signature.value, "signature")?);
+    key.verify_strict(canonical_json(payload).as_bytes(), &value)
+        .map_err(|_| CertificateError::InvalidSignature)?;
+
+    let certificate: TraceabilityCertificate = serde_json::from_value(payload.clone())
+        .map_err(|e| CertificateError::Malformed(e.to_string()))?;
+    check_chain(&certificate)?;
+    Ok(certificate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use serde_json::json;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32])
+    }
+
+    fn trusted() -> Vec<TrustedKey> {
+        vec![TrustedKey {
+            key_id: "2025-07".into(),
+            algorithm: ALGORITHM.into(),
+            public_key: STANDARD.encode(signing_key().verifying_key().as_bytes()),
+        }]
+    }
+
+    fn certificate() -> TraceabilityCertificate {
+        let event = |sequence: i64, prev_hash: Option<&str>, hash: &str| CertificateEvent {
+            event_id: Uuid::new_v4(),
+            sequence,
+            event_type: EventType::LotRegistered,
+            description: None,
+            event_location: None,
+            occurred_at: Utc::now(),
+            corrects_event_id: None,
+            prev_hash: prev_hash.map(String::from),
+            hash: hash.into(),
+        };
+        TraceabilityCertificate {
+            certificate_id: Uuid::new_v4(),
+            issuer: "Kairos".into(),
+            issued_at: Utc::now(),
+            lot: CertificateLot {
+                lot_id: Uuid::new_v4(),
+                lot_code: "LOT-2024-0001".into(),
+                product_name: "Tomates Cherry".into(),
+                crop_type: CropType::OrganicCertified,
+                status: LotStatus::Harvested,
+                quantity: Decimal::new(5000, 1),
+                unit_of_measure: "kg".into(),
+                estimated_harvest_date: NaiveDate::from_ymd_opt(2024, 8, 15).unwrap(),
+                actual_harvest_date: None,
+                producer_name: "Ana Pérez".into(),
+                farm_name: Some("Finca La Esperanza".into()),
+            },
+            events: vec![event(1, None, "aa"), event(2, Some("aa"), "bb")],
+            head_hash: Some("bb".into()),
+            origins: Vec::new(),
+        }
+    }
+
+    fn sign(certificate: &TraceabilityCertificate) -> Value {
+        let payload = serde_json::to_value(certificate).unwrap();
+        let signature = signing_key().sign(canonical_json(&payload).as_bytes());
+        json!({
+            "certificate": payload,
+            "signature": {
+                "algorithm": ALGORITHM,
+                "key_id": "2025-07",
+                "public_key": STANDARD.encode(signing_key().verifying_key().as_bytes()),
+                "value": STANDARD.encode(signature.to_bytes()),
+            },
+        })
+    }
+
+    #[test]
+    fn canonical_json_ignores_key_order_and_whitespace() {
+        let a: Value = serde_json::from_str(r#"{ "b": [1, {"d": 2, "c": "x"}], "a": null }"#).unwrap();
+        assert_eq!(canonical_json(&a), r#"{"a":null,"b":[1,{"c":"x","d":2}]}"#);
+    }
+
+    #[test]
+    fn signed_certificate_verifies_offline() {
+        let certificate = certificate();
+        let document = sign(&certificate);
+
+        // Reformatear el documento no cambia la firma
+        let reparsed: Value =
+            serde_json::from_str(&serde_json::to_string_pretty(&document).unwrap()).unwrap();
+        let verified = verify_certificate(&reparsed, &trusted()).unwrap();
+
+        assert_eq!(verified.certificate_id, certificate.certificate_id);
+    }
+
+    #[test]
+    fn tampering_or_unknown_keys_are_rejected() {
+        let mut document = sign(&certificate());
+        document["certificate"]["lot"]["quantity"] = json!("900.0");
+        assert_eq!(
+            verify_certificate(&document, &trusted()).unwrap_err(),
+            CertificateError::InvalidSignature
+        );
+
+        let document = sign(&certificate());
+        assert!(matches!(
+            verify_certificate(&document, &[]),
+            Err(CertificateError::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn broken_event_chain_is_rejected_even_if_signed() {
+        let mut certificate = certificate();
+        certificate.events[1].prev_hash = Some("zz".into());
+
+        assert_eq!(
+            verify_certificate(&sign(&certificate), &trusted()).unwrap_err(),
+            CertificateError::BrokenChain(2)
+        );
+    }
+}
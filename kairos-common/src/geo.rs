@@ -0,0 +1,262 @@
+//! Distancias y recuadros geográficos para la búsqueda de lotes por ubicación.
+//!
+//! Igual que en `Point`, `x` es la longitud e `y` la latitud, en grados WGS84.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Point;
+
+/// Radio medio de la Tierra en km (IUGG).
+pub const EARTH_MEAN_RADIUS_KM: f64 = 6371.0088;
+/// Máximo de resultados en una búsqueda de vecinos más cercanos.
+pub const MAX_NEAREST: i64 = 100;
+
+/// Parámetros espaciales de `GET /lots/geo`; se combinan con los filtros de `LotQuery`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LotGeoQuery {
+    /// Centro para `radius_km` y `nearest`.
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub radius_km: Option<f64>,
+    /// `min_lon,min_lat,max_lon,max_lat`, como el `bbox` de GeoJSON.
+    pub bbox: Option<String>,
+    /// Número de lotes más cercanos al centro.
+    pub nearest: Option<i64>,
+    /// `geojson` para recibir una `FeatureCollection` en lugar de JSON.
+    pub format: Option<String>,
+}
+
+impl LotGeoQuery {
+    /// Centro de la búsqueda, validado. Es obligatorio con `radius_km` o `nearest`.
+    pub fn center(&self) -> Result<Option<Point>, String> {
+        match (self.lon, self.lat) {
+            (Some(x), Some(y)) => {
+                let center = Point { x, y };
+                if !is_valid(center) {
+                    return Err("lat/lon are not a valid WGS84 position".into());
+                }
+                Ok(Some(center))
+            }
+            (None, None) if self.radius_km.is_some() || self.nearest.is_some() => {
+                Err("radius_km and nearest require lat and lon".into())
+            }
+            (None, None) => Ok(None),
+            _ => Err("lat and lon must be given together".into()),
+        }
+    }
+}
+
+/// Recuadro en grados. Si `min_lon > max_lon`, cruza el antimeridiano.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+pub fn is_valid(p: Point) -> bool {
+    p.x.is_finite()
+        && p.y.is_finite()
+        && (-180.0..=180.0).contains(&p.x)
+        && (-90.0..=90.0).contains(&p.y)
+}
+
+/// Distancia por la superficie de la esfera, en km.
+pub fn haversine_km(a: Point, b: Point) -> f64 {
+    let (lat1, lat2) = (a.y.to_radians(), b.y.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.x - a.x).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_MEAN_RADIUS_KM * h.sqrt().min(1.0).asin()
+}
+
+impl BoundingBox {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let parts: Vec<f64> = value
+            .split(',')
+            .map(|part| part.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "bbox must be min_lon,min_lat,max_lon,max_lat".to_string())?;
+        let [min_lon, min_lat, max_lon, max_lat] = parts[..] else {
+            return Err("bbox must be min_lon,min_lat,max_lon,max_lat".into());
+        };
+
+        let bbox = BoundingBox {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+        };
+        let corners = [
+            Point {
+                x: min_lon,
+                y: min_lat,
+            },
+            Point {
+                x: max_lon,
+                y: max_lat,
+            },
+        ];
+        if !corners.into_iter().all(is_valid) || min_lat > max_lat {
+            return Err("bbox is not a valid WGS84 box".into());
+        }
+        Ok(bbox)
+    }
+
+    /// Recuadro que contiene el círculo de `radius_km` alrededor de `center`; sirve de
+    /// prefiltro indexable antes de calcular la distancia exacta (Matuschek, 2010).
+    pub fn around(center: Point, radius_km: f64) -> Self {
+        let angular = radius_km / EARTH_MEAN_RADIUS_KM;
+        let d_lat = angular.to_degrees();
+        let min_lat = center.y - d_lat;
+        let max_lat = center.y + d_lat;
+        // Si el círculo alcanza un polo, abarca todas las longitudes
+        if min_lat <= -90.0 || max_lat >= 90.0 {
+            return BoundingBox {
+                min_lon: -180.0,
+                min_lat: min_lat.max(-90.0),
+                max_lon: 180.0,
+                max_lat: max_lat.min(90.0),
+            };
+        }
+
+        let sin_d_lon = angular.sin() / center.y.to_radians().cos();
+        if sin_d_lon >= 1.0 {
+            return BoundingBox {
+                min_lon: -180.0,
+                min_lat,
+                max_lon: 180.0,
+                max_lat,
+            };
+        }
+        let d_lon = sin_d_lon.asin().to_degrees();
+        let wrap = |lon: f64| {
+            if lon < -180.0 {
+                lon + 360.0
+            } else if lon > 180.0 {
+                lon - 360.0
+            } else {
+                lon
+            }
+        };
+        BoundingBox {
+            min_lon: wrap(center.x - d_lon),
+            min_lat,
+            max_lon: wrap(center.x + d_lon),
+            max_lat,
+        }
+    }
+
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.min_lon > self.max_lon
+    }
+
+    /// Divide el recuadro en los que no cruzan el antimeridiano (uno o dos).
+    pub fn split(&self) -> Vec<BoundingBox> {
+        if !self.crosses_antimeridian() {
+            return vec![*self];
+        }
+        vec![
+            BoundingBox {
+                max_lon: 180.0,
+                ..*self
+            },
+            BoundingBox {
+                min_lon: -180.0,
+                ..*self
+            },
+        ]
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.split().iter().any(|b| {
+            (b.min_lon..=b.max_lon).contains(&p.x) && (b.min_lat..=b.max_lat).contains(&p.y)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MADRID: Point = Point {
+        x: -3.7038,
+        y: 40.4168,
+    };
+    const BARCELONA: Point = Point {
+        x: 2.1734,
+        y: 41.3851,
+    };
+
+    #[test]
+    fn haversine_matches_known_distances() {
+        let km = haversine_km(MADRID, BARCELONA);
+        assert!((km - 505.0).abs() < 2.0, "{}", km);
+        assert_eq!(haversine_km(MADRID, MADRID), 0.0);
+    }
+
+    #[test]
+    fn radius_box_contains_the_circle() {
+        let bbox = BoundingBox::around(MADRID, 50.0);
+        for bearing in 0..36 {
+            let angle = (bearing as f64 * 10.0).to_radians();
+            // Punto a 49.9 km en cada rumbo, aproximado sobre el plano local
+            let p = Point {
+                x: MADRID.x
+                    + (49.9 * angle.sin() / (EARTH_MEAN_RADIUS_KM * MADRID.y.to_radians().cos()))
+                        .to_degrees(),
+                y: MADRID.y + (49.9 * angle.cos() / EARTH_MEAN_RADIUS_KM).to_degrees(),
+            };
+            assert!(haversine_km(MADRID, p) < 50.0);
+            assert!(bbox.contains(p));
+        }
+        assert!(!bbox.contains(BARCELONA));
+    }
+
+    #[test]
+    fn boxes_near_the_antimeridian_and_poles() {
+        let fiji = BoundingBox::around(Point { x: 179.9, y: -17.0 }, 30.0);
+        assert!(fiji.crosses_antimeridian());
+        assert_eq!(fiji.split().len(), 2);
+        assert!(fiji.contains(Point {
+            x: -179.95,
+            y: -17.0
+        }));
+
+        let polar = BoundingBox::around(Point { x: 10.0, y: 89.9 }, 50.0);
+        assert_eq!(
+            (polar.min_lon, polar.max_lon, polar.max_lat),
+            (-180.0, 180.0, 90.0)
+        );
+    }
+
+    #[test]
+    fn bbox_parameter_is_validated() {
+        let bbox = BoundingBox::parse("-3.8, 40.3, -3.6, 40.5").unwrap();
+        assert!(bbox.contains(MADRID));
+        assert!(BoundingBox::parse("170,-20,-170,-10")
+            .unwrap()
+            .crosses_antimeridian());
+        assert!(BoundingBox::parse("-3.8,40.3,-3.6").is_err());
+        assert!(BoundingBox::parse("-3.8,40.5,-3.6,40.3").is_err());
+        assert!(BoundingBox::parse("40.3,-3.8,40.5,200").is_err());
+    }
+
+    #[test]
+    fn center_is_required_for_radius_and_nearest() {
+        let query = LotGeoQuery {
+            radius_km: Some(50.0),
+            ..Default::default()
+        };
+        assert!(query.center().is_err());
+
+        let query = LotGeoQuery {
+            lat: Some(40.4),
+            lon: Some(-3.7),
+            nearest: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(query.center().unwrap(), Some(Point { x: -3.7, y: 40.4 }));
+    }
+}
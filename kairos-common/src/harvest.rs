@@ -0,0 +1,267 @@
+//! Conciliación de cosecha: cantidad estimada frente a la cosechada y las pérdidas
+//! registradas, por lote y agregada por cultivo o por productor.
+//!
+//! Las cantidades de un lote van en su `unit_of_measure`; los informes las suman en
+//! una unidad de masa con las conversiones de `crate::units`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    units::{Unit, QUANTITY_SCALE},
+    CropType,
+};
+
+/// Decimales de los porcentajes de los informes.
+pub const PERCENT_SCALE: u32 = 2;
+
+// Los valores coinciden con el CHECK de `lot_losses.reason`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LossReason {
+    Weather,
+    Pests,
+    /// Producto rechazado por el comprador o en la inspección de calidad.
+    Rejection,
+    /// Producto que se echa a perder antes de venderse.
+    Spoilage,
+}
+
+impl LossReason {
+    pub const ALL: [LossReason; 4] = [
+        LossReason::Weather,
+        LossReason::Pests,
+        LossReason::Rejection,
+        LossReason::Spoilage,
+    ];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            LossReason::Weather => "WEATHER",
+            LossReason::Pests => "PESTS",
+            LossReason::Rejection => "REJECTION",
+            LossReason::Spoilage => "SPOILAGE",
+        }
+    }
+}
+
+impl fmt::Display for LossReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl FromStr for LossReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LossReason::ALL
+            .into_iter()
+            .find(|reason| reason.to_str() == s)
+            .ok_or_else(|| format!("unknown loss reason '{}'", s))
+    }
+}
+
+/// Cantidad realmente cosechada, en la unidad del lote.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordHarvestRequest {
+    pub actual_quantity: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordLossRequest {
+    pub reason: LossReason,
+    /// En la unidad del lote.
+    pub quantity: Decimal,
+    pub notes: Option<String>,
+    /// Por defecto, la fecha de hoy.
+    pub occurred_on: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotLoss {
+    pub id: Uuid,
+    pub lot_id: Uuid,
+    pub reason: LossReason,
+    pub quantity: Decimal,
+    pub notes: Option<String>,
+    pub occurred_on: NaiveDate,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Respuesta de `GET /lots/{id}/harvest`, en la unidad del lote.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotHarvest {
+    pub lot_id: Uuid,
+    pub unit_of_measure: String,
+    pub estimated_quantity: Decimal,
+    pub actual_quantity: Option<Decimal>,
+    pub total_losses: Decimal,
+    /// `actual_quantity - estimated_quantity`, si ya se registró la cosecha.
+    pub variance: Option<Decimal>,
+    pub losses: Vec<LotLoss>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconciliationGroup {
+    #[default]
+    Crop,
+    Producer,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ReconciliationQuery {
+    /// `crop` (por defecto) o `producer`.
+    pub group_by: Option<ReconciliationGroup>,
+}
+
+fn round(value: Decimal, scale: u32) -> Decimal {
+    value.round_dp_with_strategy(scale, RoundingStrategy::MidpointAwayFromZero)
+}
+
+fn percent(part: Decimal, whole: Decimal) -> Option<Decimal> {
+    (!whole.is_zero()).then(|| round(part * Decimal::ONE_HUNDRED / whole, PERCENT_SCALE))
+}
+
+/// Acumula en kg lo estimado, lo cosechado y lo perdido de un grupo de lotes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct YieldTotals {
+    pub lots: i64,
+    pub estimated_kg: Decimal,
+    pub actual_kg: Decimal,
+    pub losses_kg: BTreeMap<LossReason, Decimal>,
+}
+
+impl YieldTotals {
+    pub fn add_lots(&mut self, lots: i64, estimated_kg: Decimal, actual_kg: Decimal) {
+        self.lots += lots;
+        self.estimated_kg += estimated_kg;
+        self.actual_kg += actual_kg;
+    }
+
+    pub fn add_loss(&mut self, reason: LossReason, kg: Decimal) {
+        *self.losses_kg.entry(reason).or_default() += kg;
+    }
+
+    pub fn merge(&mut self, other: &YieldTotals) {
+        self.add_lots(other.lots, other.estimated_kg, other.actual_kg);
+        for (reason, kg) in &other.losses_kg {
+            self.add_loss(*reason, *kg);
+        }
+    }
+
+    /// `unit` debe ser una unidad de masa.
+    pub fn report(&self, unit: Unit) -> YieldReport {
+        let factor = unit.kg_factor().unwrap_or(Decimal::ONE);
+        let to_unit = |kg: Decimal| round(kg / factor, QUANTITY_SCALE);
+        let losses_kg: Decimal = self.losses_kg.values().sum();
+        let variance_kg = self.actual_kg - self.estimated_kg;
+
+        YieldReport {
+            lots: self.lots,
+            unit,
+            estimated: to_unit(self.estimated_kg),
+            actual: to_unit(self.actual_kg),
+            variance: to_unit(variance_kg),
+            variance_pct: percent(variance_kg, self.estimated_kg),
+            losses: to_unit(losses_kg),
+            loss_pct: percent(losses_kg, self.estimated_kg),
+            losses_by_reason: self
+                .losses_kg
+                .iter()
+                .map(|(reason, kg)| (*reason, to_unit(*kg)))
+                .collect(),
+        }
+    }
+}
+
+/// Estimado frente a cosechado de un grupo de lotes. Los porcentajes son sobre lo
+/// estimado y faltan si no se estimó nada.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct YieldReport {
+    pub lots: i64,
+    pub unit: Unit,
+    pub estimated: Decimal,
+    pub actual: Decimal,
+    /// `actual - estimated`; negativa si se cosechó menos de lo previsto.
+    pub variance: Decimal,
+    pub variance_pct: Option<Decimal>,
+    pub losses: Decimal,
+    pub loss_pct: Option<Decimal>,
+    pub losses_by_reason: BTreeMap<LossReason, Decimal>,
+}
+
+/// Una fila del informe: un cultivo o un productor, según `group_by`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconciliationLine {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crop_type: Option<CropType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub producer_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub producer_name: Option<String>,
+    #[serde(flatten)]
+    pub report: YieldReport,
+}
+
+/// Respuesta de `GET /lots/reconciliation`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconciliationReport {
+    pub group_by: ReconciliationGroup,
+    pub lines: Vec<ReconciliationLine>,
+    pub total: YieldReport,
+    /// Lotes cosechados que quedan fuera porque su unidad no se puede pasar a masa.
+    pub unconverted_lots: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variance_and_losses_are_relative_to_the_estimate() {
+        let mut totals = YieldTotals::default();
+        totals.add_lots(2, Decimal::new(10_000, 0), Decimal::new(8_500, 0));
+        totals.add_loss(LossReason::Weather, Decimal::new(1_000, 0));
+        totals.add_loss(LossReason::Spoilage, Decimal::new(2505, 1));
+
+        let report = totals.report(Unit::Ton);
+
+        assert_eq!(report.estimated, Decimal::new(10, 0));
+        assert_eq!(report.actual, Decimal::new(85, 1));
+        assert_eq!(report.variance, Decimal::new(-15, 1));
+        assert_eq!(report.variance_pct, Some(Decimal::new(-15, 0)));
+        assert_eq!(report.losses, Decimal::new(1251, 3));
+        assert_eq!(report.loss_pct, Some(Decimal::new(1251, 2)));
+        assert_eq!(
+            report.losses_by_reason[&LossReason::Spoilage],
+            Decimal::new(251, 3)
+        );
+    }
+
+    #[test]
+    fn nothing_estimated_has_no_percentages() {
+        let report = YieldTotals::default().report(Unit::Kg);
+        assert_eq!(report.variance_pct, None);
+        assert_eq!(report.loss_pct, None);
+    }
+
+    #[test]
+    fn loss_reasons_round_trip() {
+        for reason in LossReason::ALL {
+            assert_eq!(reason.to_str().parse(), Ok(reason));
+            assert_eq!(
+                serde_json::to_value(reason).unwrap(),
+                serde_json::json!(reason.to_str())
+            );
+        }
+    }
+}
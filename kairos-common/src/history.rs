@@ -0,0 +1,162 @@
+//! Historial de versiones de un lote: una foto completa del lote por cada cambio,
+//! diferencias campo a campo y el estado en un instante dado.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Campos que cambian en cada versión y no aportan nada al comparar.
+pub const IGNORED_FIELDS: &[&str] = &["updated_at"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotVersion {
+    pub lot_id: Uuid,
+    /// Correlativo por lote desde 1. La versión 1 es el lote antes de su primera
+    /// edición.
+    pub version: i32,
+    /// El lote tal como quedó en esta versión.
+    pub snapshot: Value,
+    pub changed_fields: Vec<String>,
+    /// Falta en la versión 1, que no es obra de una edición.
+    pub actor_id: Option<Uuid>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    /// `null` si el campo no existía o no tenía valor.
+    pub from: Value,
+    pub to: Value,
+}
+
+/// Respuesta de `GET /lots/{id}/versions/diff`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotVersionDiff {
+    pub lot_id: Uuid,
+    pub from: i32,
+    pub to: i32,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct VersionDiffQuery {
+    pub from: i32,
+    pub to: i32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LotAsOfQuery {
+    pub at: DateTime<Utc>,
+}
+
+/// Respuesta de `GET /lots/{id}/as-of`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotAsOf {
+    pub lot_id: Uuid,
+    pub at: DateTime<Utc>,
+    /// Versión vigente en `at`; falta si el lote nunca se ha editado.
+    pub version: Option<i32>,
+    pub lot: Value,
+}
+
+/// Campos de primer nivel que difieren entre dos fotos, en orden alfabético.
+pub fn diff(from: &Value, to: &Value) -> Vec<FieldChange> {
+    let empty = serde_json::Map::new();
+    let from = from.as_object().unwrap_or(&empty);
+    let to = to.as_object().unwrap_or(&empty);
+
+    let mut fields: Vec<&String> = from.keys().chain(to.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter(|field| !IGNORED_FIELDS.contains(&field.as_str()))
+        .filter_map(|field| {
+            let before = from.get(field).unwrap_or(&Value::Null);
+            let after = to.get(field).unwrap_or(&Value::Null);
+            (before != after).then(|| FieldChange {
+                field: field.clone(),
+                from: before.clone(),
+                to: after.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Versión vigente en `at`: la última registrada hasta entonces o, si todas son
+/// posteriores, la primera, que guarda el estado previo a cualquier edición.
+/// `versions` debe venir ordenado por `version`.
+pub fn version_at(versions: &[LotVersion], at: DateTime<Utc>) -> Option<&LotVersion> {
+    versions
+        .iter()
+        .rev()
+        .find(|v| v.recorded_at <= at)
+        .or_else(|| versions.first())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use serde_json::json;
+
+    fn version(version: i32, recorded_at: DateTime<Utc>) -> LotVersion {
+        LotVersion {
+            lot_id: Uuid::nil(),
+            version,
+            snapshot: json!({ "version": version }),
+            changed_fields: vec![],
+            actor_id: None,
+            recorded_at,
+        }
+    }
+
+    #[test]
+    fn diff_reports_changed_added_and_removed_fields() {
+        let from = json!({
+            "estimated_quantity": "500.000",
+            "estimated_harvest_date": "2024-08-15",
+            "additional_description": "Invernadero 2",
+            "updated_at": "2024-07-01T10:00:00Z",
+        });
+        let to = json!({
+            "estimated_quantity": "450.000",
+            "estimated_harvest_date": "2024-08-15",
+            "plot_id": "b7f0",
+            "updated_at": "2024-07-02T10:00:00Z",
+        });
+
+        let changes = diff(&from, &to);
+
+        let fields: Vec<_> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, ["additional_description", "estimated_quantity", "plot_id"]);
+        assert_eq!(changes[0].to, Value::Null);
+        assert_eq!(changes[1].from, json!("500.000"));
+        assert_eq!(changes[2].from, Value::Null);
+    }
+
+    #[test]
+    fn identical_snapshots_have_no_changes() {
+        let lot = json!({ "lot_code": "KRS-1", "updated_at": "2024-07-01T10:00:00Z" });
+        assert!(diff(&lot, &lot).is_empty());
+    }
+
+    #[test]
+    fn version_at_falls_back_to_the_original_state() {
+        let start = Utc::now();
+        let versions = vec![
+            version(1, start),
+            version(2, start + Duration::hours(1)),
+            version(3, start + Duration::hours(2)),
+        ];
+
+        let at = |hours: i64| version_at(&versions, start + Duration::minutes(hours * 60 + 30));
+        assert_eq!(at(1).unwrap().version, 2);
+        assert_eq!(at(5).unwrap().version, 3);
+        assert_eq!(at(-2).unwrap().version, 1);
+        assert!(version_at(&[], start).is_none());
+    }
+}
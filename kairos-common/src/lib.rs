@@ -4,6 +4,13 @@ use chrono::{DateTime, Utc};
 use std::fmt;
 use std::str::FromStr;
 
+pub mod certificate;
+pub mod geo;
+pub mod harvest;
+pub mod history;
+pub mod plot;
+pub mod units;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash, Default)]
 pub enum Language {
     English,
@@ -65,33 +72,140 @@ impl FromStr for Language {
     }
 }
 
+// Los valores coinciden con `event_type_enum` en la base de datos
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum EventType {
-    Planting,
-    Fertilization,
+    LotRegistered,
+    FertilizerApplication,
     Irrigation,
     PestControl,
-    Harvest,
-    Other,
+    HarvestStarted,
+    HarvestCompleted,
+    LotUpdated,
+    QualityInspection,
+}
+
+// Los eventos que genera el sistema (alta del lote, cambios de estado) son el
+// registro del ciclo de vida y no se pueden editar ni borrar; los de campo sí.
+impl EventType {
+    pub fn is_system_generated(&self) -> bool {
+        matches!(
+            self,
+            EventType::LotRegistered | EventType::HarvestCompleted | EventType::LotUpdated
+        )
+    }
+
+    pub fn is_editable(&self) -> bool {
+        !self.is_system_generated()
+    }
+
+    /// Coincide con `check_description_required` de la tabla `events`.
+    pub fn requires_description(&self) -> bool {
+        matches!(
+            self,
+            EventType::FertilizerApplication | EventType::PestControl | EventType::QualityInspection
+        )
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            EventType::LotRegistered => "LOT_REGISTERED",
+            EventType::FertilizerApplication => "FERTILIZER_APPLICATION",
+            EventType::Irrigation => "IRRIGATION",
+            EventType::PestControl => "PEST_CONTROL",
+            EventType::HarvestStarted => "HARVEST_STARTED",
+            EventType::HarvestCompleted => "HARVEST_COMPLETED",
+            EventType::LotUpdated => "LOT_UPDATED",
+            EventType::QualityInspection => "QUALITY_INSPECTION",
+        }
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
 }
 
+// Los valores coinciden con `crop_type_enum` en la base de datos
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum CropType {
-    Grain,
-    Vegetable,
-    Fruit,
-    Other,
+    Conventional,
+    AgroecologicalUncertified,
+    OrganicCertified,
+    Hydroponic,
+}
+
+impl CropType {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            CropType::Conventional => "CONVENTIONAL",
+            CropType::AgroecologicalUncertified => "AGROECOLOGICAL_UNCERTIFIED",
+            CropType::OrganicCertified => "ORGANIC_CERTIFIED",
+            CropType::Hydroponic => "HYDROPONIC",
+        }
+    }
 }
 
+impl fmt::Display for CropType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+// Los valores coinciden con `lot_status_enum` en la base de datos
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum LotStatus {
     Registered,
-    InProgress,
+    Growing,
+    ReadyForHarvest,
     Harvested,
-    Completed,
+    Sold,
     Cancelled,
 }
 
+// Ciclo de vida del lote: REGISTERED → GROWING → READY_FOR_HARVEST → HARVESTED → SOLD,
+// con CANCELLED posible desde cualquier estado no terminal.
+impl LotStatus {
+    pub fn allowed_transitions(&self) -> &'static [LotStatus] {
+        match self {
+            LotStatus::Registered => &[LotStatus::Growing, LotStatus::Cancelled],
+            LotStatus::Growing => &[LotStatus::ReadyForHarvest, LotStatus::Cancelled],
+            LotStatus::ReadyForHarvest => &[LotStatus::Harvested, LotStatus::Cancelled],
+            LotStatus::Harvested => &[LotStatus::Sold, LotStatus::Cancelled],
+            LotStatus::Sold | LotStatus::Cancelled => &[],
+        }
+    }
+
+    pub fn can_transition_to(&self, next: LotStatus) -> bool {
+        self.allowed_transitions().contains(&next)
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.allowed_transitions().is_empty()
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            LotStatus::Registered => "REGISTERED",
+            LotStatus::Growing => "GROWING",
+            LotStatus::ReadyForHarvest => "READY_FOR_HARVEST",
+            LotStatus::Harvested => "HARVESTED",
+            LotStatus::Sold => "SOLD",
+            LotStatus::Cancelled => "CANCELLED",
+        }
+    }
+}
+
+impl fmt::Display for LotStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Point {
     pub x: f64,
@@ -114,6 +228,9 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+/// Unidades de medida admitidas; coincide con el CHECK de `lots.unit_of_measure`.
+pub const UNITS_OF_MEASURE: [&str; 5] = ["kg", "ton", "unit", "box", "sack"];
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateLotRequest {
     pub product_name: String,
@@ -212,10 +329,294 @@ pub struct UpdateEventRequest {
     pub metadata: Option<serde_json::Value>,
 }
 
+/// Parte de un lote que se separa como lote hijo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotPortion {
+    pub quantity: rust_decimal::Decimal,
+    /// Por defecto, el del lote original.
+    pub product_name: Option<String>,
+    pub additional_description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitLotRequest {
+    pub portions: Vec<LotPortion>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeSource {
+    pub lot_id: Uuid,
+    /// Cantidad que se aporta; por defecto, todo lo disponible.
+    pub quantity: Option<rust_decimal::Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeLotsRequest {
+    pub sources: Vec<MergeSource>,
+    /// Por defecto, el del primer lote de origen.
+    pub product_name: Option<String>,
+    pub additional_description: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenealogyOperation {
+    Split,
+    Merge,
+}
+
+impl GenealogyOperation {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            GenealogyOperation::Split => "split",
+            GenealogyOperation::Merge => "merge",
+        }
+    }
+}
+
+/// Lote alcanzado al recorrer la genealogía, a `depth` pasos del consultado.
+/// `via_lot_id` es el lote del paso anterior con el que está enlazado.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenealogyLink {
+    pub lot_id: Uuid,
+    pub lot_code: String,
+    pub product_name: String,
+    pub unit_of_measure: String,
+    pub via_lot_id: Uuid,
+    pub quantity: rust_decimal::Decimal,
+    pub operation: GenealogyOperation,
+    pub depth: i32,
+}
+
+/// Respuesta de `GET /lots/{id}/genealogy`: de dónde viene el lote y a dónde fue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotGenealogy {
+    pub lot_id: Uuid,
+    pub lot_code: String,
+    /// Cantidad que aún no se ha traspasado a lotes hijos.
+    pub available_quantity: rust_decimal::Decimal,
+    pub ancestors: Vec<GenealogyLink>,
+    pub descendants: Vec<GenealogyLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateShipmentRequest {
+    pub buyer_name: String,
+    pub buyer_email: Option<String>,
+    pub quantity: rust_decimal::Decimal,
+    /// Por defecto, el momento del registro.
+    pub shipped_at: Option<DateTime<Utc>>,
+    pub reference: Option<String>,
+}
+
+/// Punto de partida de una retirada: un lote, un insumo usado en algún evento
+/// (`metadata.input_product` o `metadata.inputs`) o un rango de fechas de cosecha.
+/// Con `input_product`, el rango filtra la fecha del evento.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecallCriteria {
+    pub lot_id: Option<Uuid>,
+    pub input_product: Option<String>,
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateRecallRequest {
+    pub reason: String,
+    #[serde(flatten)]
+    pub criteria: RecallCriteria,
+}
+
+/// Relación de un lote afectado con los lotes de partida.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceDirection {
+    /// Cumple el criterio de la retirada.
+    Seed,
+    /// Origen de un lote de partida; se incluye para investigar la causa.
+    Upstream,
+    /// Derivado de un lote de partida.
+    Downstream,
+}
+
+impl TraceDirection {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            TraceDirection::Seed => "seed",
+            TraceDirection::Upstream => "upstream",
+            TraceDirection::Downstream => "downstream",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AffectedLot {
+    pub lot_id: Uuid,
+    pub lot_code: String,
+    pub product_name: String,
+    pub producer_id: Uuid,
+    pub direction: TraceDirection,
+    /// Pasos de genealogía desde el lote de partida más cercano (0 para los de partida).
+    pub depth: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecallPartyType {
+    Producer,
+    Buyer,
+}
+
+impl RecallPartyType {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            RecallPartyType::Producer => "producer",
+            RecallPartyType::Buyer => "buyer",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AffectedParty {
+    pub party_type: RecallPartyType,
+    pub name: String,
+    pub email: Option<String>,
+    pub producer_id: Option<Uuid>,
+    /// Lotes por los que la parte está afectada.
+    pub lot_codes: Vec<String>,
+}
+
+/// Informe de lotes y partes afectadas por una retirada.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecallReport {
+    pub recall_id: Uuid,
+    pub reason: String,
+    pub criteria: RecallCriteria,
+    pub initiated_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub lots: Vec<AffectedLot>,
+    pub parties: Vec<AffectedParty>,
+}
+
+/// Evento EPCIS de un socio adjuntado a uno de nuestros lotes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedEpcisEvent {
+    pub epcis_event_id: String,
+    pub event_id: Uuid,
+    pub event_type: EventType,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedEpcisEvent {
+    pub epcis_event_id: Option<String>,
+    pub reason: String,
+}
+
+/// Respuesta de `POST /lots/{id}/epcis`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EpcisImportResult {
+    pub imported: Vec<ImportedEpcisEvent>,
+    pub skipped: Vec<SkippedEpcisEvent>,
+}
+
+/// Problema en una fila (o en la cabecera, fila 1) del CSV de `POST /lots/import`.
+/// `row` es la línea del fichero, como la numera una hoja de cálculo.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LotImportError {
+    pub row: usize,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedLot {
+    pub row: usize,
+    pub lot_id: Uuid,
+    pub lot_code: String,
+}
+
+/// Respuesta de `POST /lots/import`. En `dry_run` no se crea nada; fuera de él, o se
+/// crean todas las filas o ninguna.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LotImportResult {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub created: Vec<ImportedLot>,
+    pub errors: Vec<LotImportError>,
+}
+
+/// Vista pública de un lote (`GET /public/lots/{lot_code}`). Solo incluye datos
+/// que el productor publica en la etiqueta: nada de IDs internos, coordenadas ni metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicLotView {
+    pub lot_code: String,
+    pub product_name: String,
+    pub crop_type: CropType,
+    pub status: LotStatus,
+    pub farm_name: Option<String>,
+    pub estimated_harvest_date: chrono::NaiveDate,
+    pub actual_harvest_date: Option<chrono::NaiveDate>,
+    pub timeline: Vec<PublicEvent>,
+    /// Resultado de recalcular la cadena de hashes de los eventos.
+    pub chain_valid: bool,
+    pub head_hash: Option<String>,
+}
+
+/// Evento tal y como queda tras aplicar correcciones; los anulados no aparecen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicEvent {
+    pub event_type: EventType,
+    pub description: Option<String>,
+    pub event_location: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub corrected: bool,
+    pub hash: Option<String>,
+}
+
+/// Motivo por el que se rompe la cadena de hashes de un lote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ChainBreak {
+    /// Falta una posición o hay posiciones repetidas.
+    SequenceGap,
+    /// `prev_hash` no coincide con el hash del evento anterior.
+    PrevHashMismatch,
+    /// El contenido del evento no corresponde con su hash.
+    HashMismatch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrokenLink {
+    pub event_id: Uuid,
+    pub sequence: i64,
+    pub reason: ChainBreak,
+}
+
+/// Resultado de `GET /lots/{id}/verify`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainVerification {
+    pub lot_id: Uuid,
+    pub valid: bool,
+    pub events_checked: i64,
+    /// Eventos anteriores a la cadena que aún no se han sellado.
+    pub unsealed_events: i64,
+    pub head_hash: Option<String>,
+    pub first_broken_link: Option<BrokenLink>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct EventQuery {
     pub event_type: Option<EventType>,
     pub lot_id: Option<Uuid>,
+    /// Incluye los eventos de lotes archivados; con `lot_id` siempre se incluyen.
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Incluye los eventos de lotes borrados; solo para administradores.
+    #[serde(default)]
+    pub include_deleted: bool,
     pub sort_by: Option<String>,
     pub sort_order: Option<String>,
     pub page: Option<i64>,
@@ -241,6 +642,14 @@ pub struct LotQuery {
     pub product_name: Option<String>,
     pub status: Option<LotStatus>,
     pub crop_type: Option<CropType>,
+    /// Ventana de cosecha (real o, si no la hay, estimada), ambos extremos incluidos.
+    pub harvest_from: Option<chrono::NaiveDate>,
+    pub harvest_to: Option<chrono::NaiveDate>,
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Solo para administradores.
+    #[serde(default)]
+    pub include_deleted: bool,
     pub sort_by: Option<String>,
     pub sort_order: Option<String>,
     pub page: Option<i64>,
@@ -294,6 +703,28 @@ pub struct TokenResponse {
     pub access_token: String,
     pub token_type: String,
     pub expires_in: i64,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    pub new_password: String,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -302,3 +733,71 @@ pub enum ProducerStatus {
     Approved,
     Rejected,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ReviewProducerRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LotTransitionRequest {
+    pub to: LotStatus,
+    pub reason: Option<String>,
+    /// Solo se usa al pasar a HARVESTED; por defecto, la fecha de hoy.
+    pub actual_harvest_date: Option<chrono::NaiveDate>,
+    /// Cantidad cosechada, en la unidad del lote; solo al pasar a HARVESTED.
+    pub actual_quantity: Option<rust_decimal::Decimal>,
+}
+
+/// Archiva los lotes cerrados (SOLD o CANCELLED) de una temporada terminada.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveSeasonRequest {
+    /// Fecha de cosecha (real o, si no la hay, estimada) hasta la que llega la
+    /// temporada, incluida.
+    pub season_end: chrono::NaiveDate,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lot_lifecycle_follows_the_happy_path() {
+        assert!(LotStatus::Registered.can_transition_to(LotStatus::Growing));
+        assert!(LotStatus::Growing.can_transition_to(LotStatus::ReadyForHarvest));
+        assert!(LotStatus::ReadyForHarvest.can_transition_to(LotStatus::Harvested));
+        assert!(LotStatus::Harvested.can_transition_to(LotStatus::Sold));
+    }
+
+    #[test]
+    fn lot_lifecycle_rejects_jumps_and_reversals() {
+        assert!(!LotStatus::Registered.can_transition_to(LotStatus::Harvested));
+        assert!(!LotStatus::Growing.can_transition_to(LotStatus::Sold));
+        assert!(!LotStatus::Harvested.can_transition_to(LotStatus::Growing));
+        assert!(!LotStatus::Registered.can_transition_to(LotStatus::Registered));
+    }
+
+    #[test]
+    fn sold_and_cancelled_are_terminal() {
+        assert!(LotStatus::Sold.is_terminal());
+        assert!(LotStatus::Cancelled.is_terminal());
+        assert!(!LotStatus::Sold.can_transition_to(LotStatus::Cancelled));
+        assert!(LotStatus::Growing.can_transition_to(LotStatus::Cancelled));
+    }
+
+    #[test]
+    fn lot_status_uses_database_names_in_json() {
+        let json = serde_json::to_string(&LotStatus::ReadyForHarvest).unwrap();
+        assert_eq!(json, "\"READY_FOR_HARVEST\"");
+        assert_eq!(LotStatus::ReadyForHarvest.to_string(), "READY_FOR_HARVEST");
+    }
+
+    #[test]
+    fn system_events_are_not_editable() {
+        assert!(!EventType::LotRegistered.is_editable());
+        assert!(!EventType::HarvestCompleted.is_editable());
+        assert!(!EventType::LotUpdated.is_editable());
+        assert!(EventType::Irrigation.is_editable());
+        assert!(EventType::QualityInspection.is_editable());
+    }
+}
@@ -0,0 +1,530 @@
+//! Parcelas como polígonos WGS84 en GeoJSON (RFC 7946): validación, superficie y centroide.
+//!
+//! Las posiciones son `[longitud, latitud]`, igual que `Point` (`x` = longitud,
+//! `y` = latitud). La superficie se calcula sobre la esfera; el centroide, en el
+//! plano lon/lat, que a escala de parcela es indistinguible del geodésico.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{geo, Point};
+
+/// Radio ecuatorial WGS84 en metros.
+pub const EARTH_RADIUS_M: f64 = 6_378_137.0;
+/// Límite de posiciones por polígono (la validación de cruces es cuadrática).
+pub const MAX_POSITIONS: usize = 5_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatePlotRequest {
+    pub name: String,
+    /// `Polygon` GeoJSON, o un `Feature` cuya geometría lo sea.
+    pub boundary: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdatePlotRequest {
+    pub name: Option<String>,
+    pub boundary: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignPlotRequest {
+    /// `None` desvincula el lote de su parcela.
+    pub plot_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlotSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub area_hectares: rust_decimal::Decimal,
+    pub centroid: Point,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedPlotFeature {
+    /// Posición del `Feature` en la colección.
+    pub index: usize,
+    pub reason: String,
+}
+
+/// Resultado de `POST /plots/import`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlotImportResult {
+    pub created: Vec<PlotSummary>,
+    pub skipped: Vec<SkippedPlotFeature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GeometryError {
+    Malformed(String),
+    /// Coordenada fuera de [-180, 180] × [-90, 90] o no finita.
+    OutOfRange {
+        ring: usize,
+        position: usize,
+    },
+    UnclosedRing(usize),
+    TooFewPositions(usize),
+    TooManyPositions,
+    SelfIntersecting(usize),
+    HoleOutsideExterior(usize),
+    /// Dos anillos se cortan, se tocan o uno contiene al otro (dos huecos).
+    RingsIntersect(usize, usize),
+    CrossesAntimeridian,
+    Degenerate,
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeometryError::Malformed(reason) => write!(f, "invalid GeoJSON polygon: {}", reason),
+            GeometryError::OutOfRange { ring, position } => write!(
+                f,
+                "position {} of ring {} is not a valid WGS84 [longitude, latitude]",
+                position, ring
+            ),
+            GeometryError::UnclosedRing(ring) => {
+                write!(f, "ring {} must end at its first position", ring)
+            }
+            GeometryError::TooFewPositions(ring) => {
+                write!(f, "ring {} needs at least 3 distinct positions", ring)
+            }
+            GeometryError::TooManyPositions => {
+                write!(f, "polygon has more than {} positions", MAX_POSITIONS)
+            }
+            GeometryError::SelfIntersecting(ring) => write!(f, "ring {} intersects itself", ring),
+            GeometryError::HoleOutsideExterior(ring) => {
+                write!(f, "hole {} is not inside the exterior ring", ring)
+            }
+            GeometryError::RingsIntersect(ring, other) => {
+                write!(f, "ring {} intersects ring {}", ring, other)
+            }
+            GeometryError::CrossesAntimeridian => {
+                write!(f, "polygon must not cross the antimeridian")
+            }
+            GeometryError::Degenerate => write!(f, "polygon has no area"),
+        }
+    }
+}
+
+impl std::error::Error for GeometryError {}
+
+/// Polígono validado: anillo exterior y huecos, cerrados y sin posiciones repetidas seguidas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    exterior: Vec<Point>,
+    holes: Vec<Vec<Point>>,
+}
+
+fn parse_ring(ring: &Value, index: usize) -> Result<Vec<Point>, GeometryError> {
+    let positions = ring
+        .as_array()
+        .ok_or_else(|| GeometryError::Malformed(format!("ring {} is not an array", index)))?;
+    positions
+        .iter()
+        .enumerate()
+        .map(|(position, value)| {
+            let coords = value.as_array().filter(|c| c.len() >= 2).ok_or_else(|| {
+                GeometryError::Malformed(format!(
+                    "position {} of ring {} is not [longitude, latitude]",
+                    position, index
+                ))
+            })?;
+            match (coords[0].as_f64(), coords[1].as_f64()) {
+                (Some(x), Some(y)) => Ok(Point { x, y }),
+                _ => Err(GeometryError::OutOfRange {
+                    ring: index,
+                    position,
+                }),
+            }
+        })
+        .collect()
+}
+
+/// Doble del área con signo en el plano lon/lat (positiva si el anillo va en sentido antihorario).
+fn signed_area2(ring: &[Point]) -> f64 {
+    ring.windows(2)
+        .map(|w| w[0].x * w[1].y - w[1].x * w[0].y)
+        .sum()
+}
+
+fn orientation(a: Point, b: Point, c: Point) -> f64 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+fn on_segment(a: Point, b: Point, p: Point) -> bool {
+    p.x >= a.x.min(b.x) && p.x <= a.x.max(b.x) && p.y >= a.y.min(b.y) && p.y <= a.y.max(b.y)
+}
+
+fn segments_intersect(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let (o1, o2) = (orientation(a, b, c), orientation(a, b, d));
+    let (o3, o4) = (orientation(c, d, a), orientation(c, d, b));
+    if o1 * o2 < 0.0 && o3 * o4 < 0.0 {
+        return true;
+    }
+    (o1 == 0.0 && on_segment(a, b, c))
+        || (o2 == 0.0 && on_segment(a, b, d))
+        || (o3 == 0.0 && on_segment(c, d, a))
+        || (o4 == 0.0 && on_segment(c, d, b))
+}
+
+fn is_simple(ring: &[Point]) -> bool {
+    let segments = ring.len() - 1;
+    for i in 0..segments {
+        // Los lados contiguos comparten vértice; el último es contiguo al primero
+        for j in (i + 2)..segments {
+            if i == 0 && j == segments - 1 {
+                continue;
+            }
+            if segments_intersect(ring[i], ring[i + 1], ring[j], ring[j + 1]) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn rings_intersect(a: &[Point], b: &[Point]) -> bool {
+    a.windows(2)
+        .any(|s| b.windows(2).any(|t| segments_intersect(s[0], s[1], t[0], t[1])))
+}
+
+fn contains(ring: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Área en m² sobre la esfera (Chamberlain y Duquette, 2007).
+fn ring_area_m2(ring: &[Point]) -> f64 {
+    let sum: f64 = ring
+        .windows(2)
+        .map(|w| {
+            (w[1].x - w[0].x).to_radians()
+                * (2.0 + w[0].y.to_radians().sin() + w[1].y.to_radians().sin())
+        })
+        .sum();
+    (sum * EARTH_RADIUS_M * EARTH_RADIUS_M / 2.0).abs()
+}
+
+/// Centroide plano y área absoluta del anillo, calculados respecto a su primer vértice.
+fn ring_centroid(ring: &[Point]) -> (Point, f64) {
+    let origin = ring[0];
+    let (mut area2, mut cx, mut cy) = (0.0, 0.0, 0.0);
+    for w in ring.windows(2) {
+        let (ax, ay) = (w[0].x - origin.x, w[0].y - origin.y);
+        let (bx, by) = (w[1].x - origin.x, w[1].y - origin.y);
+        let cross = ax * by - bx * ay;
+        area2 += cross;
+        cx += (ax + bx) * cross;
+        cy += (ay + by) * cross;
+    }
+    let centroid = Point {
+        x: origin.x + cx / (3.0 * area2),
+        y: origin.y + cy / (3.0 * area2),
+    };
+    (centroid, (area2 / 2.0).abs())
+}
+
+impl Polygon {
+    pub fn new(exterior: Vec<Point>, holes: Vec<Vec<Point>>) -> Result<Self, GeometryError> {
+        let total: usize = exterior.len() + holes.iter().map(Vec::len).sum::<usize>();
+        if total > MAX_POSITIONS {
+            return Err(GeometryError::TooManyPositions);
+        }
+
+        let mut rings = Vec::with_capacity(holes.len() + 1);
+        for (index, mut ring) in std::iter::once(exterior).chain(holes).enumerate() {
+            for (position, p) in ring.iter().enumerate() {
+                if !geo::is_valid(*p) {
+                    return Err(GeometryError::OutOfRange {
+                        ring: index,
+                        position,
+                    });
+                }
+            }
+            if ring.len() < 4 {
+                return Err(GeometryError::TooFewPositions(index));
+            }
+            if ring.first() != ring.last() {
+                return Err(GeometryError::UnclosedRing(index));
+            }
+            // Los GPS repiten posiciones a menudo; no aportan nada y estorban al validar cruces
+            ring.dedup();
+            if ring.len() < 4 {
+                return Err(GeometryError::TooFewPositions(index));
+            }
+            if !is_simple(&ring) {
+                return Err(GeometryError::SelfIntersecting(index));
+            }
+            rings.push(ring);
+        }
+
+        let exterior = rings.remove(0);
+        let (min_x, max_x) = exterior.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+            (lo.min(p.x), hi.max(p.x))
+        });
+        if max_x - min_x > 180.0 {
+            return Err(GeometryError::CrossesAntimeridian);
+        }
+        if signed_area2(&exterior) == 0.0 {
+            return Err(GeometryError::Degenerate);
+        }
+        for (index, hole) in rings.iter().enumerate() {
+            if !hole.iter().all(|p| contains(&exterior, *p)) {
+                return Err(GeometryError::HoleOutsideExterior(index + 1));
+            }
+            // Con el exterior cóncavo un hueco puede tener los vértices dentro y aun así cruzarlo
+            if rings_intersect(hole, &exterior) {
+                return Err(GeometryError::RingsIntersect(0, index + 1));
+            }
+            for (other, previous) in rings[..index].iter().enumerate() {
+                if rings_intersect(hole, previous)
+                    || contains(previous, hole[0])
+                    || contains(hole, previous[0])
+                {
+                    return Err(GeometryError::RingsIntersect(other + 1, index + 1));
+                }
+            }
+        }
+
+        let polygon = Polygon {
+            exterior,
+            holes: rings,
+        };
+        if polygon.area_m2() <= 0.0 {
+            return Err(GeometryError::Degenerate);
+        }
+        Ok(polygon)
+    }
+
+    /// Acepta una geometría `Polygon` o un `Feature` que la contenga.
+    pub fn from_geojson(value: &Value) -> Result<Self, GeometryError> {
+        let geometry = match value.get("type").and_then(Value::as_str) {
+            Some("Feature") => value
+                .get("geometry")
+                .filter(|g| !g.is_null())
+                .ok_or_else(|| GeometryError::Malformed("feature has no geometry".into()))?,
+            Some(_) => value,
+            None => return Err(GeometryError::Malformed("missing type".into())),
+        };
+        match geometry.get("type").and_then(Value::as_str) {
+            Some("Polygon") => {}
+            Some(other) => {
+                return Err(GeometryError::Malformed(format!(
+                    "expected a Polygon, got {}",
+                    other
+                )))
+            }
+            None => return Err(GeometryError::Malformed("missing geometry type".into())),
+        }
+
+        let rings = geometry
+            .get("coordinates")
+            .and_then(Value::as_array)
+            .filter(|rings| !rings.is_empty())
+            .ok_or_else(|| {
+                GeometryError::Malformed("coordinates must be a list of rings".into())
+            })?;
+        let mut rings = rings
+            .iter()
+            .enumerate()
+            .map(|(index, ring)| parse_ring(ring, index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let exterior = rings.remove(0);
+        Polygon::new(exterior, rings)
+    }
+
+    /// Geometría GeoJSON con la regla de la mano derecha: exterior antihorario, huecos horarios.
+    pub fn to_geojson(&self) -> Value {
+        let ring = |ring: &[Point], counter_clockwise: bool| {
+            let mut positions: Vec<Value> = ring.iter().map(|p| json!([p.x, p.y])).collect();
+            if (signed_area2(ring) > 0.0) != counter_clockwise {
+                positions.reverse();
+            }
+            Value::Array(positions)
+        };
+        let mut rings = vec![ring(&self.exterior, true)];
+        rings.extend(self.holes.iter().map(|hole| ring(hole, false)));
+        json!({ "type": "Polygon", "coordinates": rings })
+    }
+
+    pub fn area_m2(&self) -> f64 {
+        let holes: f64 = self.holes.iter().map(|hole| ring_area_m2(hole)).sum();
+        (ring_area_m2(&self.exterior) - holes).max(0.0)
+    }
+
+    pub fn area_hectares(&self) -> f64 {
+        self.area_m2() / 10_000.0
+    }
+
+    /// Centroide del área (descontados los huecos), como `Point` lon/lat.
+    pub fn centroid(&self) -> Point {
+        let (exterior, exterior_area) = ring_centroid(&self.exterior);
+        let (mut x, mut y, mut area) = (
+            exterior.x * exterior_area,
+            exterior.y * exterior_area,
+            exterior_area,
+        );
+        for hole in &self.holes {
+            let (c, a) = ring_centroid(hole);
+            x -= c.x * a;
+            y -= c.y * a;
+            area -= a;
+        }
+        Point {
+            x: x / area,
+            y: y / area,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Un hectómetro en grados de longitud sobre el ecuador.
+    const HM: f64 = 100.0 / (EARTH_RADIUS_M * std::f64::consts::PI / 180.0);
+
+    fn square(x: f64, y: f64, side: f64) -> Value {
+        json!({
+            "type": "Polygon",
+            "coordinates": [[[x, y], [x, y + side], [x + side, y + side], [x + side, y], [x, y]]],
+        })
+    }
+
+    #[test]
+    fn hectare_square_at_the_equator() {
+        let polygon = Polygon::from_geojson(&square(0.0, 0.0, HM)).unwrap();
+
+        assert!((polygon.area_hectares() - 1.0).abs() < 0.001);
+        let centroid = polygon.centroid();
+        assert!((centroid.x - HM / 2.0).abs() < 1e-12 && (centroid.y - HM / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn output_follows_the_right_hand_rule_and_holes_reduce_area() {
+        let mut geometry = square(-3.7, 40.4, 0.01);
+        geometry["coordinates"].as_array_mut().unwrap().push(json!([
+            [-3.698, 40.402],
+            [-3.696, 40.402],
+            [-3.696, 40.404],
+            [-3.698, 40.404],
+            [-3.698, 40.402]
+        ]));
+        let polygon =
+            Polygon::from_geojson(&json!({ "type": "Feature", "geometry": geometry })).unwrap();
+        let solid = Polygon::from_geojson(&square(-3.7, 40.4, 0.01)).unwrap();
+        assert!(polygon.area_hectares() < solid.area_hectares());
+
+        let out = polygon.to_geojson();
+        let exterior = parse_ring(&out["coordinates"][0], 0).unwrap();
+        let hole = parse_ring(&out["coordinates"][1], 1).unwrap();
+        assert!(signed_area2(&exterior) > 0.0 && signed_area2(&hole) < 0.0);
+        assert_eq!(
+            Polygon::from_geojson(&out).unwrap().area_m2(),
+            polygon.area_m2()
+        );
+    }
+
+    #[test]
+    fn invalid_polygons_are_rejected() {
+        let bowtie = json!({
+            "type": "Polygon",
+            "coordinates": [[[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 1.0], [0.0, 0.0]]],
+        });
+        assert_eq!(
+            Polygon::from_geojson(&bowtie).unwrap_err(),
+            GeometryError::SelfIntersecting(0)
+        );
+
+        let open = json!({ "type": "Polygon", "coordinates": [[[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]]] });
+        assert_eq!(
+            Polygon::from_geojson(&open).unwrap_err(),
+            GeometryError::UnclosedRing(0)
+        );
+
+        // Latitud y longitud invertidas
+        assert!(matches!(
+            Polygon::from_geojson(&square(40.0, 120.0, 0.01)),
+            Err(GeometryError::OutOfRange { ring: 0, .. })
+        ));
+        let wide = json!({
+            "type": "Polygon",
+            "coordinates": [[[179.0, 0.0], [179.0, 1.0], [-179.0, 1.0], [-179.0, 0.0], [179.0, 0.0]]],
+        });
+        assert_eq!(
+            Polygon::from_geojson(&wide).unwrap_err(),
+            GeometryError::CrossesAntimeridian
+        );
+        assert!(matches!(
+            Polygon::from_geojson(&json!({ "type": "Point", "coordinates": [0.0, 0.0] })),
+            Err(GeometryError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn holes_cannot_cross_the_exterior_or_each_other() {
+        // Exterior en U: los vértices del hueco quedan dentro pero su lado cruza la muesca
+        let u_shape = json!([[0.0, 0.0], [0.0, 3.0], [1.0, 3.0], [1.0, 1.0], [2.0, 1.0], [2.0, 3.0], [3.0, 3.0], [3.0, 0.0], [0.0, 0.0]]);
+        let bridge = json!([[0.5, 2.0], [2.5, 2.0], [2.5, 2.5], [0.5, 2.5], [0.5, 2.0]]);
+        let geometry = json!({ "type": "Polygon", "coordinates": [u_shape, bridge] });
+        assert_eq!(
+            Polygon::from_geojson(&geometry).unwrap_err(),
+            GeometryError::RingsIntersect(0, 1)
+        );
+
+        let hole = |x: f64, y: f64, side: f64| {
+            json!([[x, y], [x + side, y], [x + side, y + side], [x, y + side], [x, y]])
+        };
+        let mut geometry = square(0.0, 0.0, 10.0);
+        let rings = geometry["coordinates"].as_array_mut().unwrap();
+        rings.push(hole(1.0, 1.0, 3.0));
+        rings.push(hole(2.0, 2.0, 3.0));
+        assert_eq!(
+            Polygon::from_geojson(&geometry).unwrap_err(),
+            GeometryError::RingsIntersect(1, 2)
+        );
+
+        let mut geometry = square(0.0, 0.0, 10.0);
+        let rings = geometry["coordinates"].as_array_mut().unwrap();
+        rings.push(hole(1.0, 1.0, 5.0));
+        rings.push(hole(2.0, 2.0, 1.0));
+        assert_eq!(
+            Polygon::from_geojson(&geometry).unwrap_err(),
+            GeometryError::RingsIntersect(1, 2)
+        );
+
+        let mut geometry = square(0.0, 0.0, 10.0);
+        let rings = geometry["coordinates"].as_array_mut().unwrap();
+        rings.push(hole(1.0, 1.0, 2.0));
+        rings.push(hole(5.0, 5.0, 2.0));
+        assert!(Polygon::from_geojson(&geometry).is_ok());
+    }
+
+    #[test]
+    fn repeated_gps_positions_are_ignored() {
+        let geometry = json!({
+            "type": "Polygon",
+            "coordinates": [[[0.0, 0.0], [0.0, 0.0], [0.0, 0.01], [0.01, 0.01], [0.01, 0.01], [0.01, 0.0], [0.0, 0.0]]],
+        });
+        let polygon = Polygon::from_geojson(&geometry).unwrap();
+        assert_eq!(
+            polygon.to_geojson()["coordinates"][0]
+                .as_array()
+                .unwrap()
+                .len(),
+            5
+        );
+    }
+}
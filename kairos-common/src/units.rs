@@ -0,0 +1,407 @@
+//! Unidades de medida de los lotes y conversión exacta entre ellas con `Decimal`.
+//!
+//! `kg` y `ton` (tonelada métrica) son de masa y se convierten siempre. `box` y
+//! `sack` dependen del producto: cada productor configura cuántos kg lleva una caja
+//! o un saco de cada producto. `unit` (pieza suelta) no se convierte.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Decimales de las cantidades en la base de datos (`NUMERIC(12,3)`).
+pub const QUANTITY_SCALE: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Kg,
+    Ton,
+    Unit,
+    Box,
+    Sack,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 5] = [Unit::Kg, Unit::Ton, Unit::Unit, Unit::Box, Unit::Sack];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Unit::Kg => "kg",
+            Unit::Ton => "ton",
+            Unit::Unit => "unit",
+            Unit::Box => "box",
+            Unit::Sack => "sack",
+        }
+    }
+
+    /// Kilos por unidad en las unidades de masa.
+    pub fn kg_factor(&self) -> Option<Decimal> {
+        match self {
+            Unit::Kg => Some(Decimal::ONE),
+            Unit::Ton => Some(Decimal::ONE_THOUSAND),
+            Unit::Unit | Unit::Box | Unit::Sack => None,
+        }
+    }
+
+    pub fn is_mass(&self) -> bool {
+        self.kg_factor().is_some()
+    }
+
+    /// Envases cuyo peso se configura por producto.
+    pub fn is_packaging(&self) -> bool {
+        matches!(self, Unit::Box | Unit::Sack)
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Unit::ALL
+            .into_iter()
+            .find(|unit| unit.to_str() == s)
+            .ok_or_else(|| {
+                let all: Vec<_> = Unit::ALL.iter().map(Unit::to_str).collect();
+                format!("unit must be one of {}", all.join(", "))
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// No hay peso configurado para ese envase de ese producto.
+    MissingWeight { product_name: String, unit: Unit },
+    /// `unit` no tiene equivalencia en masa.
+    NotConvertible(Unit),
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::MissingWeight { product_name, unit } => {
+                write!(f, "no {} weight is configured for {}", unit, product_name)
+            }
+            ConversionError::NotConvertible(unit) => {
+                write!(f, "{} cannot be converted to a mass", unit)
+            }
+        }
+    }
+}
+
+/// Kilos por caja o saco de cada producto, de un productor. El nombre del producto
+/// se compara sin distinguir mayúsculas ni espacios en los extremos.
+#[derive(Debug, Clone, Default)]
+pub struct PackagingWeights {
+    weights: HashMap<(String, Unit), Decimal>,
+}
+
+fn product_key(product_name: &str) -> String {
+    product_name.trim().to_lowercase()
+}
+
+impl PackagingWeights {
+    pub fn insert(&mut self, product_name: &str, unit: Unit, kg_per_unit: Decimal) {
+        self.weights
+            .insert((product_key(product_name), unit), kg_per_unit);
+    }
+
+    pub fn kg_per_unit(&self, product_name: &str, unit: Unit) -> Option<Decimal> {
+        self.weights
+            .get(&(product_key(product_name), unit))
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quantity {
+    pub amount: Decimal,
+    pub unit: Unit,
+}
+
+fn round(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(QUANTITY_SCALE, RoundingStrategy::MidpointAwayFromZero)
+}
+
+impl Quantity {
+    pub fn new(amount: Decimal, unit: Unit) -> Self {
+        Quantity { amount, unit }
+    }
+
+    /// Kilos por unidad de `unit` para `product_name`.
+    fn kg_per(
+        unit: Unit,
+        product_name: &str,
+        weights: &PackagingWeights,
+    ) -> Result<Decimal, ConversionError> {
+        match unit.kg_factor() {
+            Some(factor) => Ok(factor),
+            None if unit.is_packaging() => {
+                weights.kg_per_unit(product_name, unit).ok_or_else(|| {
+                    ConversionError::MissingWeight {
+                        product_name: product_name.trim().to_string(),
+                        unit,
+                    }
+                })
+            }
+            None => Err(ConversionError::NotConvertible(unit)),
+        }
+    }
+
+    /// Cantidad en kg, sin redondear: multiplicar por el factor es exacto.
+    pub fn to_kg(
+        &self,
+        product_name: &str,
+        weights: &PackagingWeights,
+    ) -> Result<Decimal, ConversionError> {
+        Ok(self.amount * Self::kg_per(self.unit, product_name, weights)?)
+    }
+
+    /// Convierte pasando por kg. Si hay que dividir, se redondea a `QUANTITY_SCALE`.
+    pub fn convert(
+        &self,
+        to: Unit,
+        product_name: &str,
+        weights: &PackagingWeights,
+    ) -> Result<Quantity, ConversionError> {
+        if to == self.unit {
+            return Ok(*self);
+        }
+        let kg = self.to_kg(product_name, weights)?;
+        let per = Self::kg_per(to, product_name, weights)?;
+        Ok(Quantity::new(round(kg / per), to))
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount.normalize(), self.unit)
+    }
+}
+
+/// Parámetro de los listados y resúmenes de lotes.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct QuantityQuery {
+    /// Unidad de masa de los totales; por defecto, `kg`.
+    pub normalize_to: Option<Unit>,
+}
+
+impl QuantityQuery {
+    pub fn target(&self) -> Result<Unit, String> {
+        match self.normalize_to.unwrap_or(Unit::Kg) {
+            unit if unit.is_mass() => Ok(unit),
+            unit => Err(format!("normalize_to must be kg or ton, not {}", unit)),
+        }
+    }
+}
+
+/// Cantidad que no se pudo sumar al total, agrupada por producto y unidad.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnconvertedQuantity {
+    pub product_name: String,
+    pub quantity: Quantity,
+    pub reason: String,
+}
+
+/// Suma de cantidades en una unidad de masa. Lo que no se puede convertir se
+/// informa aparte en lugar de mezclarlo con el total.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuantityTotals {
+    pub total: Quantity,
+    pub unconverted: Vec<UnconvertedQuantity>,
+}
+
+/// Acumula cantidades de distintos productos y unidades en `QuantityTotals`.
+#[derive(Debug, Clone)]
+pub struct QuantityAggregator<'a> {
+    target: Unit,
+    weights: &'a PackagingWeights,
+    kg: Decimal,
+    unconverted: BTreeMap<(String, Unit), (Decimal, String)>,
+}
+
+impl<'a> QuantityAggregator<'a> {
+    /// `target` debe ser una unidad de masa (ver `QuantityQuery::target`).
+    pub fn new(target: Unit, weights: &'a PackagingWeights) -> Self {
+        QuantityAggregator {
+            target,
+            weights,
+            kg: Decimal::ZERO,
+            unconverted: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, product_name: &str, quantity: Quantity) {
+        match quantity.to_kg(product_name, self.weights) {
+            Ok(kg) => self.kg += kg,
+            Err(e) => {
+                let entry = self
+                    .unconverted
+                    .entry((product_name.trim().to_string(), quantity.unit))
+                    .or_insert_with(|| (Decimal::ZERO, e.to_string()));
+                entry.0 += quantity.amount;
+            }
+        }
+    }
+
+    pub fn finish(self) -> QuantityTotals {
+        let factor = self.target.kg_factor().unwrap_or(Decimal::ONE);
+        QuantityTotals {
+            total: Quantity::new(round(self.kg / factor), self.target),
+            unconverted: self
+                .unconverted
+                .into_iter()
+                .map(
+                    |((product_name, unit), (amount, reason))| UnconvertedQuantity {
+                        product_name,
+                        quantity: Quantity::new(amount, unit),
+                        reason,
+                    },
+                )
+                .collect(),
+        }
+    }
+}
+
+/// Peso de un envase de un producto, configurado por el productor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackagingWeight {
+    pub id: Uuid,
+    pub producer_id: Uuid,
+    pub product_name: String,
+    pub unit: Unit,
+    pub kg_per_unit: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetPackagingWeightRequest {
+    pub product_name: String,
+    /// `box` o `sack`.
+    pub unit: Unit,
+    pub kg_per_unit: Decimal,
+}
+
+/// Totales de los lotes de un producto en `GET /lots/summary`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductQuantitySummary {
+    pub product_name: String,
+    pub lots: i64,
+    pub totals: QuantityTotals,
+}
+
+/// Respuesta de `GET /lots/summary`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LotQuantitySummary {
+    pub lots: i64,
+    pub totals: QuantityTotals,
+    pub products: Vec<ProductQuantitySummary>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weights() -> PackagingWeights {
+        let mut weights = PackagingWeights::default();
+        weights.insert("Tomate pera", Unit::Box, Decimal::new(125, 1));
+        weights.insert("Papa", Unit::Sack, Decimal::new(50, 0));
+        weights
+    }
+
+    fn qty(amount: i64, scale: u32, unit: Unit) -> Quantity {
+        Quantity::new(Decimal::new(amount, scale), unit)
+    }
+
+    #[test]
+    fn mass_units_convert_exactly() {
+        let none = PackagingWeights::default();
+        assert_eq!(
+            qty(2500, 0, Unit::Kg).convert(Unit::Ton, "Maíz", &none),
+            Ok(qty(25, 1, Unit::Ton))
+        );
+        // Igual que en la base de datos, como mucho 3 decimales
+        assert_eq!(
+            qty(12505, 1, Unit::Kg).convert(Unit::Ton, "Maíz", &none),
+            Ok(qty(1251, 3, Unit::Ton))
+        );
+        assert_eq!(
+            qty(25, 1, Unit::Ton).to_kg("Maíz", &none),
+            Ok(Decimal::new(2500, 0))
+        );
+    }
+
+    #[test]
+    fn packaging_uses_the_producers_weight_for_the_product() {
+        let weights = weights();
+        assert_eq!(
+            qty(4, 0, Unit::Box).to_kg("  tomate PERA ", &weights),
+            Ok(Decimal::new(50, 0))
+        );
+        // 100 kg son 8 cajas de 12,5 kg
+        assert_eq!(
+            qty(100, 0, Unit::Kg).convert(Unit::Box, "Tomate pera", &weights),
+            Ok(qty(8, 0, Unit::Box))
+        );
+        // Las divisiones inexactas se redondean a 3 decimales
+        assert_eq!(
+            qty(1, 0, Unit::Sack)
+                .convert(Unit::Box, "Papa", &{
+                    let mut w = weights.clone();
+                    w.insert("Papa", Unit::Box, Decimal::new(3, 0));
+                    w
+                })
+                .unwrap()
+                .amount,
+            Decimal::new(16667, 3)
+        );
+        assert_eq!(
+            qty(1, 0, Unit::Box).to_kg("Papa", &weights),
+            Err(ConversionError::MissingWeight {
+                product_name: "Papa".into(),
+                unit: Unit::Box
+            })
+        );
+        assert_eq!(
+            qty(1, 0, Unit::Unit).to_kg("Lechuga", &weights),
+            Err(ConversionError::NotConvertible(Unit::Unit))
+        );
+    }
+
+    #[test]
+    fn totals_keep_what_cannot_be_converted_apart() {
+        let weights = weights();
+        let mut totals = QuantityAggregator::new(Unit::Ton, &weights);
+        totals.add("Tomate pera", qty(80, 0, Unit::Box));
+        totals.add("Papa", qty(1500, 0, Unit::Kg));
+        totals.add("Papa", qty(2, 0, Unit::Ton));
+        totals.add("Lechuga", qty(300, 0, Unit::Unit));
+        totals.add("Lechuga", qty(200, 0, Unit::Unit));
+        let totals = totals.finish();
+
+        assert_eq!(totals.total, qty(45, 1, Unit::Ton));
+        assert_eq!(totals.unconverted.len(), 1);
+        assert_eq!(totals.unconverted[0].quantity, qty(500, 0, Unit::Unit));
+    }
+
+    #[test]
+    fn units_match_the_lot_constraint() {
+        let names: Vec<_> = Unit::ALL.iter().map(Unit::to_str).collect();
+        assert_eq!(names, crate::UNITS_OF_MEASURE);
+        assert_eq!("sack".parse::<Unit>(), Ok(Unit::Sack));
+        assert!("litre".parse::<Unit>().is_err());
+        assert!(QuantityQuery {
+            normalize_to: Some(Unit::Box)
+        }
+        .target()
+        .is_err());
+    }
+}